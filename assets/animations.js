@@ -0,0 +1,164 @@
+// Draw animations besides the wheel. Every animation shows the names of the
+// spin instruction, ends on `instruction.winner` and calls `done` afterwards.
+(function (global) {
+	"use strict";
+
+	function element(tag, class_name, text) {
+		const el = document.createElement(tag);
+		el.className = class_name;
+		if (text !== undefined) {
+			el.textContent = text;
+		}
+		return el;
+	}
+
+	function shuffle(array) {
+		for (let i = array.length - 1; i > 0; i--) {
+			const j = Math.floor(Math.random() * (i + 1));
+			[array[i], array[j]] = [array[j], array[i]];
+		}
+		return array;
+	}
+
+	// A strip of names that slows down until the winner is in the middle of the window
+	function reel(container, instruction, done, horizontal) {
+		const names = instruction.participants;
+		// repeat the names, so that the strip moves long enough
+		const loops = Math.max(3, Math.ceil(60 / names.length));
+		const view = element('div', horizontal ? 'reel horizontal' : 'reel');
+		const strip = element('div', 'reel-strip');
+		for (let loop = 0; loop < loops; loop++) {
+			for (const name of names) {
+				strip.appendChild(element('div', 'reel-item', name));
+			}
+		}
+		view.appendChild(strip);
+		container.appendChild(view);
+
+		const target = strip.children[(loops - 1) * names.length + instruction.winner];
+		strip.addEventListener('transitionend', () => {
+			target.classList.add('winner');
+			done();
+		}, {once: true});
+
+		setTimeout(() => {
+			// reading the offsets forces a layout, so the transition starts from 0
+			const offset = horizontal
+				? target.offsetLeft + target.offsetWidth / 2 - view.clientWidth / 2
+				: target.offsetTop + target.offsetHeight / 2 - view.clientHeight / 2;
+			strip.style.transition = 'transform 6s cubic-bezier(0.1, 0.6, 0.2, 1)';
+			strip.style.transform = horizontal
+				? `translateX(${-offset}px)`
+				: `translateY(${-offset}px)`;
+		}, 1000);
+	}
+
+	// The names are shown, turned face down, shuffled and the winner's card is revealed
+	function card_shuffle(container, instruction, done) {
+		const grid = element('div', 'cards');
+		const cards = instruction.participants.map(name => {
+			const card = element('div', 'card face-up');
+			card.appendChild(element('div', 'card-back', '?'));
+			card.appendChild(element('div', 'card-front', name));
+			grid.appendChild(card);
+			return card;
+		});
+		container.appendChild(grid);
+
+		const rounds = 6;
+		let round = 0;
+		const move = () => {
+			round += 1;
+			if (round > rounds) {
+				cards.forEach(card => card.style.transform = '');
+				setTimeout(reveal, 800);
+				return;
+			}
+			const width = grid.clientWidth;
+			const height = grid.clientHeight;
+			for (const card of cards) {
+				const dx = (Math.random() - 0.5) * width * 0.5;
+				const dy = (Math.random() - 0.5) * height * 0.5;
+				card.style.transform = `translate(${dx}px, ${dy}px)`;
+			}
+			setTimeout(move, 500);
+		};
+		const reveal = () => {
+			cards[instruction.winner].classList.add('face-up', 'winner');
+			setTimeout(() => {
+				cards.forEach(card => card.classList.add('face-up'));
+				done();
+			}, 1500);
+		};
+
+		setTimeout(() => {
+			cards.forEach(card => card.classList.remove('face-up'));
+			setTimeout(move, 800);
+		}, 2000);
+	}
+
+	// Every round pairs up the remaining names and eliminates one of each pair
+	function elimination(container, instruction, done) {
+		const grid = element('div', 'bracket');
+		const entrants = instruction.participants.map(name => {
+			const entrant = element('div', 'entrant', name);
+			grid.appendChild(entrant);
+			return entrant;
+		});
+		container.appendChild(grid);
+
+		let remaining = entrants.map((_, i) => i);
+		const round = () => {
+			if (remaining.length <= 1) {
+				entrants[instruction.winner].classList.add('winner');
+				done();
+				return;
+			}
+			const next = [];
+			shuffle(remaining);
+			for (let i = 0; i < remaining.length; i += 2) {
+				const pair = remaining.slice(i, i + 2);
+				const keep = pair.includes(instruction.winner)
+					? instruction.winner
+					: pair[Math.floor(Math.random() * pair.length)];
+				pair.filter(p => p !== keep).forEach(p => entrants[p].classList.add('eliminated'));
+				next.push(keep);
+			}
+			remaining = next;
+			setTimeout(round, 1500);
+		};
+		setTimeout(round, 2000);
+	}
+
+	// Opens a mystery box over the winner, which flickers through the possible prizes first
+	function reveal_prize(container, reveal, done) {
+		const box = element('div', 'mystery-box', '?');
+		container.appendChild(box);
+
+		let flicker = 0;
+		const teasers = reveal.teasers.length > 0 ? reveal.teasers : [reveal.prize];
+		const timer = setInterval(() => {
+			box.textContent = `Ӿ ${teasers[flicker % teasers.length]}`;
+			flicker += 1;
+		}, 150);
+
+		setTimeout(() => {
+			clearInterval(timer);
+			box.textContent = `Ӿ ${reveal.prize}`;
+			box.classList.add('opened');
+			if (reveal.big_win) {
+				box.classList.add('big-win');
+			}
+			done();
+		}, 3000);
+	}
+
+	global.revealPrize = reveal_prize;
+
+	global.drawAnimations = {
+		slot_reel: (container, instruction, done) => reel(container, instruction, done, false),
+		name_scroll: (container, instruction, done) => reel(container, instruction, done, true),
+		card_shuffle: card_shuffle,
+		elimination: elimination,
+	};
+})(window);
@@ -0,0 +1,42 @@
+// Shared code of the overlay pages in /overlays/
+
+// Calls `on_event` for every overlay event. The browser reconnects automatically.
+// Names and chat are only sent to overlays that are opened with `?token=<spinner token>`,
+// unless RAFFLE_PUBLIC_NAMES is on
+function connect_events(on_event) {
+	const token = new URLSearchParams(location.search).get("token");
+	const events = new EventSource(token ? `/events?token=${encodeURIComponent(token)}` : "/events");
+	events.onmessage = (message) => on_event(JSON.parse(message.data));
+	return events;
+}
+
+function apply_theme(theme) {
+	const style = document.documentElement.style;
+	style.setProperty('--accent', theme.segment_colors[0] || '#c7160c');
+	style.setProperty('--accent-text', theme.label_colors[0] || '#fff');
+	style.setProperty('--font', `'${theme.font_family}', sans-serif`);
+}
+
+function format_countdown(seconds) {
+	const minutes = Math.floor(seconds / 60);
+	const rest = seconds % 60;
+	return `${minutes}:${rest.toString().padStart(2, '0')}`;
+}
+
+// Adds an entry to a list and removes it again after `lifetime_ms`
+function add_entry(list, element, max_entries, lifetime_ms) {
+	list.prepend(element);
+	while (list.children.length > max_entries) {
+		list.lastElementChild.remove();
+	}
+	if (lifetime_ms) {
+		setTimeout(() => element.remove(), lifetime_ms);
+	}
+}
+
+function text_element(tag, class_name, text) {
+	const element = document.createElement(tag);
+	element.className = class_name;
+	element.textContent = text;
+	return element;
+}
@@ -0,0 +1,176 @@
+// Minimal self-hosted stand-in for the spin-wheel library
+// (https://github.com/CrazyTim/spin-wheel, MIT license). It is NOT the upstream
+// code: it implements the subset of `spinWheel.Wheel` that the overlay uses:
+// drawing the items, an overlay image, `spinToItem` and the `onRest` callback.
+// TODO: replace this file with the upstream spin-wheel@5.0.2
+// dist/spin-wheel-iife.js and its license header. Until then the upstream
+// file can be used by putting it into RAFFLE_ASSETS_DIR as spin-wheel.js.
+(function (global) {
+	"use strict";
+
+	const defaults = {
+		radius: 0.95,
+		rotation: 0,
+		items: [],
+		itemBackgroundColors: ['#fff'],
+		itemLabelColors: ['#000'],
+		itemLabelFont: 'sans-serif',
+		itemLabelFontSizeMax: 500,
+		itemLabelRadius: 0.85,
+		itemLabelRadiusMax: 0.2,
+		itemLabelRotation: 0,
+		itemLabelBaselineOffset: 0,
+		lineColor: '#000',
+		lineWidth: 1,
+		overlayImage: null,
+		onRest: null,
+	};
+
+	function ease_out_cubic(t) {
+		return 1 - Math.pow(1 - t, 3);
+	}
+
+	function modulo(n, m) {
+		return ((n % m) + m) % m;
+	}
+
+	class Wheel {
+		constructor(container, props) {
+			this.props = Object.assign({}, defaults, props);
+			this.rotation = this.props.rotation;
+			this.canvas = document.createElement('canvas');
+			this.canvas.style.display = 'block';
+			this.canvas.style.width = '100%';
+			this.canvas.style.height = '100%';
+			container.appendChild(this.canvas);
+			this.context = this.canvas.getContext('2d');
+			this.resize();
+			this.onResize = () => this.resize();
+			window.addEventListener('resize', this.onResize);
+		}
+
+		get items() {
+			return this.props.items;
+		}
+
+		resize() {
+			const ratio = window.devicePixelRatio || 1;
+			this.canvas.width = this.canvas.clientWidth * ratio;
+			this.canvas.height = this.canvas.clientHeight * ratio;
+			this.draw();
+		}
+
+		remove() {
+			window.removeEventListener('resize', this.onResize);
+			this.canvas.remove();
+		}
+
+		// Item 0 starts at 12 o'clock, the following items are arranged clockwise
+		itemAngle() {
+			return 360 / Math.max(this.items.length, 1);
+		}
+
+		// Rotates the wheel so that the given item stops at 12 o'clock
+		spinToItem(index, duration, spinToCenter, revolutions, direction, easing) {
+			const arc = this.itemAngle();
+			const offset = spinToCenter ? arc / 2 : arc * (0.1 + Math.random() * 0.8);
+			const target = modulo(-(index * arc + offset), 360);
+			const dir = direction === -1 ? -1 : 1;
+			const delta = dir === 1
+				? modulo(target - this.rotation, 360)
+				: -modulo(this.rotation - target, 360);
+			this.animate(this.rotation + dir * (revolutions || 0) * 360 + delta, duration, easing || ease_out_cubic);
+		}
+
+		animate(target, duration, easing) {
+			const start = this.rotation;
+			const started = performance.now();
+			const frame = (now) => {
+				const t = duration > 0 ? Math.min((now - started) / duration, 1) : 1;
+				this.rotation = start + (target - start) * easing(t);
+				this.draw();
+				if (t < 1) {
+					requestAnimationFrame(frame);
+				} else {
+					this.rotation = modulo(target, 360);
+					this.rest();
+				}
+			};
+			requestAnimationFrame(frame);
+		}
+
+		rest() {
+			if (this.props.onRest) {
+				const arc = this.itemAngle();
+				const currentIndex = Math.floor(modulo(-this.rotation, 360) / arc);
+				this.props.onRest({type: 'rest', currentIndex: currentIndex, rotation: this.rotation});
+			}
+		}
+
+		draw() {
+			const ctx = this.context;
+			const width = this.canvas.width;
+			const height = this.canvas.height;
+			const size = Math.min(width, height);
+			const cx = width / 2;
+			const cy = height / 2;
+			const radius = size / 2 * this.props.radius;
+			const arc = this.itemAngle() * Math.PI / 180;
+			const rotation = this.rotation * Math.PI / 180;
+
+			ctx.clearRect(0, 0, width, height);
+			this.items.forEach((item, i) => {
+				const start = rotation + i * arc - Math.PI / 2;
+				ctx.beginPath();
+				ctx.moveTo(cx, cy);
+				ctx.arc(cx, cy, radius, start, start + arc);
+				ctx.closePath();
+				ctx.fillStyle = item.backgroundColor
+					|| this.props.itemBackgroundColors[i % this.props.itemBackgroundColors.length];
+				ctx.fill();
+				if (this.props.lineWidth > 0) {
+					ctx.lineWidth = this.props.lineWidth;
+					ctx.strokeStyle = this.props.lineColor;
+					ctx.stroke();
+				}
+				this.drawLabel(item, i, cx, cy, radius, start + arc / 2, arc);
+			});
+
+			const overlay = this.props.overlayImage;
+			if (overlay && overlay.complete) {
+				ctx.drawImage(overlay, cx - size / 2, cy - size / 2, size, size);
+			}
+		}
+
+		drawLabel(item, i, cx, cy, radius, angle, arc) {
+			if (!item.label) {
+				return;
+			}
+			const ctx = this.context;
+			const outer = radius * this.props.itemLabelRadius;
+			const inner = radius * this.props.itemLabelRadiusMax;
+			const maxWidth = Math.max(outer - inner, 1);
+			// The label must fit into the height of its segment
+			let fontSize = Math.min(2 * outer * Math.sin(Math.min(arc, Math.PI) / 2) * 0.8,
+				this.props.itemLabelFontSizeMax);
+			ctx.font = `${fontSize}px ${this.props.itemLabelFont}`;
+			const width = ctx.measureText(item.label).width;
+			if (width > maxWidth) {
+				fontSize = fontSize * maxWidth / width;
+				ctx.font = `${fontSize}px ${this.props.itemLabelFont}`;
+			}
+
+			ctx.save();
+			ctx.translate(cx, cy);
+			ctx.rotate(angle + this.props.itemLabelRotation * Math.PI / 180);
+			ctx.textAlign = 'right';
+			ctx.textBaseline = 'middle';
+			ctx.fillStyle = item.labelColor
+				|| this.props.itemLabelColors[i % this.props.itemLabelColors.length];
+			ctx.fillText(item.label, outer, this.props.itemLabelBaselineOffset * fontSize);
+			ctx.restore();
+		}
+	}
+
+	global.spinWheel = {Wheel: Wheel};
+})(window);
@@ -0,0 +1,433 @@
+use crate::chat_messages::Platform;
+use rsnano_core::Account;
+use std::collections::{HashMap, VecDeque};
+
+/// Something in a chat message that can be turned into a Nano account
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub(crate) enum AddressCandidate {
+    Account(Account),
+    Alias(Alias),
+}
+
+/// A human readable name like `@name`, `name@domain.com` or `name.xno`
+#[derive(PartialEq, Eq, Debug, Clone, Hash)]
+pub(crate) struct Alias {
+    pub name: String,
+    pub domain: Option<String>,
+}
+
+impl Alias {
+    pub fn new(name: impl Into<String>, domain: Option<&str>) -> Self {
+        Self {
+            name: name.into().to_ascii_lowercase(),
+            domain: domain.map(|d| d.to_ascii_lowercase()),
+        }
+    }
+}
+
+impl std::fmt::Display for Alias {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.domain {
+            Some(domain) => write!(f, "{}@{}", self.name, domain),
+            None => write!(f, "@{}", self.name),
+        }
+    }
+}
+
+/// Finds all Nano addresses in a chat message. An alias is only accepted if
+/// the viewer explicitly registers with it, see `registration_alias`
+pub(crate) fn find_addresses(message: &str) -> Vec<AddressCandidate> {
+    let words: Vec<&str> = message.split_whitespace().map(trim_punctuation).collect();
+    let mut result = Vec::new();
+    let mut i = 0;
+    while i < words.len() {
+        let word = words[i];
+        i += 1;
+
+        let Some(mut address) = normalize_prefix(word) else {
+            continue;
+        };
+
+        // The address may have been broken across lines or by spaces
+        while i < words.len()
+            && address.len() + words[i].len() <= ADDRESS_LEN
+            && is_address_fragment(words[i])
+        {
+            address.push_str(&words[i].to_ascii_lowercase());
+            i += 1;
+        }
+
+        if let Ok(account) = Account::decode_account(&address) {
+            result.push(AddressCandidate::Account(account));
+        }
+    }
+
+    if result.is_empty() {
+        if let Some(alias) = registration_alias(message) {
+            result.push(AddressCandidate::Alias(alias));
+        }
+    }
+    result
+}
+
+/// The alias of a message that consists only of the alias or of
+/// `!register <alias>`. Mentions like `gg @bob` are not registrations
+fn registration_alias(message: &str) -> Option<Alias> {
+    let mut words = message.split_whitespace();
+    let mut word = words.next()?;
+    if word.eq_ignore_ascii_case("!register") {
+        word = words.next()?;
+    }
+    if words.next().is_some() {
+        return None;
+    }
+    parse_alias(trim_punctuation(word))
+}
+
+fn trim_punctuation(word: &str) -> &str {
+    word.trim_start_matches(|c: char| !c.is_ascii_alphanumeric() && c != '_' && c != '@')
+        .trim_end_matches(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+}
+
+/// Converts `xrb_`, `xrb-` and `nano-` prefixes to the canonical `nano_` prefix
+fn normalize_prefix(word: &str) -> Option<String> {
+    let lower = word.to_ascii_lowercase();
+    ["nano_", "nano-", "xrb_", "xrb-"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+        .map(|rest| format!("nano_{}", rest))
+}
+
+fn is_address_fragment(word: &str) -> bool {
+    !word.is_empty()
+        && word
+            .chars()
+            .all(|c| ADDRESS_ALPHABET.contains(c.to_ascii_lowercase()))
+}
+
+fn parse_alias(word: &str) -> Option<Alias> {
+    if let Some(name) = word.strip_prefix('@') {
+        return is_alias_name(name).then(|| Alias::new(name, None));
+    }
+
+    if let Some((name, domain)) = word.split_once('@') {
+        return (is_alias_name(name) && is_domain(domain)).then(|| Alias::new(name, Some(domain)));
+    }
+
+    let lower = word.to_ascii_lowercase();
+    if let Some(name) = lower.strip_suffix(".xno") {
+        return is_alias_name(name).then(|| Alias::new(name, None));
+    }
+
+    None
+}
+
+fn is_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= 64
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// The bot fetches from the domain, so it must be a public host name.
+/// IP literals and names of the local network are rejected
+fn is_domain(domain: &str) -> bool {
+    let lower = domain.to_ascii_lowercase();
+    let valid_labels = lower.split('.').all(|part| {
+        !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    // A numeric last label makes the host an IPv4 address, also in hex like `0x7f.1`
+    let valid_tld = lower
+        .rsplit_once('.')
+        .is_some_and(|(_, tld)| tld.starts_with(|c: char| c.is_ascii_alphabetic()));
+    let local = LOCAL_DOMAINS
+        .iter()
+        .any(|local| lower == *local || lower.ends_with(&format!(".{}", local)));
+    valid_labels && valid_tld && !local
+}
+
+const LOCAL_DOMAINS: [&str; 5] = ["localhost", "local", "internal", "lan", "home.arpa"];
+
+/// Remembers which aliases were already resolved and which still need a lookup.
+/// At most `MAX_ALIASES` results are kept, the oldest ones are forgotten first
+#[derive(Default)]
+pub(crate) struct AliasCache {
+    resolved: HashMap<Alias, Option<Account>>,
+    /// The resolved aliases in the order they were added
+    resolved_order: VecDeque<Alias>,
+    /// All viewers who wait for the lookup of an alias
+    waiting: HashMap<Alias, Vec<AliasLookup>>,
+    pending: Vec<Alias>,
+}
+
+/// A chat author who waits for an alias to be resolved
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub(crate) struct AliasLookup {
+    pub alias: Alias,
+    pub channel_id: String,
+    pub name: String,
+    pub platform: Platform,
+}
+
+impl AliasCache {
+    /// Returns the cached result or queues a lookup if the alias is unknown.
+    /// Every viewer who uses the alias meanwhile waits for the same lookup.
+    /// No more than `MAX_ALIASES` aliases are looked up at the same time
+    pub fn get_or_queue(&mut self, lookup: AliasLookup) -> Option<Account> {
+        if let Some(cached) = self.resolved.get(&lookup.alias) {
+            return *cached;
+        }
+        if !self.waiting.contains_key(&lookup.alias) && self.waiting.len() >= MAX_ALIASES {
+            return None;
+        }
+
+        let waiting = self.waiting.entry(lookup.alias.clone()).or_default();
+        if waiting.is_empty() {
+            self.pending.push(lookup.alias.clone());
+        }
+        if !waiting.contains(&lookup) {
+            waiting.push(lookup);
+        }
+        None
+    }
+
+    /// Takes all aliases which need a lookup
+    pub fn take_pending(&mut self) -> Vec<Alias> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Returns the account and everyone who waited for it. Failed lookups are
+    /// not cached, so the alias is looked up again the next time it is used
+    pub fn resolved(
+        &mut self,
+        alias: Alias,
+        result: anyhow::Result<Option<Account>>,
+    ) -> (Option<Account>, Vec<AliasLookup>) {
+        let waiting = self.waiting.remove(&alias).unwrap_or_default();
+        let account = match result {
+            Ok(account) => {
+                self.cache(alias, account);
+                account
+            }
+            Err(_) => None,
+        };
+        (account, waiting)
+    }
+
+    fn cache(&mut self, alias: Alias, account: Option<Account>) {
+        if self.resolved.insert(alias.clone(), account).is_some() {
+            return;
+        }
+        self.resolved_order.push_back(alias);
+        if self.resolved_order.len() > MAX_ALIASES {
+            if let Some(oldest) = self.resolved_order.pop_front() {
+                self.resolved.remove(&oldest);
+            }
+        }
+    }
+}
+
+const MAX_ALIASES: usize = 1000;
+
+const ADDRESS_LEN: usize = 65;
+const ADDRESS_ALPHABET: &str = "13456789abcdefghijkmnopqrstuwxyz";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "nano_37391u1nrr1j7tdn8w9zathoio5suz9bar18jksqheeiy4obwz3pkgp9aqz6";
+
+    fn account() -> Account {
+        Account::decode_account(ADDRESS).unwrap()
+    }
+
+    #[test]
+    fn no_address() {
+        assert_eq!(find_addresses("hello world"), Vec::new());
+    }
+
+    #[test]
+    fn plain_address() {
+        assert_eq!(
+            find_addresses(&format!("my address is {} :-)", ADDRESS)),
+            vec![AddressCandidate::Account(account())]
+        );
+    }
+
+    #[test]
+    fn surrounded_by_punctuation() {
+        assert_eq!(
+            find_addresses(&format!("(\"{}\"),", ADDRESS)),
+            vec![AddressCandidate::Account(account())]
+        );
+    }
+
+    #[test]
+    fn xrb_prefix() {
+        let xrb = ADDRESS.replace("nano_", "xrb_");
+        assert_eq!(
+            find_addresses(&xrb),
+            vec![AddressCandidate::Account(account())]
+        );
+    }
+
+    #[test]
+    fn upper_case() {
+        assert_eq!(
+            find_addresses(&ADDRESS.to_ascii_uppercase()),
+            vec![AddressCandidate::Account(account())]
+        );
+    }
+
+    #[test]
+    fn broken_across_lines() {
+        let broken = format!(
+            "{}\n{}\n{}",
+            &ADDRESS[..30],
+            &ADDRESS[30..50],
+            &ADDRESS[50..]
+        );
+        assert_eq!(
+            find_addresses(&broken),
+            vec![AddressCandidate::Account(account())]
+        );
+    }
+
+    #[test]
+    fn at_alias() {
+        assert_eq!(
+            find_addresses("@Alice!"),
+            vec![AddressCandidate::Alias(Alias::new("alice", None))]
+        );
+    }
+
+    #[test]
+    fn register_command() {
+        assert_eq!(
+            find_addresses("!register @Alice"),
+            vec![AddressCandidate::Alias(Alias::new("alice", None))]
+        );
+    }
+
+    #[test]
+    fn ignore_mentions() {
+        assert_eq!(find_addresses("send it to @Alice!"), Vec::new());
+        assert_eq!(find_addresses("mail me at bob@example.com"), Vec::new());
+        assert_eq!(find_addresses("!register"), Vec::new());
+    }
+
+    #[test]
+    fn alias_with_domain() {
+        assert_eq!(
+            find_addresses("bob@example.com"),
+            vec![AddressCandidate::Alias(Alias::new(
+                "bob",
+                Some("example.com")
+            ))]
+        );
+    }
+
+    #[test]
+    fn reject_local_hosts() {
+        for domain in [
+            "192.168.1.1",
+            "127.1",
+            "0x7f.0x1",
+            "localhost",
+            "printer.local",
+            "db.internal",
+            "router.home.arpa",
+        ] {
+            assert_eq!(find_addresses(&format!("bob@{}", domain)), Vec::new());
+        }
+    }
+
+    #[test]
+    fn xno_alias() {
+        assert_eq!(
+            find_addresses("carol.xno"),
+            vec![AddressCandidate::Alias(Alias::new("carol", None))]
+        );
+    }
+
+    #[test]
+    fn queue_unknown_alias_once() {
+        let mut cache = AliasCache::default();
+        let lookup = test_lookup();
+        assert_eq!(cache.get_or_queue(lookup.clone()), None);
+        assert_eq!(cache.get_or_queue(lookup.clone()), None);
+        assert_eq!(cache.take_pending(), vec![lookup.alias]);
+        assert_eq!(cache.take_pending(), Vec::new());
+    }
+
+    #[test]
+    fn keep_every_waiting_viewer() {
+        let mut cache = AliasCache::default();
+        let first = test_lookup();
+        let second = AliasLookup {
+            channel_id: "def".to_owned(),
+            ..test_lookup()
+        };
+        cache.get_or_queue(first.clone());
+        assert_eq!(cache.take_pending(), vec![first.alias.clone()]);
+        cache.get_or_queue(second.clone());
+        assert_eq!(cache.take_pending(), Vec::new());
+
+        let (resolved, waiting) = cache.resolved(first.alias.clone(), Ok(Some(account())));
+        assert_eq!(resolved, Some(account()));
+        assert_eq!(waiting, vec![first, second]);
+    }
+
+    #[test]
+    fn retry_failed_lookup() {
+        let mut cache = AliasCache::default();
+        let lookup = test_lookup();
+        cache.get_or_queue(lookup.clone());
+        cache.take_pending();
+        let (resolved, _) = cache.resolved(lookup.alias.clone(), Err(anyhow::anyhow!("timeout")));
+        assert_eq!(resolved, None);
+
+        assert_eq!(cache.get_or_queue(lookup.clone()), None);
+        assert_eq!(cache.take_pending(), vec![lookup.alias]);
+    }
+
+    #[test]
+    fn use_cached_alias() {
+        let mut cache = AliasCache::default();
+        let lookup = test_lookup();
+        cache.resolved(lookup.alias.clone(), Ok(Some(account())));
+        assert_eq!(cache.get_or_queue(lookup), Some(account()));
+        assert_eq!(cache.take_pending(), Vec::new());
+    }
+
+    #[test]
+    fn cache_unknown_alias() {
+        let mut cache = AliasCache::default();
+        let lookup = test_lookup();
+        cache.resolved(lookup.alias.clone(), Ok(None));
+        assert_eq!(cache.get_or_queue(lookup), None);
+        assert_eq!(cache.take_pending(), Vec::new());
+    }
+
+    #[test]
+    fn forget_oldest_alias() {
+        let mut cache = AliasCache::default();
+        for i in 0..=MAX_ALIASES {
+            cache.resolved(Alias::new(format!("a{}", i), None), Ok(None));
+        }
+        assert_eq!(cache.resolved.len(), MAX_ALIASES);
+        assert!(!cache.resolved.contains_key(&Alias::new("a0", None)));
+        assert!(cache.resolved.contains_key(&Alias::new("a1", None)));
+    }
+
+    fn test_lookup() -> AliasLookup {
+        AliasLookup {
+            alias: Alias::new("alice", None),
+            channel_id: "abc".to_owned(),
+            name: "Alice".to_owned(),
+            platform: Platform::YouTube,
+        }
+    }
+}
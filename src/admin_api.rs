@@ -0,0 +1,486 @@
+use crate::{
+    amounts::parse_xno,
+    database::{unix_now, AuditRow},
+    draw_animation::AnimationMode,
+    http_server::AppState,
+    named_raffles::RaffleOverview,
+    participants::Participant,
+    schedule::{Schedule, SCHEDULE_SETTING},
+    winners::WinnerRecord,
+};
+use axum::{
+    extract::{Path, Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+    Json, Router,
+};
+use blake2::{Blake2b512, Digest};
+use rsnano_core::{Account, Amount};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Routes for remote controlling the bot. Every request needs an
+/// `Authorization: Bearer <RAFFLE_ADMIN_TOKEN>` header
+pub(crate) fn admin_routes(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/status", get(get_status))
+        .route("/start", post(post_start))
+        .route("/stop", post(post_stop))
+        .route("/run-now", post(post_run_now))
+        .route("/confirm", post(post_confirm))
+        .route("/prize", put(put_prize))
+        .route("/interval", put(put_interval))
+        .route("/animation", put(put_animation))
+        .route("/schedule", put(put_schedule))
+        .route("/end-stream", post(post_end_stream))
+        .route("/participants", get(get_participants))
+        .route("/participants/{channel_id}/kick", post(post_kick))
+        .route("/participants/{channel_id}/ban", post(post_ban))
+        .route("/winners", get(get_winners))
+        .route("/payouts/failed", get(get_failed_payouts))
+        .route("/payouts/{raffle_id}/retry", post(post_retry_payout))
+        .route("/payouts/held", get(get_held_payouts))
+        .route("/payouts/{raffle_id}/approve", post(post_approve_payout))
+        .route("/payouts/{raffle_id}/deny", post(post_deny_payout))
+        .route("/payouts/audit", get(get_payout_audit))
+        .route_layer(middleware::from_fn_with_state(state, require_token))
+}
+
+async fn require_token(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+
+    match (token, state.admin_token.as_deref()) {
+        (Some(token), Some(expected)) if tokens_match(token, expected) => next.run(request).await,
+        _ => StatusCode::UNAUTHORIZED.into_response(),
+    }
+}
+
+/// Compares the digests in constant time, so that neither the token nor its
+/// length can be guessed from the response time
+pub(crate) fn tokens_match(a: &str, b: &str) -> bool {
+    let (a, b) = (Blake2b512::digest(a), Blake2b512::digest(b));
+    a.iter()
+        .zip(b.iter())
+        .fold(0, |diff, (x, y)| diff | (x ^ y))
+        == 0
+}
+
+type ApiResult<T = ()> = Result<T, (StatusCode, String)>;
+
+#[derive(Serialize)]
+struct Status {
+    running: bool,
+    prize: Amount,
+    interval_secs: u64,
+    countdown_secs: u64,
+    /// Prize of the next raffle, which the schedule may override
+    next_prize: Amount,
+    /// Unpaid pot of the jackpot mode
+    jackpot: Option<Amount>,
+    schedule: String,
+    spinner_connected: bool,
+    participants: usize,
+    current_raffle: Option<u64>,
+    /// The main raffle and the raffles that run next to it
+    raffles: Vec<RaffleOverview>,
+    /// `None` if the animation is chosen automatically
+    animation: Option<AnimationMode>,
+}
+
+async fn get_status(State(state): State<AppState>) -> Json<Status> {
+    let mut guard = state.logic.lock().unwrap();
+    let now = state.clock.now();
+    Json(Status {
+        running: guard.running(),
+        prize: guard.prize(),
+        interval_secs: guard.raffle_interval().as_secs(),
+        countdown_secs: guard.countdown(now).as_secs(),
+        next_prize: guard.next_draw(now).1,
+        jackpot: guard.jackpot(),
+        schedule: guard.schedule().to_string(),
+        spinner_connected: guard.spinner_connected(now),
+        participants: guard.participants().len(),
+        current_raffle: guard.current_win().map(|w| w.id),
+        raffles: guard.raffles(now),
+        animation: guard.animation(),
+    })
+}
+
+async fn post_start(State(state): State<AppState>) {
+    state.logic.lock().unwrap().start();
+}
+
+async fn post_stop(State(state): State<AppState>) {
+    state.logic.lock().unwrap().stop();
+}
+
+async fn post_run_now(State(state): State<AppState>) {
+    state
+        .logic
+        .lock()
+        .unwrap()
+        .run_raffle_now(state.clock.now());
+}
+
+/// Confirms the current spin like the "confirm winner" button in the GUI
+async fn post_confirm(State(state): State<AppState>) -> ApiResult {
+    let mut guard = state.logic.lock().unwrap();
+    if guard.current_win().is_none() {
+        return Err((StatusCode::CONFLICT, "no spin pending".to_owned()));
+    }
+    guard.confirm_manually();
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct PrizeRequest {
+    /// Prize in XNO, for example "0.5"
+    prize: String,
+}
+
+async fn put_prize(State(state): State<AppState>, Json(request): Json<PrizeRequest>) -> ApiResult {
+    let prize = parse_xno(&request.prize)
+        .map_err(|_| bad_request(format!("invalid prize: {}", request.prize)))?;
+    state.logic.lock().unwrap().set_prize(prize);
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct IntervalRequest {
+    seconds: u64,
+}
+
+async fn put_interval(
+    State(state): State<AppState>,
+    Json(request): Json<IntervalRequest>,
+) -> ApiResult {
+    if request.seconds == 0 {
+        return Err(bad_request("interval must not be 0".to_owned()));
+    }
+    state
+        .logic
+        .lock()
+        .unwrap()
+        .set_raffle_interval(Duration::from_secs(request.seconds));
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct AnimationRequest {
+    /// `auto` or one of the animation modes, for example "slot_reel"
+    animation: String,
+}
+
+/// Selects the animation for the following raffles
+async fn put_animation(
+    State(state): State<AppState>,
+    Json(request): Json<AnimationRequest>,
+) -> ApiResult {
+    let animation =
+        AnimationMode::parse(&request.animation).map_err(|e| bad_request(e.to_string()))?;
+    state.logic.lock().unwrap().set_animation(animation);
+    Ok(())
+}
+
+#[derive(Deserialize)]
+struct ScheduleRequest {
+    /// For example "every 15m 20:00-22:00; hourly prize 5; final". Empty to use the interval
+    schedule: String,
+}
+
+/// Replaces the schedule and persists it
+async fn put_schedule(
+    State(state): State<AppState>,
+    Json(request): Json<ScheduleRequest>,
+) -> ApiResult {
+    let schedule = Schedule::parse(&request.schedule).map_err(|e| bad_request(e.to_string()))?;
+    state
+        .database
+        .lock()
+        .unwrap()
+        .save_setting(SCHEDULE_SETTING, &schedule.to_string())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.logic.lock().unwrap().set_schedule(schedule);
+    Ok(())
+}
+
+/// Runs the final draw of the schedule and stops the raffle afterwards
+async fn post_end_stream(State(state): State<AppState>) {
+    state.logic.lock().unwrap().end_stream(state.clock.now());
+}
+
+async fn get_participants(State(state): State<AppState>) -> Json<Vec<Participant>> {
+    Json(state.logic.lock().unwrap().participants())
+}
+
+async fn post_kick(State(state): State<AppState>, Path(channel_id): Path<String>) -> ApiResult {
+    if state.logic.lock().unwrap().kick(&channel_id) {
+        Ok(())
+    } else {
+        Err(not_found(format!("unknown participant: {}", channel_id)))
+    }
+}
+
+async fn post_ban(State(state): State<AppState>, Path(channel_id): Path<String>) -> ApiResult {
+    state
+        .database
+        .lock()
+        .unwrap()
+        .insert_ban(&channel_id, unix_now())
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state.logic.lock().unwrap().ban(&channel_id);
+    Ok(())
+}
+
+async fn get_winners(State(state): State<AppState>) -> Json<Vec<WinnerRecord>> {
+    Json(state.logic.lock().unwrap().winners().all().to_vec())
+}
+
+async fn get_failed_payouts(State(state): State<AppState>) -> Json<Vec<WinnerRecord>> {
+    let guard = state.logic.lock().unwrap();
+    Json(guard.failed_payouts().into_iter().cloned().collect())
+}
+
+async fn post_retry_payout(State(state): State<AppState>, Path(raffle_id): Path<u64>) -> ApiResult {
+    if state.logic.lock().unwrap().retry_payout(raffle_id) {
+        Ok(())
+    } else {
+        Err(not_found(format!(
+            "no failed payout for raffle {}",
+            raffle_id
+        )))
+    }
+}
+
+async fn get_held_payouts(State(state): State<AppState>) -> Json<Vec<HeldPayout>> {
+    let guard = state.logic.lock().unwrap();
+    Json(
+        guard
+            .held_payouts()
+            .iter()
+            .map(|h| HeldPayout {
+                raffle_id: h.winner.raffle_id,
+                name: h.winner.name.clone(),
+                account: h.winner.account,
+                prize: h.winner.prize,
+                reason: h.reason.clone(),
+            })
+            .collect(),
+    )
+}
+
+#[derive(Serialize)]
+struct HeldPayout {
+    raffle_id: u64,
+    name: String,
+    account: Account,
+    prize: Amount,
+    reason: String,
+}
+
+/// Sends a payout that exceeded a limit. The stream cap still applies
+async fn post_approve_payout(
+    State(state): State<AppState>,
+    Path(raffle_id): Path<u64>,
+) -> ApiResult {
+    if state.logic.lock().unwrap().approve_payout(raffle_id) {
+        Ok(())
+    } else {
+        Err(not_found(format!(
+            "no held payout for raffle {}",
+            raffle_id
+        )))
+    }
+}
+
+async fn post_deny_payout(State(state): State<AppState>, Path(raffle_id): Path<u64>) -> ApiResult {
+    if state.logic.lock().unwrap().deny_payout(raffle_id) {
+        Ok(())
+    } else {
+        Err(not_found(format!(
+            "no held payout for raffle {}",
+            raffle_id
+        )))
+    }
+}
+
+async fn get_payout_audit(State(state): State<AppState>) -> ApiResult<Json<Vec<AuditRow>>> {
+    state
+        .database
+        .lock()
+        .unwrap()
+        .payout_audit(AUDIT_LIMIT)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+const AUDIT_LIMIT: usize = 500;
+
+fn bad_request(message: String) -> (StatusCode, String) {
+    (StatusCode::BAD_REQUEST, message)
+}
+
+fn not_found(message: String) -> (StatusCode, String) {
+    (StatusCode::NOT_FOUND, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{Method, Request},
+    };
+    use tower::ServiceExt;
+
+    #[test]
+    fn compare_tokens() {
+        assert!(tokens_match("secret", "secret"));
+        assert!(!tokens_match("secret", "secreT"));
+        assert!(!tokens_match("secret", "secret2"));
+        assert!(!tokens_match("", "secret"));
+    }
+
+    #[tokio::test]
+    async fn reject_missing_or_wrong_token() {
+        let app = admin_app(AppState::new_test_instance());
+        for auth in [None, Some("Bearer wrong"), Some("Bearer "), Some(TOKEN)] {
+            let status = call(&app, Method::GET, "/status", auth, None).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{:?}", auth);
+        }
+    }
+
+    #[tokio::test]
+    async fn routes_need_token() {
+        let app = admin_app(AppState::new_test_instance());
+        for (method, uri, body, expected) in routes() {
+            let status = call(&app, method.clone(), uri, None, body).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{}", uri);
+
+            let auth = format!("Bearer {}", TOKEN);
+            let status = call(&app, method, uri, Some(&auth), body).await;
+            assert_eq!(status, expected, "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn set_prize() {
+        let state = AppState::new_test_instance();
+        let app = admin_app(state.clone());
+        let auth = format!("Bearer {}", TOKEN);
+        let body = r#"{"prize":"0.5"}"#;
+        let status = call(&app, Method::PUT, "/prize", Some(&auth), Some(body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(
+            state.logic.lock().unwrap().prize(),
+            Amount::raw(Amount::nano(1).number() / 2)
+        );
+    }
+
+    const TOKEN: &str = "admin-secret";
+
+    /// Every admin route with a valid request and the status it answers with
+    fn routes() -> Vec<(Method, &'static str, Option<&'static str>, StatusCode)> {
+        vec![
+            (Method::GET, "/status", None, StatusCode::OK),
+            (Method::POST, "/start", None, StatusCode::OK),
+            (Method::POST, "/stop", None, StatusCode::OK),
+            (Method::POST, "/run-now", None, StatusCode::OK),
+            (Method::POST, "/confirm", None, StatusCode::CONFLICT),
+            (
+                Method::PUT,
+                "/prize",
+                Some(r#"{"prize":"1"}"#),
+                StatusCode::OK,
+            ),
+            (
+                Method::PUT,
+                "/interval",
+                Some(r#"{"seconds":60}"#),
+                StatusCode::OK,
+            ),
+            (
+                Method::PUT,
+                "/animation",
+                Some(r#"{"animation":"auto"}"#),
+                StatusCode::OK,
+            ),
+            (
+                Method::PUT,
+                "/schedule",
+                Some(r#"{"schedule":""}"#),
+                StatusCode::OK,
+            ),
+            (Method::POST, "/end-stream", None, StatusCode::OK),
+            (Method::GET, "/participants", None, StatusCode::OK),
+            (
+                Method::POST,
+                "/participants/unknown/kick",
+                None,
+                StatusCode::NOT_FOUND,
+            ),
+            (
+                Method::POST,
+                "/participants/unknown/ban",
+                None,
+                StatusCode::OK,
+            ),
+            (Method::GET, "/winners", None, StatusCode::OK),
+            (Method::GET, "/payouts/failed", None, StatusCode::OK),
+            (
+                Method::POST,
+                "/payouts/1/retry",
+                None,
+                StatusCode::NOT_FOUND,
+            ),
+            (Method::GET, "/payouts/held", None, StatusCode::OK),
+            (
+                Method::POST,
+                "/payouts/1/approve",
+                None,
+                StatusCode::NOT_FOUND,
+            ),
+            (Method::POST, "/payouts/1/deny", None, StatusCode::NOT_FOUND),
+            (Method::GET, "/payouts/audit", None, StatusCode::OK),
+        ]
+    }
+
+    fn admin_app(mut state: AppState) -> Router {
+        state.admin_token = Some(TOKEN.to_owned());
+        Router::new()
+            .nest("/api/admin", admin_routes(state.clone()))
+            .with_state(state)
+    }
+
+    async fn call(
+        app: &Router,
+        method: Method,
+        uri: &str,
+        auth: Option<&str>,
+        body: Option<&str>,
+    ) -> StatusCode {
+        let mut request = Request::builder()
+            .method(method)
+            .uri(format!("/api/admin{}", uri));
+        if let Some(auth) = auth {
+            request = request.header(header::AUTHORIZATION, auth);
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_owned())),
+            None => request.body(Body::empty()),
+        };
+        app.clone()
+            .oneshot(request.unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+}
@@ -0,0 +1,234 @@
+use crate::addresses::Alias;
+use anyhow::anyhow;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use rsnano_core::Account;
+use serde::Deserialize;
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+/// Resolves human readable aliases to Nano accounts
+pub(crate) enum AliasResolver {
+    /// Aliases are ignored
+    Disabled,
+    /// Looks up `https://<domain>/.well-known/nano-currency.json?names=<name>`.
+    /// Aliases without a domain are resolved via `default_domain`
+    WellKnown { default_domain: String },
+    /// Calls a URL with `{name}` and `{domain}` placeholders which returns either
+    /// the plain address or a JSON object with an `address` field
+    Template { url: String, default_domain: String },
+}
+
+impl AliasResolver {
+    /// Configures the resolver via the `NANO_ALIAS_RESOLVER` and `NANO_ALIAS_DOMAIN`
+    /// env vars. Aliases are ignored unless `NANO_ALIAS_RESOLVER` is `well-known`
+    /// or a URL template
+    pub(crate) fn from_env() -> Self {
+        let default_domain =
+            std::env::var("NANO_ALIAS_DOMAIN").unwrap_or_else(|_| DEFAULT_DOMAIN.to_owned());
+        match std::env::var("NANO_ALIAS_RESOLVER").ok().as_deref() {
+            None | Some("") | Some("off") => Self::Disabled,
+            Some("well-known") => Self::WellKnown { default_domain },
+            Some(url) => Self::Template {
+                url: url.to_owned(),
+                default_domain,
+            },
+        }
+    }
+
+    pub(crate) fn enabled(&self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+
+    pub(crate) async fn resolve(&self, alias: &Alias) -> anyhow::Result<Option<Account>> {
+        match self {
+            Self::Disabled => Ok(None),
+            Self::WellKnown { default_domain } => {
+                let domain = alias.domain.as_deref().unwrap_or(default_domain);
+                resolve_well_known(&alias.name, domain).await
+            }
+            Self::Template {
+                url,
+                default_domain,
+            } => {
+                let domain = alias.domain.as_deref().unwrap_or(default_domain);
+                // Only the host of the template is trusted, not one chosen by a viewer
+                let public_only = template_host(url).contains("{domain}");
+                let url = url
+                    .replace("{name}", &alias.name)
+                    .replace("{domain}", domain);
+                resolve_template(&url, public_only).await
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct WellKnownResponse {
+    names: Vec<WellKnownName>,
+}
+
+#[derive(Deserialize)]
+struct WellKnownName {
+    name: String,
+    address: String,
+}
+
+async fn resolve_well_known(name: &str, domain: &str) -> anyhow::Result<Option<Account>> {
+    let response: WellKnownResponse = http_client(true)?
+        .get(format!("https://{}/.well-known/nano-currency.json", domain))
+        .query(&[("names", name)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    let address = response
+        .names
+        .into_iter()
+        .find(|n| n.name.eq_ignore_ascii_case(name))
+        .map(|n| n.address);
+
+    match address {
+        Some(address) => decode(&address).map(Some),
+        None => Ok(None),
+    }
+}
+
+#[derive(Deserialize)]
+struct TemplateResponse {
+    address: String,
+}
+
+async fn resolve_template(url: &str, public_only: bool) -> anyhow::Result<Option<Account>> {
+    let response = http_client(public_only)?.get(url).send().await?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(None);
+    }
+
+    let body = response.error_for_status()?.text().await?;
+    let address = match serde_json::from_str::<TemplateResponse>(&body) {
+        Ok(json) => json.address,
+        Err(_) => body.trim().to_owned(),
+    };
+    decode(&address).map(Some)
+}
+
+fn decode(address: &str) -> anyhow::Result<Account> {
+    Account::decode_account(address).map_err(|_| anyhow!("invalid address: {}", address))
+}
+
+fn template_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    rest.split(['/', '?', '#']).next().unwrap_or_default()
+}
+
+/// With `public_only` the client refuses to connect to loopback, private and
+/// other addresses which aren't reachable from the internet, so that viewers
+/// can't make the bot call hosts in its local network. Redirects aren't
+/// followed then, because a redirect to an IP address skips the resolver
+fn http_client(public_only: bool) -> anyhow::Result<reqwest::Client> {
+    let mut builder = reqwest::ClientBuilder::new().timeout(Duration::from_secs(5));
+    if public_only {
+        builder = builder
+            .dns_resolver(Arc::new(PublicOnly))
+            .redirect(reqwest::redirect::Policy::none());
+    }
+    Ok(builder.build()?)
+}
+
+/// Resolves host names, but only returns public addresses
+struct PublicOnly;
+
+impl Resolve for PublicOnly {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = format!("{}:0", name.as_str());
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host(host)
+                .await?
+                .filter(|addr| is_public(addr.ip()))
+                .collect();
+            if addrs.is_empty() {
+                return Err(anyhow!("host has no public address").into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public_v4(ip),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        // "this network", shared address space (CGNAT), benchmarking and reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_multicast()
+        // unique local, link local and documentation addresses
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80
+        || first == 0x2001 && ip.segments()[1] == 0x0db8)
+}
+
+const DEFAULT_DOMAIN: &str = "nano.to";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_public_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "192.168.1.1",
+            "169.254.1.1",
+            "100.64.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["::1", "fd00::1", "fe80::1", "::ffff:192.168.1.1"] {
+            assert!(!is_public(ip.parse().unwrap()), "{}", ip);
+        }
+        assert!(is_public("1.1.1.1".parse().unwrap()));
+        assert!(is_public("2606:4700::1111".parse().unwrap()));
+    }
+
+    #[test]
+    fn host_of_template() {
+        assert_eq!(
+            template_host("https://{domain}/.well-known/x?names={name}"),
+            "{domain}"
+        );
+        assert_eq!(
+            template_host("http://127.0.0.1:9000/resolve/{name}@{domain}"),
+            "127.0.0.1:9000"
+        );
+    }
+}
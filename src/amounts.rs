@@ -0,0 +1,57 @@
+use anyhow::{anyhow, bail};
+use rsnano_core::Amount;
+
+/// Parses an amount in XNO like "0.5". `Amount::decode_dec` expects raw
+pub(crate) fn parse_xno(s: &str) -> anyhow::Result<Amount> {
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    let is_number = |d: &str| d.bytes().all(|b| b.is_ascii_digit());
+    if (whole.is_empty() && fraction.is_empty())
+        || !is_number(whole)
+        || !is_number(fraction)
+        || fraction.len() > XNO_DECIMALS
+    {
+        bail!("invalid amount: {}", s);
+    }
+
+    let too_large = || anyhow!("amount too large: {}", s);
+    let whole: u128 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().map_err(|_| too_large())?
+    };
+    let fraction: u128 = format!("{:0<width$}", fraction, width = XNO_DECIMALS).parse()?;
+    whole
+        .checked_mul(Amount::nano(1).number())
+        .and_then(|raw| raw.checked_add(fraction))
+        .map(Amount::raw)
+        .ok_or_else(too_large)
+}
+
+const XNO_DECIMALS: usize = 30;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(parse_xno("1").unwrap(), Amount::nano(1));
+        assert_eq!(
+            parse_xno("10.5").unwrap(),
+            Amount::raw(105 * 10u128.pow(29))
+        );
+        assert_eq!(parse_xno(".5").unwrap(), Amount::raw(5 * 10u128.pow(29)));
+        assert_eq!(
+            parse_xno("0.000000000000000000000000000001").unwrap(),
+            Amount::raw(1)
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        for s in ["", ".", "-1", "1,5", "0.5 ", "abc", "1e3", "1000000000000"] {
+            assert!(parse_xno(s).is_err(), "{}", s);
+        }
+        assert!(parse_xno("0.0000000000000000000000000000001").is_err());
+    }
+}
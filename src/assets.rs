@@ -0,0 +1,218 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use std::{
+    borrow::Cow,
+    path::{Component, PathBuf},
+};
+
+use crate::{
+    http_server::AppState,
+    theme::{render_html, render_svg},
+};
+
+/// Files that are compiled into the binary, so that the overlay works offline
+const EMBEDDED: &[(&str, &[u8])] = &[
+    ("index.html", include_bytes!("../assets/index.html")),
+    ("overlay.svg", include_bytes!("../assets/overlay.svg")),
+    ("spin-wheel.js", include_bytes!("../assets/spin-wheel.js")),
+    ("animations.js", include_bytes!("../assets/animations.js")),
+    ("animations.css", include_bytes!("../assets/animations.css")),
+    ("icon-256.png", include_bytes!("../assets/icon-256.png")),
+    ("overlay.js", include_bytes!("../assets/overlay.js")),
+    ("overlay.css", include_bytes!("../assets/overlay.css")),
+    (
+        "overlays/countdown.html",
+        include_bytes!("../assets/overlays/countdown.html"),
+    ),
+    (
+        "overlays/registrations.html",
+        include_bytes!("../assets/overlays/registrations.html"),
+    ),
+    (
+        "overlays/winners.html",
+        include_bytes!("../assets/overlays/winners.html"),
+    ),
+    (
+        "overlays/chat.html",
+        include_bytes!("../assets/overlays/chat.html"),
+    ),
+    (
+        "overlays/enter.html",
+        include_bytes!("../assets/overlays/enter.html"),
+    ),
+];
+
+/// Serves the overlay files. Every file can be replaced by putting a file with
+/// the same name into the override directory, which also allows adding fonts and images
+pub(crate) struct Assets {
+    override_dir: Option<PathBuf>,
+}
+
+pub(crate) struct Asset {
+    pub content: Cow<'static, [u8]>,
+    /// Overridden files are never cached, so that changes show up immediately
+    pub overridden: bool,
+}
+
+impl Assets {
+    pub(crate) fn new(override_dir: Option<PathBuf>) -> Self {
+        Self { override_dir }
+    }
+
+    pub(crate) async fn get(&self, name: &str) -> Option<Asset> {
+        if !is_safe_path(name) {
+            return None;
+        }
+
+        if let Some(dir) = &self.override_dir {
+            if let Ok(content) = tokio::fs::read(dir.join(name)).await {
+                return Some(Asset {
+                    content: Cow::Owned(content),
+                    overridden: true,
+                });
+            }
+        }
+
+        EMBEDDED
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, content)| Asset {
+                content: Cow::Borrowed(*content),
+                overridden: false,
+            })
+    }
+}
+
+/// Only relative paths without `..` may be requested
+fn is_safe_path(name: &str) -> bool {
+    !name.is_empty()
+        && std::path::Path::new(name)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+}
+
+fn content_type(name: &str) -> &'static str {
+    match name.rsplit_once('.').map(|(_, ext)| ext) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("json") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("woff2") => "font/woff2",
+        Some("woff") => "font/woff",
+        Some("ttf") => "font/ttf",
+        Some("otf") => "font/otf",
+        _ => "application/octet-stream",
+    }
+}
+
+async fn serve_asset(assets: &Assets, name: &str) -> Response {
+    let Some(asset) = assets.get(name).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    let cache_control = if asset.overridden {
+        "no-cache"
+    } else {
+        "public, max-age=3600"
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, content_type(name)),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+        asset.content,
+    )
+        .into_response()
+}
+
+/// Serves an asset after replacing its placeholders. The result depends on
+/// the theme, so it is never cached
+pub(crate) async fn serve_template(
+    assets: &Assets,
+    name: &str,
+    render: impl FnOnce(&str) -> String,
+) -> Response {
+    let Some(asset) = assets.get(name).await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let rendered = render(&String::from_utf8_lossy(&asset.content));
+    (
+        [
+            (header::CONTENT_TYPE, content_type(name)),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        rendered,
+    )
+        .into_response()
+}
+
+/// HTML pages and the overlay SVG are templates, everything else is served as is
+pub(crate) async fn get_asset(State(state): State<AppState>, Path(name): Path<String>) -> Response {
+    if name.ends_with(".html") {
+        let theme = state.theme.load();
+        serve_template(&state.assets, &name, |t| render_html(t, &theme, false)).await
+    } else if name == "overlay.svg" {
+        let theme = state.theme.load();
+        serve_template(&state.assets, &name, |t| render_svg(t, &theme)).await
+    } else {
+        serve_asset(&state.assets, &name).await
+    }
+}
+
+/// The overlay pages for OBS browser sources, e.g. `/overlays/countdown`
+pub(crate) async fn get_overlay_page(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Response {
+    let theme = state.theme.load();
+    let path = format!("overlays/{}.html", name);
+    serve_template(&state.assets, &path, |t| render_html(t, &theme, false)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reject_unsafe_paths() {
+        assert!(is_safe_path("fonts/font.woff2"));
+        assert!(!is_safe_path("../secret"));
+        assert!(!is_safe_path("/etc/passwd"));
+        assert!(!is_safe_path(""));
+    }
+
+    #[test]
+    fn content_types() {
+        assert_eq!(
+            content_type("spin-wheel.js"),
+            "text/javascript; charset=utf-8"
+        );
+        assert_eq!(content_type("fonts/a.woff2"), "font/woff2");
+        assert_eq!(content_type("noext"), "application/octet-stream");
+    }
+
+    #[tokio::test]
+    async fn override_embedded_asset() {
+        let dir = std::env::temp_dir().join(format!("raffle-bot-assets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("overlay.svg"), "<svg/>").unwrap();
+
+        let assets = Assets::new(Some(dir));
+        let overridden = assets.get("overlay.svg").await.unwrap();
+        assert!(overridden.overridden);
+        assert_eq!(overridden.content.as_ref(), b"<svg/>");
+
+        let embedded = assets.get("spin-wheel.js").await.unwrap();
+        assert!(!embedded.overridden);
+        assert!(assets.get("missing.js").await.is_none());
+    }
+}
@@ -1,28 +1,56 @@
 use crate::{
+    alias_resolver::AliasResolver,
     chat_messages::ChatMessage,
-    http_server::run_http_server,
-    logic::{Action, RaffleLogic},
+    chat_script::listen_to_chat_script,
+    database::{unix_now, Database, PayoutStatus},
+    dry_run::{DryRunConfig, SimulatedSender},
+    http_server::{run_http_server, HttpConfig},
+    jackpot::JACKPOT_SETTING,
+    keystore::Signer,
+    logic::{Action, RaffleLogic, Winner},
+    metrics::Metrics,
+    overlay_events::OverlayEvent,
     participants_file::ParticipantsFile,
-    prize_sender::PrizeSender,
+    payout_policy::{PayoutAudit, PolicyDecision},
+    prize_sender::{monitor_wallet, NodeSender, PrizeSender},
     twitch_chat_listener::listen_to_twitch_chat,
+    winners::WinnerRecord,
     youtube_chat_listener::listen_to_youtube_chat,
 };
+use chrono::Local;
 use log::{info, warn};
 use rand::{rng, RngCore};
-use rsnano_core::PrivateKey;
 use rsnano_nullable_clock::SteadyClock;
 use std::{
     ffi::OsStr,
     sync::{Arc, Mutex},
-    time::Duration,
+    time::{Duration, Instant},
 };
-use tokio::{process::Command, sync::oneshot::Receiver, task::JoinSet, time::sleep};
+use tokio::{
+    process::Command,
+    sync::{
+        broadcast,
+        mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+        oneshot::Receiver,
+    },
+    task::JoinSet,
+    time::sleep,
+};
+
+/// Whether real prizes are paid out
+pub(crate) enum RunMode {
+    Live(Signer),
+    /// Never touches the network: payouts are simulated and chat comes from a script
+    DryRun(DryRunConfig),
+}
 
 pub(crate) fn run_backend(
     logic: &Arc<Mutex<RaffleLogic>>,
     clock: &Arc<SteadyClock>,
-    priv_key: PrivateKey,
+    mode: RunMode,
     participants_file: ParticipantsFile,
+    database: &Arc<Mutex<Database>>,
+    stream_id: i64,
     stop: Receiver<()>,
 ) {
     let runtime = tokio::runtime::Builder::new_current_thread()
@@ -30,20 +58,86 @@ pub(crate) fn run_backend(
         .build()
         .unwrap();
 
+    let metrics = Arc::new(Metrics::default());
     let logic_l = logic.clone();
-    let handle_message = move |msg: ChatMessage| logic_l.lock().unwrap().handle_chat_message(msg);
+    let metrics_l = metrics.clone();
+    let handle_message = move |msg: ChatMessage| {
+        metrics_l.chat_message(msg.platform);
+        logic_l.lock().unwrap().handle_chat_message(msg)
+    };
 
     runtime.block_on(async {
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
         let mut set = JoinSet::new();
-        set.spawn(run_ticker(
+        let live = matches!(mode, RunMode::Live(_));
+        let (prize_sender, chat_script) = match mode {
+            RunMode::Live(signer) => {
+                let logic_l = logic.clone();
+                let on_received =
+                    move |source, amount| logic_l.lock().unwrap().donation_received(source, amount);
+                let logic_l = logic.clone();
+                let on_balance = move |balance| logic_l.lock().unwrap().wallet_balance(balance);
+                set.spawn(monitor_wallet(
+                    signer.account(),
+                    on_received,
+                    on_balance,
+                    metrics.clone(),
+                ));
+                let sender = NodeSender::new(signer, metrics.clone());
+                (PrizeSender::Node(sender), None)
+            }
+            RunMode::DryRun(config) => {
+                let sender = SimulatedSender::new(&config, metrics.clone());
+                (PrizeSender::Simulated(sender), config.chat_script)
+            }
+        };
+        let alias_resolver = Arc::new(if live {
+            AliasResolver::from_env()
+        } else {
+            AliasResolver::Disabled
+        });
+        logic
+            .lock()
+            .unwrap()
+            .set_aliases_enabled(alias_resolver.enabled());
+        let (payouts, winners) = unbounded_channel();
+        let worker = PayoutWorker {
+            logic: logic.clone(),
+            database: database.clone(),
+            stream_id,
+            prize_sender,
+            metrics: metrics.clone(),
+        };
+        set.spawn(run_payouts(worker, winners));
+        let ticker = Ticker {
+            logic: logic.clone(),
+            clock: clock.clone(),
+            participants_file,
+            database: database.clone(),
+            stream_id,
+            payouts,
+            alias_resolver,
+            events: events.clone(),
+            metrics: metrics.clone(),
+        };
+        set.spawn(run_ticker(ticker));
+        set.spawn(run_http_server(
             logic.clone(),
             clock.clone(),
-            participants_file,
-            priv_key,
+            events,
+            database.clone(),
+            metrics.clone(),
+            HttpConfig::from_env(),
         ));
-        set.spawn(run_http_server(logic.clone(), clock.clone()));
-        set.spawn(listen_to_twitch_chat(handle_message.clone()));
-        set.spawn(listen_to_youtube_chat(handle_message));
+        if live {
+            set.spawn(listen_to_twitch_chat(
+                handle_message.clone(),
+                metrics.clone(),
+            ));
+            set.spawn(listen_to_youtube_chat(handle_message, metrics));
+        } else if let Some(path) = chat_script {
+            set.spawn(listen_to_chat_script(path, handle_message, metrics));
+        }
 
         tokio::select!(
             _ = set.join_all() => {},
@@ -52,23 +146,56 @@ pub(crate) fn run_backend(
     });
 }
 
-/// Periodically check logic for new things to do
-async fn run_ticker(
+/// Everything the ticker needs to execute the actions of the logic
+struct Ticker {
     logic: Arc<Mutex<RaffleLogic>>,
     clock: Arc<SteadyClock>,
-    mut participants_file: ParticipantsFile,
-    priv_key: PrivateKey,
-) {
-    let prize_sender = PrizeSender::new(priv_key);
+    participants_file: ParticipantsFile,
+    database: Arc<Mutex<Database>>,
+    stream_id: i64,
+    /// Winners for the payout worker
+    payouts: UnboundedSender<Winner>,
+    alias_resolver: Arc<AliasResolver>,
+    events: broadcast::Sender<OverlayEvent>,
+    metrics: Arc<Metrics>,
+}
+
+/// Periodically check logic for new things to do
+async fn run_ticker(ticker: Ticker) {
+    let Ticker {
+        logic,
+        clock,
+        mut participants_file,
+        database,
+        stream_id,
+        payouts,
+        alias_resolver,
+        events,
+        metrics,
+    } = ticker;
     loop {
         let participants;
         let actions;
+        let overlay_events;
         {
             let mut guard = logic.lock().unwrap();
+            let now = clock.now();
+            guard.sync_local_time(now, Local::now().naive_local());
             participants = guard.participants();
-            actions = guard.tick(clock.now(), rng().next_u32())
+            actions = guard.tick(now, rng().next_u32());
+            overlay_events = guard.take_events();
         };
 
+        metrics.ticked(Instant::now());
+        // Sending only fails if no overlay is connected, which is fine
+        for event in overlay_events {
+            if matches!(event, OverlayEvent::Registration { .. }) {
+                metrics.registration();
+            }
+            let _ = events.send(event);
+        }
+        let _ = events.send(OverlayEvent::Heartbeat);
+
         participants_file.update(participants);
 
         for action in actions {
@@ -77,23 +204,233 @@ async fn run_ticker(
                     show_notification(message).await;
                 }
                 Action::SendToWinner(winner) => {
-                    info!(
-                        "We have a winner: {} with address {}",
-                        winner.name,
-                        winner.account.encode_account()
-                    );
-
-                    match prize_sender.send_prize(winner.account, winner.prize).await {
-                        Ok(_) => info!("Prize sent!"),
-                        Err(e) => warn!("Could not send prize: {:?}", e),
+                    // Sent by the payout worker, so that the ticker keeps running
+                    let _ = payouts.send(winner);
+                }
+                Action::RecordRaffle(result) => {
+                    metrics.draw();
+                    let drawn_at = unix_now();
+                    let inserted = database
+                        .lock()
+                        .unwrap()
+                        .insert_raffle(stream_id, drawn_at, &result);
+                    match inserted {
+                        Ok(()) => logic.lock().unwrap().add_winner(WinnerRecord {
+                            raffle_id: result.id,
+                            stream_id,
+                            name: result.winner.name,
+                            channel_id: result.winner.channel_id,
+                            platform: result.winner.platform,
+                            account: result.winner.account,
+                            prize: result.prize,
+                            timestamp: drawn_at,
+                            participant_count: result.participants.len(),
+                            block_hash: None,
+                        }),
+                        Err(e) => warn!("Could not record raffle {}: {:?}", result.id, e),
+                    }
+                }
+                Action::SaveJackpot(pot) => {
+                    let result = database
+                        .lock()
+                        .unwrap()
+                        .save_setting(JACKPOT_SETTING, &pot.number().to_string());
+                    if let Err(e) = result {
+                        warn!("Could not save jackpot: {:?}", e);
                     }
                 }
+                Action::AuditPayout(audit) => {
+                    if let Err(e) = database.lock().unwrap().insert_audit(&audit, unix_now()) {
+                        warn!("Could not record payout decision: {:?}", e);
+                    }
+                }
+                Action::ResolveAlias(alias) => {
+                    // Lookups can be slow, so they must not block the ticker
+                    let resolver = alias_resolver.clone();
+                    let logic = logic.clone();
+                    tokio::spawn(async move {
+                        let result = resolver.resolve(&alias).await;
+                        if let Err(e) = &result {
+                            warn!("Could not resolve alias {}: {:?}", alias, e);
+                        }
+                        logic.lock().unwrap().alias_resolved(alias, result);
+                    });
+                }
             }
         }
         sleep(Duration::from_secs(1)).await
     }
 }
 
+/// Everything the payout worker needs to send the prizes
+struct PayoutWorker {
+    logic: Arc<Mutex<RaffleLogic>>,
+    database: Arc<Mutex<Database>>,
+    stream_id: i64,
+    prize_sender: PrizeSender,
+    metrics: Arc<Metrics>,
+}
+
+/// Sends the prizes one after the other. A payout takes at least 15s,
+/// so it must not block the ticker
+async fn run_payouts(worker: PayoutWorker, mut winners: UnboundedReceiver<Winner>) {
+    let PayoutWorker {
+        logic,
+        database,
+        stream_id,
+        prize_sender,
+        metrics,
+    } = worker;
+    while let Some(winner) = winners.recv().await {
+        info!(
+            "We have a winner: {} ({} on {}) with address {}",
+            winner.name,
+            winner.channel_id,
+            winner.platform.as_str(),
+            winner.account.encode_account()
+        );
+
+        // A retry must not pay again if the earlier send was published after all
+        let previous = find_previous_send(&logic, &database, &prize_sender, &winner).await;
+        let winner = if matches!(previous, Ok(None)) {
+            let Some(winner) = check_payout(&logic, &database, stream_id, winner).await else {
+                continue;
+            };
+            winner
+        } else {
+            winner
+        };
+
+        let (block_hash, error) = match previous {
+            Ok(Some(hash)) => {
+                info!("Prize for raffle {} was already sent", winner.raffle_id);
+                (Some(hash), None)
+            }
+            Ok(None) => {
+                let started = Instant::now();
+                let sent = prize_sender.send_prize(winner.account, winner.prize).await;
+                metrics.payout(sent.is_ok(), started.elapsed());
+                match sent {
+                    Ok(hash) => {
+                        info!("Prize sent!");
+                        (Some(hash.to_string()), None)
+                    }
+                    Err(e) => {
+                        warn!("Could not send prize: {:?}", e);
+                        (None, Some(format!("{:?}", e)))
+                    }
+                }
+            }
+            Err(e) => {
+                warn!("Could not check for an earlier payout: {:?}", e);
+                (
+                    None,
+                    Some(format!("could not check account history: {:?}", e)),
+                )
+            }
+        };
+
+        let status = match &block_hash {
+            Some(hash) => PayoutStatus::Sent(hash),
+            None => PayoutStatus::Failed(error.as_deref().unwrap_or_default()),
+        };
+        // The payout can only reference the raffle if it was recorded
+        let recorded = logic
+            .lock()
+            .unwrap()
+            .winners()
+            .get(winner.raffle_id)
+            .is_some();
+        if let Err(e) = database.lock().unwrap().insert_payout(
+            recorded.then_some(winner.raffle_id),
+            winner.account,
+            winner.prize,
+            status,
+            unix_now(),
+        ) {
+            warn!("Could not record payout: {:?}", e);
+        }
+
+        let mut guard = logic.lock().unwrap();
+        match block_hash {
+            Some(hash) => guard.payout_sent(winner.raffle_id, hash),
+            None => guard.payout_failed(winner.raffle_id),
+        }
+    }
+}
+
+/// Returns the hash of a send of the prize that was published since the raffle
+/// was drawn but never recorded as payout
+async fn find_previous_send(
+    logic: &Mutex<RaffleLogic>,
+    database: &Mutex<Database>,
+    prize_sender: &PrizeSender,
+    winner: &Winner,
+) -> anyhow::Result<Option<String>> {
+    let drawn_at = logic
+        .lock()
+        .unwrap()
+        .winners()
+        .get(winner.raffle_id)
+        .map(|w| w.timestamp);
+    let Some(drawn_at) = drawn_at else {
+        return Ok(None);
+    };
+    let recorded = database.lock().unwrap().sent_block_hashes(winner.account)?;
+    prize_sender
+        .find_unrecorded_send(winner.account, winner.prize, drawn_at, &recorded)
+        .await
+}
+
+/// Checks the payout limits and logs the decision in the audit trail. Returns
+/// the winner if the payout is allowed, otherwise it is held for the operator
+/// or marked as failed
+async fn check_payout(
+    logic: &Mutex<RaffleLogic>,
+    database: &Mutex<Database>,
+    stream_id: i64,
+    winner: Winner,
+) -> Option<Winner> {
+    let spending = database
+        .lock()
+        .unwrap()
+        .payout_spending(winner.account, stream_id, unix_now());
+    let decision = match spending {
+        Ok(spent) => logic.lock().unwrap().check_payout(&winner, &spent),
+        Err(e) => PolicyDecision::Reject(format!("could not check payout limits: {:?}", e)),
+    };
+
+    let audit = PayoutAudit::new(winner.raffle_id, winner.account, winner.prize, &decision);
+    if let Err(e) = database.lock().unwrap().insert_audit(&audit, unix_now()) {
+        warn!("Could not record payout decision: {:?}", e);
+    }
+
+    match decision {
+        PolicyDecision::Allow => Some(winner),
+        PolicyDecision::Hold(reason) => {
+            warn!("Payout for raffle {} held: {}", winner.raffle_id, reason);
+            show_notification(format!(
+                "Payout to {} needs approval: {}",
+                winner.name, reason
+            ))
+            .await;
+            logic.lock().unwrap().hold_payout(winner, reason);
+            None
+        }
+        PolicyDecision::Reject(reason) => {
+            warn!(
+                "Payout for raffle {} rejected: {}",
+                winner.raffle_id, reason
+            );
+            show_notification(format!("Payout to {} rejected: {}", winner.name, reason)).await;
+            logic.lock().unwrap().payout_failed(winner.raffle_id);
+            None
+        }
+    }
+}
+
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
 async fn show_notification(message: impl AsRef<OsStr>) {
     match Command::new("notify-send")
         .arg("-i")
@@ -1,11 +1,51 @@
 use rsnano_core::Account;
+use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
 
+/// Where a chat message or participant came from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Platform {
+    YouTube,
+    Twitch,
+    /// Entered by the operator in the GUI
+    Manual,
+}
+
+impl Platform {
+    /// Guesses the platform for records that were stored before the platform was tracked
+    pub fn from_channel_id(channel_id: &str) -> Self {
+        if channel_id.starts_with("twitch-") {
+            Self::Twitch
+        } else {
+            Self::YouTube
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::YouTube => "youtube",
+            Self::Twitch => "twitch",
+            Self::Manual => "manual",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "youtube" => Some(Self::YouTube),
+            "twitch" => Some(Self::Twitch),
+            "manual" => Some(Self::Manual),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(crate) struct ChatMessage {
     pub author_channel_id: String,
     pub author_name: Option<String>,
     pub message: String,
+    pub platform: Platform,
 }
 
 impl ChatMessage {
@@ -14,6 +54,7 @@ impl ChatMessage {
             author_name: Some("John Doe".to_owned()),
             author_channel_id: "abc".to_owned(),
             message: "test message".to_owned(),
+            platform: Platform::YouTube,
         }
     }
 
@@ -22,6 +63,7 @@ impl ChatMessage {
             author_name: Some("John Doe".to_owned()),
             author_channel_id: "abc".to_owned(),
             message: account.encode_account(),
+            platform: Platform::YouTube,
         }
     }
 }
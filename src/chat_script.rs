@@ -0,0 +1,112 @@
+use crate::{
+    chat_messages::{ChatMessage, Platform},
+    metrics::Metrics,
+};
+use anyhow::{anyhow, Context};
+use log::{info, warn};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::time::sleep;
+
+/// A chat message that is replayed after a delay
+#[derive(Debug, PartialEq)]
+struct ScriptLine {
+    delay: Duration,
+    message: ChatMessage,
+}
+
+/// Replays a chat script instead of listening to a real chat. Every line has
+/// the form `<seconds> <platform> <name> <message>`, where the seconds are the
+/// delay after the previous line. Empty lines and lines starting with `#` are ignored
+pub(crate) async fn listen_to_chat_script<F>(path: PathBuf, on_message: F, metrics: Arc<Metrics>)
+where
+    F: Fn(ChatMessage) + Send + Sync,
+{
+    let lines = match read_script(&path) {
+        Ok(lines) => lines,
+        Err(e) => {
+            warn!("Could not load chat script: {:?}", e);
+            return;
+        }
+    };
+
+    info!(
+        "Replaying {} chat messages from {}",
+        lines.len(),
+        path.display()
+    );
+    for line in lines {
+        sleep(line.delay).await;
+        metrics.chat_source_seen(line.message.platform, Instant::now());
+        on_message(line.message);
+    }
+    info!("Chat script finished");
+}
+
+fn read_script(path: &Path) -> anyhow::Result<Vec<ScriptLine>> {
+    let script = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read {}", path.display()))?;
+    parse_script(&script)
+}
+
+fn parse_script(script: &str) -> anyhow::Result<Vec<ScriptLine>> {
+    script
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| parse_line(line).with_context(|| format!("line {}", i + 1)))
+        .collect()
+}
+
+fn parse_line(line: &str) -> anyhow::Result<ScriptLine> {
+    let mut parts = line.trim().splitn(4, char::is_whitespace);
+    let (Some(delay), Some(platform), Some(name), Some(message)) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(anyhow!("expected <seconds> <platform> <name> <message>"));
+    };
+
+    let delay = delay
+        .parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| anyhow!("invalid delay: {}", delay))?;
+    let platform =
+        Platform::parse(platform).ok_or_else(|| anyhow!("unknown platform: {}", platform))?;
+
+    Ok(ScriptLine {
+        delay,
+        message: ChatMessage {
+            author_channel_id: format!("{}-{}", platform.as_str(), name),
+            author_name: Some(name.to_owned()),
+            message: message.trim().to_owned(),
+            platform,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let script = "# warm up\n\n0.5 twitch alice hello world\n2 youtube bob nano_1abc\n";
+        let lines = parse_script(script).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].delay, Duration::from_millis(500));
+        assert_eq!(lines[0].message.author_channel_id, "twitch-alice");
+        assert_eq!(lines[0].message.message, "hello world");
+        assert_eq!(lines[1].message.platform, Platform::YouTube);
+    }
+
+    #[test]
+    fn reject_invalid_lines() {
+        assert!(parse_script("1 twitch alice").is_err());
+        assert!(parse_script("x twitch alice hi").is_err());
+        assert!(parse_script("1 irc alice hi").is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{
+    database::Database,
+    export::{export_history, import_participants, ExportFormat},
+    http_server::{spinner_url, HttpConfig, SPINNER_TOKEN_SETTING},
+    keystore::{from_hex, KdfParams, Keystore, SecretKind},
+    participants::ParticipantRegistry,
+    participants_file::ParticipantsFile,
+};
+use anyhow::{anyhow, bail, Context};
+use log::{info, warn};
+use std::{
+    fs::{File, TryLockError},
+    path::Path,
+};
+use zeroize::Zeroizing;
+
+/// Runs a subcommand like `export` or `import`. Returns false if no subcommand was given
+pub(crate) fn run_command(args: &[String], database_path: &str) -> anyhow::Result<bool> {
+    let Some(command) = args.first() else {
+        return Ok(false);
+    };
+
+    match command.as_str() {
+        "export" => export(&args[1..], database_path)?,
+        "import" => import(&args[1..], database_path)?,
+        "keystore" => keystore(&args[1..])?,
+        "spinner-url" => print_spinner_url(database_path)?,
+        _ => bail!("unknown command: {}\n{}", command, USAGE),
+    }
+    Ok(true)
+}
+
+/// `export [csv|json] [DIR]`
+fn export(args: &[String], database_path: &str) -> anyhow::Result<()> {
+    let format = match args.first() {
+        Some(f) => ExportFormat::parse(f).ok_or_else(|| anyhow!("unknown format: {}", f))?,
+        None => ExportFormat::Csv,
+    };
+    let dir = args
+        .get(1)
+        .map(String::as_str)
+        .unwrap_or(DEFAULT_EXPORT_DIR);
+
+    let database = Database::open(database_path)?;
+    let participants = ParticipantsFile::default().load()?;
+    for file in export_history(&database, &participants, Path::new(dir), format)? {
+        info!("exported {}", file.display());
+    }
+    Ok(())
+}
+
+/// `import FILE.csv`. The running bot would overwrite the imported participants,
+/// so the import is refused while it runs
+fn import(args: &[String], database_path: &str) -> anyhow::Result<()> {
+    let path = args
+        .first()
+        .ok_or_else(|| anyhow!("no CSV file given\n{}", USAGE))?;
+    let _lock = lock_database(database_path).context("stop the bot before importing")?;
+    let report = import_participants(File::open(path)?)?;
+    for (line, reason) in &report.rejected {
+        warn!("line {} rejected: {}", line, reason);
+    }
+
+    let mut participants_file = ParticipantsFile::default();
+    let mut registry = ParticipantRegistry::default();
+    registry.set(participants_file.load()?);
+    registry.set(report.participants.clone());
+    participants_file.update(registry.list());
+
+    info!(
+        "imported {} participants, rejected {} lines",
+        report.participants.len(),
+        report.rejected.len()
+    );
+    Ok(())
+}
+
+/// `spinner-url`. Uses `RAFFLE_SPINNER_TOKEN` or the token which the bot generated
+fn print_spinner_url(database_path: &str) -> anyhow::Result<()> {
+    let config = HttpConfig::from_env();
+    let token = match config.spinner_token {
+        Some(token) => token,
+        None => Database::open(database_path)?
+            .setting(SPINNER_TOKEN_SETTING)?
+            .ok_or_else(|| anyhow!("no spinner token yet, start the bot once to generate it"))?,
+    };
+    info!("spinner page: {}", spinner_url(&config.bind, &token));
+    Ok(())
+}
+
+/// Makes sure that only one process changes the database and the participants.
+/// The lock is held until the file is dropped or the process exits
+pub(crate) fn lock_database(database_path: &str) -> anyhow::Result<File> {
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(format!("{}.lock", database_path))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(TryLockError::WouldBlock) => bail!("{} is used by a running raffle bot", database_path),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+/// `keystore create FILE [--seed] [--index N]` or `keystore account FILE`
+fn keystore(args: &[String]) -> anyhow::Result<()> {
+    match (args.first().map(String::as_str), args.get(1)) {
+        (Some("create"), Some(path)) => create_keystore(Path::new(path), &args[2..]),
+        (Some("account"), Some(path)) => {
+            let keystore = Keystore::load(Path::new(path))?;
+            let password = Zeroizing::new(rpassword::prompt_password("Keystore password: ")?);
+            let signer = keystore.unlock(&password)?;
+            info!("account: {}", signer.account().encode_account());
+            Ok(())
+        }
+        _ => bail!("invalid keystore command\n{}", USAGE),
+    }
+}
+
+fn create_keystore(path: &Path, options: &[String]) -> anyhow::Result<()> {
+    let mut kind = SecretKind::PrivateKey;
+    let mut index = 0;
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        match option.as_str() {
+            "--seed" => kind = SecretKind::Seed,
+            "--index" => {
+                let value = options
+                    .next()
+                    .ok_or_else(|| anyhow!("--index needs a value"))?;
+                index = value
+                    .parse()
+                    .map_err(|_| anyhow!("invalid index: {}", value))?;
+            }
+            _ => bail!("unknown option: {}\n{}", option, USAGE),
+        }
+    }
+
+    let prompt = match kind {
+        SecretKind::PrivateKey => "Private key (hex): ",
+        SecretKind::Seed => "Seed (hex): ",
+    };
+    let hex = Zeroizing::new(rpassword::prompt_password(prompt)?);
+    let bytes = Zeroizing::new(from_hex(&hex)?);
+    let secret: Zeroizing<[u8; 32]> = Zeroizing::new(
+        bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| anyhow!("the secret must be 32 bytes"))?,
+    );
+
+    let password = Zeroizing::new(rpassword::prompt_password("New keystore password: ")?);
+    let repeated = Zeroizing::new(rpassword::prompt_password("Repeat password: ")?);
+    if password.is_empty() {
+        bail!("the password must not be empty");
+    }
+    if password != repeated {
+        bail!("the passwords don't match");
+    }
+
+    let keystore = Keystore::encrypt(&secret, kind, index, &password, KdfParams::default())?;
+    let account = keystore.unlock(&password)?.account();
+    keystore.save(path)?;
+    info!("account: {}", account.encode_account());
+    Ok(())
+}
+
+pub(crate) const DEFAULT_EXPORT_DIR: &str = "export";
+
+const USAGE: &str = "usage:
+    raffle-bot                         run the raffle bot
+    raffle-bot export [csv|json] [DIR] export payouts, raffle entries and participants
+    raffle-bot import FILE.csv         import participants (channel_id,name,address[,platform])
+                                       while the bot isn't running
+    raffle-bot keystore create FILE [--seed] [--index N]
+                                       encrypt a private key or a seed with a password
+    raffle-bot keystore account FILE   show the account of a keystore
+    raffle-bot spinner-url             show the link to the spinner page with its token";
@@ -0,0 +1,773 @@
+use crate::{
+    chat_messages::Platform,
+    payout_policy::{PayoutAudit, Spending},
+    raffle_runner::RaffleResult,
+    winners::WinnerRecord,
+};
+use anyhow::anyhow;
+use rsnano_core::{Account, Amount};
+use rusqlite::{
+    params, types::Type, Connection, Error::FromSqlConversionFailure, OptionalExtension, Params,
+    Row,
+};
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Embedded SQLite store for everything that has to survive a restart
+pub(crate) struct Database {
+    conn: Connection,
+}
+
+/// Outcome of a prize payout
+#[derive(Clone, Copy)]
+pub(crate) enum PayoutStatus<'a> {
+    /// Contains the block hash of the send block
+    Sent(&'a str),
+    Failed(&'a str),
+}
+
+impl Database {
+    pub(crate) fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn open_in_memory() -> anyhow::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(conn: Connection) -> anyhow::Result<Self> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        let db = Self { conn };
+        db.migrate()?;
+        Ok(db)
+    }
+
+    fn migrate(&self) -> anyhow::Result<()> {
+        let version: usize = self
+            .conn
+            .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+        for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+            self.conn.execute_batch(migration)?;
+            self.conn
+                .execute_batch(&format!("PRAGMA user_version = {}", i + 1))?;
+        }
+        Ok(())
+    }
+
+    /// Starts a new stream session and returns its id
+    pub(crate) fn start_stream(&self, started_at: u64) -> anyhow::Result<i64> {
+        self.conn
+            .execute("INSERT INTO streams (started_at) VALUES (?1)", [started_at])?;
+        Ok(self.conn.last_insert_rowid())
+    }
+
+    pub(crate) fn end_stream(&self, stream_id: i64, ended_at: u64) -> anyhow::Result<()> {
+        self.conn.execute(
+            "UPDATE streams SET ended_at = ?1 WHERE id = ?2",
+            params![ended_at, stream_id],
+        )?;
+        Ok(())
+    }
+
+    /// Stores a finished raffle together with its entries and winner
+    pub(crate) fn insert_raffle(
+        &mut self,
+        stream_id: i64,
+        drawn_at: u64,
+        result: &RaffleResult,
+    ) -> anyhow::Result<()> {
+        let tx = self.conn.transaction()?;
+        let raffle_id = result.id;
+        tx.execute(
+            "INSERT INTO raffles
+             (id, stream_id, drawn_at, prize_raw, participant_count, random, name)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                raffle_id,
+                stream_id,
+                drawn_at,
+                amount_to_sql(result.prize),
+                result.participants.len(),
+                result.random,
+                result.raffle
+            ],
+        )?;
+
+        for p in &result.participants {
+            tx.execute(
+                "INSERT OR IGNORE INTO entries (raffle_id, channel_id, name, account)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![raffle_id, p.channel_id, p.name, p.account.encode_account()],
+            )?;
+        }
+
+        tx.execute(
+            "INSERT INTO winners (raffle_id, channel_id, name, account, platform)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                raffle_id,
+                result.winner.channel_id,
+                result.winner.name,
+                result.winner.account.encode_account(),
+                result.winner.platform.as_str()
+            ],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The highest raffle id that was used so far
+    pub(crate) fn max_raffle_id(&self) -> anyhow::Result<u64> {
+        let id = self
+            .conn
+            .query_row("SELECT COALESCE(MAX(id), 0) FROM raffles", [], |row| {
+                row.get(0)
+            })?;
+        Ok(id)
+    }
+
+    pub(crate) fn insert_payout(
+        &self,
+        raffle_id: Option<u64>,
+        account: Account,
+        amount: Amount,
+        status: PayoutStatus,
+        created_at: u64,
+    ) -> anyhow::Result<()> {
+        let (status, block_hash, error) = match status {
+            PayoutStatus::Sent(hash) => ("sent", Some(hash), None),
+            PayoutStatus::Failed(error) => ("failed", None, Some(error)),
+        };
+        self.conn.execute(
+            "INSERT INTO payouts (raffle_id, account, amount_raw, status, block_hash, error, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                raffle_id,
+                account.encode_account(),
+                amount_to_sql(amount),
+                status,
+                block_hash,
+                error,
+                created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Hashes of the send blocks of all recorded payouts to the account
+    pub(crate) fn sent_block_hashes(&self, account: Account) -> anyhow::Result<HashSet<String>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT block_hash FROM payouts
+             WHERE status = 'sent' AND account = ?1 AND block_hash IS NOT NULL",
+        )?;
+        let hashes = stmt
+            .query_map([account.encode_account()], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        Ok(hashes)
+    }
+
+    /// Sums up the sent payouts which count against the payout limits
+    pub(crate) fn payout_spending(
+        &self,
+        account: Account,
+        stream_id: i64,
+        now: u64,
+    ) -> anyhow::Result<Spending> {
+        let mut stmt = self.conn.prepare(
+            "SELECT p.account, p.amount_raw, p.created_at, r.stream_id = ?1
+             FROM payouts p
+             LEFT JOIN raffles r ON r.id = p.raffle_id
+             WHERE p.status = 'sent' AND (p.created_at >= ?2 OR r.stream_id = ?1)",
+        )?;
+        let day_start = now.saturating_sub(24 * 60 * 60);
+        let hour_start = now.saturating_sub(60 * 60);
+        let rows = stmt
+            .query_map(params![stream_id, day_start], |row| {
+                Ok((
+                    parse_column(row, 0, account_from_sql)?,
+                    parse_column(row, 1, amount_from_sql)?,
+                    row.get::<_, u64>(2)?,
+                    row.get::<_, Option<bool>>(3)?.unwrap_or_default(),
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut last_hour = 0u128;
+        let mut last_day = 0u128;
+        let mut stream = 0u128;
+        let mut recipient = 0u128;
+        for (payee, amount, created_at, in_stream) in rows {
+            let amount = amount.number();
+            if created_at >= hour_start {
+                last_hour += amount;
+            }
+            if created_at >= day_start {
+                last_day += amount;
+            }
+            if in_stream {
+                stream += amount;
+                if payee == account {
+                    recipient += amount;
+                }
+            }
+        }
+        Ok(Spending {
+            last_hour: Amount::raw(last_hour),
+            last_day: Amount::raw(last_day),
+            stream: Amount::raw(stream),
+            recipient: Amount::raw(recipient),
+        })
+    }
+
+    pub(crate) fn insert_audit(&self, audit: &PayoutAudit, created_at: u64) -> anyhow::Result<()> {
+        self.conn.execute(
+            "INSERT INTO payout_audit (raffle_id, account, amount_raw, decision, reason, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                audit.raffle_id,
+                audit.account.encode_account(),
+                amount_to_sql(audit.amount),
+                audit.decision,
+                audit.reason,
+                created_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The latest decisions about payouts, newest first
+    pub(crate) fn payout_audit(&self, limit: usize) -> anyhow::Result<Vec<AuditRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT raffle_id, account, amount_raw, decision, reason, created_at
+             FROM payout_audit ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt
+            .query_map([limit], |row| {
+                Ok(AuditRow {
+                    raffle_id: row.get(0)?,
+                    account: parse_column(row, 1, account_from_sql)?,
+                    amount: parse_column(row, 2, amount_from_sql)?,
+                    decision: row.get(3)?,
+                    reason: row.get(4)?,
+                    created_at: row.get(5)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    pub(crate) fn insert_ban(&self, channel_id: &str, banned_at: u64) -> anyhow::Result<()> {
+        self.conn.execute(
+            "INSERT OR IGNORE INTO bans (channel_id, banned_at) VALUES (?1, ?2)",
+            params![channel_id, banned_at],
+        )?;
+        Ok(())
+    }
+
+    /// Channel ids of all banned viewers
+    pub(crate) fn bans(&self) -> anyhow::Result<Vec<String>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT channel_id FROM bans ORDER BY channel_id")?;
+        let bans = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(bans)
+    }
+
+    pub(crate) fn setting(&self, key: &str) -> anyhow::Result<Option<String>> {
+        Ok(self
+            .conn
+            .query_row("SELECT value FROM settings WHERE key = ?1", [key], |row| {
+                row.get(0)
+            })
+            .optional()?)
+    }
+
+    pub(crate) fn save_setting(&self, key: &str, value: &str) -> anyhow::Result<()> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+            params![key, value],
+        )?;
+        Ok(())
+    }
+
+    /// All winners, oldest first
+    pub(crate) fn winner_history(&self) -> anyhow::Result<Vec<WinnerRecord>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT r.id, r.stream_id, w.name, w.channel_id, w.platform, w.account,
+                r.prize_raw, r.drawn_at, r.participant_count,
+                (SELECT p.block_hash FROM payouts p
+                 WHERE p.raffle_id = r.id AND p.status = 'sent'
+                 ORDER BY p.id DESC LIMIT 1)
+             FROM winners w JOIN raffles r ON r.id = w.raffle_id
+             ORDER BY r.id",
+        )?;
+        let records = stmt
+            .query_map([], |row| {
+                let channel_id: String = row.get(3)?;
+                let platform: String = row.get(4)?;
+                Ok(WinnerRecord {
+                    raffle_id: row.get(0)?,
+                    stream_id: row.get(1)?,
+                    name: row.get(2)?,
+                    platform: Platform::parse(&platform)
+                        .unwrap_or_else(|| Platform::from_channel_id(&channel_id)),
+                    channel_id,
+                    account: parse_column(row, 5, account_from_sql)?,
+                    prize: parse_column(row, 6, amount_from_sql)?,
+                    timestamp: row.get(7)?,
+                    participant_count: row.get(8)?,
+                    block_hash: row.get(9)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(records)
+    }
+
+    /// All payouts, oldest first
+    pub(crate) fn payouts(&self) -> anyhow::Result<Vec<PayoutRow>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT p.id, p.raffle_id, r.stream_id, w.name, w.channel_id, p.account,
+                p.amount_raw, p.status, p.block_hash, p.error, p.created_at
+             FROM payouts p
+             LEFT JOIN raffles r ON r.id = p.raffle_id
+             LEFT JOIN winners w ON w.raffle_id = p.raffle_id
+             ORDER BY p.id",
+        )?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(PayoutRow {
+                    payout_id: row.get(0)?,
+                    raffle_id: row.get(1)?,
+                    stream_id: row.get(2)?,
+                    winner_name: row.get(3)?,
+                    channel_id: row.get(4)?,
+                    account: parse_column(row, 5, account_from_sql)?,
+                    amount: parse_column(row, 6, amount_from_sql)?,
+                    status: row.get(7)?,
+                    block_hash: row.get(8)?,
+                    error: row.get(9)?,
+                    created_at: row.get(10)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    /// The participants of every raffle, ordered by raffle
+    pub(crate) fn entries(&self) -> anyhow::Result<Vec<EntryRow>> {
+        self.query_entries("", [])
+    }
+
+    /// The participants of a single raffle
+    pub(crate) fn raffle_entries(&self, raffle_id: u64) -> anyhow::Result<Vec<EntryRow>> {
+        self.query_entries("WHERE e.raffle_id = ?1", [raffle_id])
+    }
+
+    fn query_entries(&self, filter: &str, params: impl Params) -> anyhow::Result<Vec<EntryRow>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT e.raffle_id, r.stream_id, r.drawn_at, e.channel_id, e.name, e.account,
+                w.channel_id IS NOT NULL
+             FROM entries e
+             JOIN raffles r ON r.id = e.raffle_id
+             LEFT JOIN winners w ON w.raffle_id = e.raffle_id AND w.channel_id = e.channel_id
+             {}
+             ORDER BY e.raffle_id, e.channel_id",
+            filter
+        ))?;
+        let rows = stmt
+            .query_map(params, |row| {
+                Ok(EntryRow {
+                    raffle_id: row.get(0)?,
+                    stream_id: row.get(1)?,
+                    drawn_at: row.get(2)?,
+                    channel_id: row.get(3)?,
+                    name: row.get(4)?,
+                    account: parse_column(row, 5, account_from_sql)?,
+                    won: row.get(6)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+}
+
+/// A single payout with the raffle it belongs to
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct PayoutRow {
+    pub payout_id: i64,
+    pub raffle_id: Option<u64>,
+    pub stream_id: Option<i64>,
+    pub winner_name: Option<String>,
+    pub channel_id: Option<String>,
+    pub account: Account,
+    pub amount: Amount,
+    pub status: String,
+    pub block_hash: Option<String>,
+    pub error: Option<String>,
+    pub created_at: u64,
+}
+
+/// A logged decision about a payout
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct AuditRow {
+    pub raffle_id: u64,
+    pub account: Account,
+    pub amount: Amount,
+    pub decision: String,
+    pub reason: String,
+    pub created_at: u64,
+}
+
+/// A participant that took part in a raffle
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct EntryRow {
+    pub raffle_id: u64,
+    pub stream_id: i64,
+    pub drawn_at: u64,
+    pub channel_id: String,
+    pub name: String,
+    pub account: Account,
+    pub won: bool,
+}
+
+/// Amounts don't fit into an SQLite integer, so they are stored as decimal raw strings
+fn amount_to_sql(amount: Amount) -> String {
+    amount.number().to_string()
+}
+
+/// Reads a text column and converts it with the given function
+fn parse_column<T>(
+    row: &Row,
+    index: usize,
+    convert: impl FnOnce(&str) -> anyhow::Result<T>,
+) -> rusqlite::Result<T> {
+    let text: String = row.get(index)?;
+    convert(&text).map_err(|e| FromSqlConversionFailure(index, Type::Text, e.into()))
+}
+
+fn amount_from_sql(raw: &str) -> anyhow::Result<Amount> {
+    Ok(Amount::raw(raw.parse::<u128>()?))
+}
+
+fn account_from_sql(account: &str) -> anyhow::Result<Account> {
+    Account::decode_account(account)
+        .map_err(|_| anyhow!("invalid account in database: {}", account))
+}
+
+pub(crate) fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE streams (
+        id INTEGER PRIMARY KEY,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER
+    );
+
+    CREATE TABLE raffles (
+        id INTEGER PRIMARY KEY,
+        stream_id INTEGER NOT NULL REFERENCES streams(id),
+        drawn_at INTEGER NOT NULL,
+        prize_raw TEXT NOT NULL,
+        participant_count INTEGER NOT NULL
+    );
+
+    CREATE TABLE entries (
+        raffle_id INTEGER NOT NULL REFERENCES raffles(id),
+        channel_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        account TEXT NOT NULL,
+        PRIMARY KEY (raffle_id, channel_id)
+    );
+
+    CREATE TABLE winners (
+        raffle_id INTEGER PRIMARY KEY REFERENCES raffles(id),
+        channel_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        account TEXT NOT NULL
+    );
+
+    CREATE TABLE payouts (
+        id INTEGER PRIMARY KEY,
+        raffle_id INTEGER REFERENCES raffles(id),
+        account TEXT NOT NULL,
+        amount_raw TEXT NOT NULL,
+        status TEXT NOT NULL,
+        error TEXT,
+        created_at INTEGER NOT NULL
+    );
+
+    CREATE INDEX raffles_drawn_at ON raffles (drawn_at);
+",
+    "
+    ALTER TABLE payouts ADD COLUMN block_hash TEXT;
+    ALTER TABLE winners ADD COLUMN platform TEXT NOT NULL DEFAULT 'youtube';
+    UPDATE winners SET platform = 'twitch' WHERE channel_id LIKE 'twitch-%';
+    CREATE INDEX payouts_raffle_id ON payouts (raffle_id);
+",
+    "
+    CREATE TABLE bans (
+        channel_id TEXT PRIMARY KEY,
+        banned_at INTEGER NOT NULL
+    );
+",
+    "
+    CREATE TABLE payout_audit (
+        id INTEGER PRIMARY KEY,
+        raffle_id INTEGER NOT NULL,
+        account TEXT NOT NULL,
+        amount_raw TEXT NOT NULL,
+        decision TEXT NOT NULL,
+        reason TEXT NOT NULL,
+        created_at INTEGER NOT NULL
+    );
+    CREATE INDEX payouts_created_at ON payouts (created_at);
+",
+    "
+    CREATE TABLE settings (
+        key TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+",
+    "
+    ALTER TABLE raffles ADD COLUMN random INTEGER;
+",
+    "
+    ALTER TABLE raffles ADD COLUMN name TEXT;
+",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{draw_animation::AnimationMode, participants::Participant};
+
+    #[test]
+    fn empty() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.winner_history().unwrap(), Vec::new());
+        assert_eq!(db.max_raffle_id().unwrap(), 0);
+        assert!(db.bans().unwrap().is_empty());
+    }
+
+    #[test]
+    fn sent_block_hashes() {
+        let db = Database::open_in_memory().unwrap();
+        let account = Account::from(1);
+        db.insert_payout(None, account, Amount::nano(1), PayoutStatus::Sent("ABC"), 1)
+            .unwrap();
+        db.insert_payout(None, account, Amount::nano(1), PayoutStatus::Failed("x"), 2)
+            .unwrap();
+        db.insert_payout(
+            None,
+            Account::from(2),
+            Amount::nano(1),
+            PayoutStatus::Sent("DEF"),
+            3,
+        )
+        .unwrap();
+        assert_eq!(
+            db.sent_block_hashes(account).unwrap(),
+            HashSet::from(["ABC".to_owned()])
+        );
+    }
+
+    #[test]
+    fn bans() {
+        let db = Database::open_in_memory().unwrap();
+        db.insert_ban("b", 1000).unwrap();
+        db.insert_ban("a", 1000).unwrap();
+        db.insert_ban("a", 1001).unwrap();
+        assert_eq!(db.bans().unwrap(), vec!["a".to_owned(), "b".to_owned()]);
+    }
+
+    #[test]
+    fn settings() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(db.setting("schedule").unwrap(), None);
+        db.save_setting("schedule", "hourly").unwrap();
+        db.save_setting("schedule", "final").unwrap();
+        assert_eq!(db.setting("schedule").unwrap(), Some("final".to_owned()));
+    }
+
+    #[test]
+    fn migrate_twice() {
+        let db = Database::open_in_memory().unwrap();
+        db.migrate().unwrap();
+    }
+
+    #[test]
+    fn store_raffle() {
+        let mut db = Database::open_in_memory().unwrap();
+        let stream_id = db.start_stream(1000).unwrap();
+        let alice = Participant::new_test_instance_for_channel("a");
+        let bob = Participant::new_test_instance_for_channel("b");
+        let raffle_id = 5;
+        let result = RaffleResult {
+            id: raffle_id,
+            raffle: "main".to_owned(),
+            winner: bob.clone(),
+            participants: vec![alice, bob.clone()],
+            prize: Amount::nano(1),
+            animation: AnimationMode::Wheel,
+            random: 0,
+            prize_reveal: None,
+        };
+
+        db.insert_raffle(stream_id, 1001, &result).unwrap();
+        assert_eq!(db.max_raffle_id().unwrap(), raffle_id);
+        db.insert_payout(
+            Some(raffle_id),
+            bob.account,
+            Amount::nano(1),
+            PayoutStatus::Failed("timeout"),
+            1002,
+        )
+        .unwrap();
+        db.insert_payout(
+            Some(raffle_id),
+            bob.account,
+            Amount::nano(1),
+            PayoutStatus::Sent("ABC"),
+            1003,
+        )
+        .unwrap();
+        db.end_stream(stream_id, 1004).unwrap();
+
+        assert_eq!(
+            db.winner_history().unwrap(),
+            vec![WinnerRecord {
+                raffle_id,
+                stream_id,
+                name: bob.name,
+                channel_id: bob.channel_id,
+                platform: bob.platform,
+                account: bob.account,
+                prize: Amount::nano(1),
+                timestamp: 1001,
+                participant_count: 2,
+                block_hash: Some("ABC".to_owned()),
+            }]
+        );
+        let entries = db.raffle_entries(raffle_id).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[1].won);
+        assert!(db.raffle_entries(raffle_id + 1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn sum_payout_spending() {
+        let mut db = Database::open_in_memory().unwrap();
+        let old_stream = db.start_stream(1000).unwrap();
+        let stream_id = db.start_stream(100_000).unwrap();
+        let alice = Participant {
+            account: Account::from(1),
+            ..Participant::new_test_instance_for_channel("a")
+        };
+        let bob = Participant::new_test_instance_for_channel("b");
+        for (id, stream, winner) in [(1, old_stream, &alice), (2, stream_id, &bob)] {
+            let result = RaffleResult {
+                id,
+                raffle: "main".to_owned(),
+                winner: winner.clone(),
+                participants: vec![alice.clone(), bob.clone()],
+                prize: Amount::nano(1),
+                animation: AnimationMode::Wheel,
+                random: 0,
+                prize_reveal: None,
+            };
+            db.insert_raffle(stream, 1001, &result).unwrap();
+        }
+        let sent = PayoutStatus::Sent("ABC");
+        db.insert_payout(Some(1), alice.account, Amount::nano(1), sent, 1002)
+            .unwrap();
+        db.insert_payout(Some(2), bob.account, Amount::nano(2), sent, 100_000)
+            .unwrap();
+        let failed = PayoutStatus::Failed("timeout");
+        db.insert_payout(Some(2), bob.account, Amount::nano(4), failed, 100_000)
+            .unwrap();
+
+        let spending = db.payout_spending(bob.account, stream_id, 101_000).unwrap();
+        assert_eq!(
+            spending,
+            Spending {
+                last_hour: Amount::nano(2),
+                last_day: Amount::nano(2),
+                stream: Amount::nano(2),
+                recipient: Amount::nano(2),
+            }
+        );
+        let spending = db
+            .payout_spending(alice.account, stream_id, 101_000)
+            .unwrap();
+        assert_eq!(spending.recipient, Amount::raw(0));
+    }
+
+    #[test]
+    fn store_audit() {
+        let db = Database::open_in_memory().unwrap();
+        let audit = PayoutAudit {
+            raffle_id: 1,
+            account: Account::from(1),
+            amount: Amount::nano(1),
+            decision: "held",
+            reason: "daily budget exceeded".to_owned(),
+        };
+        db.insert_audit(&audit, 1000).unwrap();
+        let rows = db.payout_audit(10).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].decision, "held");
+        assert_eq!(rows[0].reason, audit.reason);
+    }
+
+    #[test]
+    fn export_payouts_and_entries() {
+        let mut db = Database::open_in_memory().unwrap();
+        let stream_id = db.start_stream(1000).unwrap();
+        let alice = Participant::new_test_instance_for_channel("a");
+        let bob = Participant::new_test_instance_for_channel("b");
+        let raffle_id = 1;
+        let result = RaffleResult {
+            id: raffle_id,
+            raffle: "main".to_owned(),
+            winner: bob.clone(),
+            participants: vec![alice, bob.clone()],
+            prize: Amount::nano(1),
+            animation: AnimationMode::Wheel,
+            random: 0,
+            prize_reveal: None,
+        };
+        db.insert_raffle(stream_id, 1001, &result).unwrap();
+        db.insert_payout(
+            Some(raffle_id),
+            bob.account,
+            Amount::nano(1),
+            PayoutStatus::Sent("ABC"),
+            1002,
+        )
+        .unwrap();
+
+        let payouts = db.payouts().unwrap();
+        assert_eq!(payouts.len(), 1);
+        assert_eq!(payouts[0].winner_name, Some(bob.name));
+        assert_eq!(payouts[0].stream_id, Some(stream_id));
+
+        let entries = db.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert!(!entries[0].won);
+        assert!(entries[1].won);
+    }
+}
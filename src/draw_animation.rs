@@ -0,0 +1,166 @@
+use anyhow::anyhow;
+use serde::Serialize;
+
+/// How the overlay reveals the winner
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum AnimationMode {
+    Wheel,
+    SlotReel,
+    CardShuffle,
+    Elimination,
+    NameScroll,
+}
+
+impl AnimationMode {
+    pub const ALL: [AnimationMode; 5] = [
+        AnimationMode::Wheel,
+        AnimationMode::SlotReel,
+        AnimationMode::CardShuffle,
+        AnimationMode::Elimination,
+        AnimationMode::NameScroll,
+    ];
+
+    /// Uses the wheel as long as its labels are readable
+    pub fn auto(participant_count: usize) -> Self {
+        if participant_count <= AnimationMode::Wheel.max_visible() {
+            AnimationMode::Wheel
+        } else {
+            AnimationMode::SlotReel
+        }
+    }
+
+    /// How many names the animation can show. Larger pools are sampled
+    pub fn max_visible(&self) -> usize {
+        match self {
+            AnimationMode::Wheel => 24,
+            AnimationMode::SlotReel => 50,
+            AnimationMode::CardShuffle => 12,
+            AnimationMode::Elimination => 16,
+            AnimationMode::NameScroll => 100,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnimationMode::Wheel => "wheel",
+            AnimationMode::SlotReel => "slot_reel",
+            AnimationMode::CardShuffle => "card_shuffle",
+            AnimationMode::Elimination => "elimination",
+            AnimationMode::NameScroll => "name_scroll",
+        }
+    }
+
+    /// Parses the `RAFFLE_ANIMATION` env var. `auto` chooses by the number of participants
+    pub fn from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("RAFFLE_ANIMATION") {
+            Ok(value) => Self::parse(&value),
+            Err(_) => Ok(None),
+        }
+    }
+
+    pub fn parse(value: &str) -> anyhow::Result<Option<Self>> {
+        let value = value.trim();
+        if value == "auto" {
+            return Ok(None);
+        }
+        Self::ALL
+            .into_iter()
+            .find(|m| m.as_str() == value)
+            .map(Some)
+            .ok_or_else(|| anyhow!("unknown animation: {}", value))
+    }
+}
+
+/// Picks at most `max` names that are shown in the animation. The winner is
+/// always part of the sample, the other names are chosen by `seed`. Returns
+/// the sampled names and the index of the winner in them
+pub(crate) fn sample_visible(
+    names: Vec<String>,
+    winner: usize,
+    max: usize,
+    seed: u64,
+) -> (Vec<String>, usize) {
+    if names.len() <= max || max == 0 {
+        return (names, winner);
+    }
+
+    let mut others: Vec<usize> = (0..names.len()).filter(|i| *i != winner).collect();
+    let mut rng = SplitMix64(seed);
+    // Partial Fisher-Yates: the first max - 1 entries are a random sample
+    for i in 0..max - 1 {
+        let j = i + (rng.next() % (others.len() - i) as u64) as usize;
+        others.swap(i, j);
+    }
+    let mut picked = others[..max - 1].to_vec();
+    picked.push(winner);
+    // Keep the registration order, so that the sample looks like the full list
+    picked.sort_unstable();
+
+    let winner_index = picked.iter().position(|i| *i == winner).unwrap_or_default();
+    let mut names: Vec<Option<String>> = names.into_iter().map(Some).collect();
+    let sample = picked.iter().filter_map(|i| names[*i].take()).collect();
+    (sample, winner_index)
+}
+
+/// Small deterministic generator, so that anything derived from a seed can be reproduced
+pub(crate) struct SplitMix64(pub u64);
+
+impl SplitMix64 {
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("viewer{}", i)).collect()
+    }
+
+    #[test]
+    fn small_pool_is_not_sampled() {
+        let (sample, winner) = sample_visible(names(5), 3, 24, 1);
+        assert_eq!(sample, names(5));
+        assert_eq!(winner, 3);
+    }
+
+    #[test]
+    fn sample_contains_winner() {
+        for seed in 0..50 {
+            let (sample, winner) = sample_visible(names(500), 321, 24, seed);
+            assert_eq!(sample.len(), 24);
+            assert_eq!(sample[winner], "viewer321");
+        }
+    }
+
+    #[test]
+    fn sample_is_deterministic() {
+        assert_eq!(
+            sample_visible(names(100), 7, 10, 42),
+            sample_visible(names(100), 7, 10, 42)
+        );
+    }
+
+    #[test]
+    fn auto_mode() {
+        assert_eq!(AnimationMode::auto(24), AnimationMode::Wheel);
+        assert_eq!(AnimationMode::auto(25), AnimationMode::SlotReel);
+    }
+
+    #[test]
+    fn parse_mode() {
+        assert_eq!(AnimationMode::parse("auto").unwrap(), None);
+        assert_eq!(
+            AnimationMode::parse("card_shuffle").unwrap(),
+            Some(AnimationMode::CardShuffle)
+        );
+        assert!(AnimationMode::parse("dice").is_err());
+    }
+}
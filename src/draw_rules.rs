@@ -0,0 +1,124 @@
+use anyhow::anyhow;
+use rsnano_core::Amount;
+
+/// What happens when a raffle is due but too few viewers participate
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum ShortfallAction {
+    /// Wait until enough viewers have entered
+    #[default]
+    Postpone,
+    /// Skip the draw and add its prize to the next one
+    RollOver,
+    /// Skip the draw
+    Cancel,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct DrawRules {
+    pub min_participants: usize,
+    pub when_not_met: ShortfallAction,
+}
+
+impl Default for DrawRules {
+    fn default() -> Self {
+        Self {
+            min_participants: 1,
+            when_not_met: ShortfallAction::Postpone,
+        }
+    }
+}
+
+impl DrawRules {
+    /// Reads `RAFFLE_MIN_PARTICIPANTS` and `RAFFLE_SHORTFALL`, which is
+    /// `postpone` (default), `rollover` or `cancel`
+    pub(crate) fn from_env() -> anyhow::Result<Self> {
+        let mut rules = Self::default();
+        if let Ok(min) = std::env::var("RAFFLE_MIN_PARTICIPANTS") {
+            rules.min_participants = min
+                .parse()
+                .map_err(|_| anyhow!("invalid RAFFLE_MIN_PARTICIPANTS: {}", min))?;
+        }
+        if let Ok(action) = std::env::var("RAFFLE_SHORTFALL") {
+            rules.when_not_met = ShortfallAction::parse(&action)?;
+        }
+        Ok(rules)
+    }
+
+    /// At least one participant is always needed to draw a winner
+    pub(crate) fn required(&self) -> usize {
+        self.min_participants.max(1)
+    }
+}
+
+impl ShortfallAction {
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "postpone" => Ok(Self::Postpone),
+            "rollover" => Ok(Self::RollOver),
+            "cancel" => Ok(Self::Cancel),
+            _ => Err(anyhow!("invalid shortfall action: {}", s)),
+        }
+    }
+}
+
+/// A due raffle that wasn't drawn because too few viewers participated
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Shortfall {
+    pub participants: usize,
+    pub required: usize,
+    pub action: ShortfallAction,
+    /// Prize of the next raffle, which includes a rolled over prize
+    pub next_prize: Amount,
+}
+
+impl Shortfall {
+    /// Explains the viewers why there is no draw
+    pub(crate) fn message(&self) -> String {
+        let reason = format!(
+            "Only {} of {} required participants entered",
+            self.participants, self.required
+        );
+        match self.action {
+            ShortfallAction::Postpone => {
+                format!("{}, the raffle waits for more viewers to enter!", reason)
+            }
+            ShortfallAction::RollOver => format!(
+                "{}, the prize rolls over! The next raffle is worth Ӿ {}",
+                reason,
+                self.next_prize.format_balance(2)
+            ),
+            ShortfallAction::Cancel => format!("{}, this raffle is cancelled", reason),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            ShortfallAction::parse("postpone").unwrap(),
+            ShortfallAction::Postpone
+        );
+        assert_eq!(
+            ShortfallAction::parse("rollover").unwrap(),
+            ShortfallAction::RollOver
+        );
+        assert_eq!(
+            ShortfallAction::parse("cancel").unwrap(),
+            ShortfallAction::Cancel
+        );
+        assert!(ShortfallAction::parse("skip").is_err());
+    }
+
+    #[test]
+    fn at_least_one_participant_required() {
+        let rules = DrawRules {
+            min_participants: 0,
+            ..Default::default()
+        };
+        assert_eq!(rules.required(), 1);
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{amounts::parse_xno, metrics::Metrics};
+use anyhow::{anyhow, bail};
+use log::info;
+use rand::{rng, Rng, RngCore};
+use rsnano_core::{Account, Amount, BlockHash};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::time::sleep;
+
+/// Runs the bot without a node and without real chats, for demos and rehearsals
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct DryRunConfig {
+    /// How long a simulated payout takes
+    pub latency: Duration,
+    /// Share of the payouts that fail, between 0 and 1
+    pub failure_rate: f64,
+    /// Starting balance of the simulated wallet
+    pub balance: Amount,
+    /// Chat messages are read from this file instead of Twitch and YouTube
+    pub chat_script: Option<PathBuf>,
+}
+
+impl Default for DryRunConfig {
+    fn default() -> Self {
+        Self {
+            latency: Duration::from_secs(2),
+            failure_rate: 0.0,
+            balance: Amount::nano(1000),
+            chat_script: None,
+        }
+    }
+}
+
+impl DryRunConfig {
+    /// Returns the config if `RAFFLE_DRY_RUN` is enabled. The simulation is tuned with
+    /// `RAFFLE_DRY_RUN_LATENCY` (seconds), `RAFFLE_DRY_RUN_FAILURE_RATE` (0 to 1),
+    /// `RAFFLE_DRY_RUN_BALANCE` (XNO) and `RAFFLE_CHAT_SCRIPT`
+    pub(crate) fn from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("RAFFLE_DRY_RUN").as_deref() {
+            Err(_) | Ok("" | "0" | "off" | "false") => return Ok(None),
+            Ok(_) => {}
+        }
+
+        let mut config = Self::default();
+        if let Ok(latency) = std::env::var("RAFFLE_DRY_RUN_LATENCY") {
+            let secs: f64 = latency
+                .parse()
+                .map_err(|_| anyhow!("invalid RAFFLE_DRY_RUN_LATENCY: {}", latency))?;
+            config.latency = Duration::try_from_secs_f64(secs)
+                .map_err(|_| anyhow!("invalid RAFFLE_DRY_RUN_LATENCY: {}", latency))?;
+        }
+        if let Ok(rate) = std::env::var("RAFFLE_DRY_RUN_FAILURE_RATE") {
+            config.failure_rate = rate
+                .parse()
+                .map_err(|_| anyhow!("invalid RAFFLE_DRY_RUN_FAILURE_RATE: {}", rate))?;
+            if !(0.0..=1.0).contains(&config.failure_rate) {
+                bail!("RAFFLE_DRY_RUN_FAILURE_RATE must be between 0 and 1");
+            }
+        }
+        if let Ok(balance) = std::env::var("RAFFLE_DRY_RUN_BALANCE") {
+            config.balance = parse_xno(&balance)
+                .map_err(|_| anyhow!("invalid RAFFLE_DRY_RUN_BALANCE: {}", balance))?;
+        }
+        config.chat_script = std::env::var_os("RAFFLE_CHAT_SCRIPT").map(PathBuf::from);
+        Ok(Some(config))
+    }
+}
+
+/// Pretends to send prizes. It never talks to a node and returns random block hashes
+pub(crate) struct SimulatedSender {
+    latency: Duration,
+    failure_rate: f64,
+    balance: Mutex<Amount>,
+    metrics: Arc<Metrics>,
+}
+
+impl SimulatedSender {
+    pub(crate) fn new(config: &DryRunConfig, metrics: Arc<Metrics>) -> Self {
+        metrics.set_rpc_up(true);
+        metrics.set_wallet_balance(config.balance);
+        Self {
+            latency: config.latency,
+            failure_rate: config.failure_rate,
+            balance: Mutex::new(config.balance),
+            metrics,
+        }
+    }
+
+    pub(crate) async fn send_prize(
+        &self,
+        destination: Account,
+        prize: Amount,
+    ) -> anyhow::Result<BlockHash> {
+        sleep(self.latency).await;
+        if rng().random_bool(self.failure_rate) {
+            bail!("simulated payout failure");
+        }
+
+        let mut balance = self.balance.lock().unwrap();
+        if *balance < prize {
+            bail!("simulated wallet has insufficient balance");
+        }
+        *balance -= prize;
+        self.metrics.set_wallet_balance(*balance);
+
+        let mut hash = [0u8; 32];
+        rng().fill_bytes(&mut hash);
+        info!(
+            "DRY RUN: pretended to send {} XNO to {}",
+            prize.format_balance(2),
+            destination.encode_account()
+        );
+        Ok(BlockHash::from_bytes(hash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn simulated_payouts_reduce_balance() {
+        let config = DryRunConfig {
+            latency: Duration::ZERO,
+            balance: Amount::nano(3),
+            ..Default::default()
+        };
+        let sender = SimulatedSender::new(&config, Arc::new(Metrics::default()));
+        let account = Account::from(1);
+        sender.send_prize(account, Amount::nano(2)).await.unwrap();
+        assert!(sender.send_prize(account, Amount::nano(2)).await.is_err());
+        assert_eq!(*sender.balance.lock().unwrap(), Amount::nano(1));
+    }
+
+    #[tokio::test]
+    async fn simulated_failures() {
+        let config = DryRunConfig {
+            latency: Duration::ZERO,
+            failure_rate: 1.0,
+            ..Default::default()
+        };
+        let sender = SimulatedSender::new(&config, Arc::new(Metrics::default()));
+        assert!(sender
+            .send_prize(Account::from(1), Amount::nano(1))
+            .await
+            .is_err());
+    }
+}
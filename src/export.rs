@@ -0,0 +1,310 @@
+use crate::{
+    addresses::{find_addresses, AddressCandidate},
+    chat_messages::Platform,
+    database::{Database, EntryRow, PayoutRow},
+    participants::Participant,
+};
+use anyhow::bail;
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "csv" => Some(Self::Csv),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PayoutExport {
+    payout_id: i64,
+    raffle_id: Option<u64>,
+    stream_id: Option<i64>,
+    date: String,
+    winner: String,
+    channel_id: String,
+    account: String,
+    amount_xno: String,
+    amount_raw: String,
+    status: String,
+    block_hash: String,
+    error: String,
+}
+
+impl From<PayoutRow> for PayoutExport {
+    fn from(row: PayoutRow) -> Self {
+        Self {
+            payout_id: row.payout_id,
+            raffle_id: row.raffle_id,
+            stream_id: row.stream_id,
+            date: format_date(row.created_at),
+            winner: row.winner_name.unwrap_or_default(),
+            channel_id: row.channel_id.unwrap_or_default(),
+            account: row.account.encode_account(),
+            amount_xno: row.amount.format_balance(6),
+            amount_raw: row.amount.number().to_string(),
+            status: row.status,
+            block_hash: row.block_hash.unwrap_or_default(),
+            error: row.error.unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct EntryExport {
+    raffle_id: u64,
+    stream_id: i64,
+    date: String,
+    channel_id: String,
+    name: String,
+    account: String,
+    won: bool,
+}
+
+impl From<EntryRow> for EntryExport {
+    fn from(row: EntryRow) -> Self {
+        Self {
+            raffle_id: row.raffle_id,
+            stream_id: row.stream_id,
+            date: format_date(row.drawn_at),
+            channel_id: row.channel_id,
+            name: row.name,
+            account: row.account.encode_account(),
+            won: row.won,
+        }
+    }
+}
+
+/// Has the columns of the import, so that an export can be imported again
+#[derive(Serialize)]
+struct ParticipantExport {
+    channel_id: String,
+    name: String,
+    address: String,
+    platform: &'static str,
+}
+
+impl From<&Participant> for ParticipantExport {
+    fn from(participant: &Participant) -> Self {
+        Self {
+            channel_id: participant.channel_id.clone(),
+            name: participant.name.clone(),
+            address: participant.account.encode_account(),
+            platform: participant.platform.as_str(),
+        }
+    }
+}
+
+/// Writes `payouts.<ext>`, `entries.<ext>` and `participants.<ext>` into the given directory
+pub(crate) fn export_history(
+    database: &Database,
+    participants: &[Participant],
+    dir: &Path,
+    format: ExportFormat,
+) -> anyhow::Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+
+    let payouts: Vec<PayoutExport> = database.payouts()?.into_iter().map(Into::into).collect();
+    let entries: Vec<EntryExport> = database.entries()?.into_iter().map(Into::into).collect();
+    let participants: Vec<ParticipantExport> = participants.iter().map(Into::into).collect();
+
+    let payouts_path = dir.join(format!("payouts.{}", format.extension()));
+    let entries_path = dir.join(format!("entries.{}", format.extension()));
+    let participants_path = dir.join(format!("participants.{}", format.extension()));
+    write_rows(&payouts_path, &payouts, format)?;
+    write_rows(&entries_path, &entries, format)?;
+    write_rows(&participants_path, &participants, format)?;
+    Ok(vec![payouts_path, entries_path, participants_path])
+}
+
+fn write_rows<T: Serialize>(path: &Path, rows: &[T], format: ExportFormat) -> anyhow::Result<()> {
+    match format {
+        ExportFormat::Csv => {
+            let mut writer = csv::Writer::from_path(path)?;
+            for row in rows {
+                writer.serialize(row)?;
+            }
+            writer.flush()?;
+        }
+        ExportFormat::Json => {
+            std::fs::write(path, serde_json::to_string_pretty(rows)?)?;
+        }
+    }
+    Ok(())
+}
+
+fn format_date(unix_secs: u64) -> String {
+    DateTime::from_timestamp(unix_secs as i64, 0)
+        .map(|d| d.to_rfc3339())
+        .unwrap_or_default()
+}
+
+#[derive(Deserialize)]
+struct ImportRow {
+    channel_id: String,
+    name: String,
+    address: String,
+    #[serde(default)]
+    platform: Option<String>,
+}
+
+/// Result of a participant import
+#[derive(Default, Debug)]
+pub(crate) struct ImportReport {
+    pub participants: Vec<Participant>,
+    /// Line number and reason for every rejected line
+    pub rejected: Vec<(usize, String)>,
+}
+
+/// Reads participants from a CSV file with the columns `channel_id`, `name`,
+/// `address` and an optional `platform`. The address is validated like a chat message.
+pub(crate) fn import_participants(reader: impl Read) -> anyhow::Result<ImportReport> {
+    let mut reader = csv::Reader::from_reader(reader);
+    let mut report = ImportReport::default();
+    for (i, row) in reader.deserialize::<ImportRow>().enumerate() {
+        // line 1 is the header
+        let line = i + 2;
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                report.rejected.push((line, e.to_string()));
+                continue;
+            }
+        };
+
+        match parse_row(row) {
+            Ok(participant) => report.participants.push(participant),
+            Err(e) => report.rejected.push((line, e.to_string())),
+        }
+    }
+    Ok(report)
+}
+
+fn parse_row(row: ImportRow) -> anyhow::Result<Participant> {
+    if row.channel_id.trim().is_empty() {
+        bail!("channel_id is empty");
+    }
+
+    let account = match find_addresses(&row.address).as_slice() {
+        [AddressCandidate::Account(account)] => *account,
+        [AddressCandidate::Alias(alias)] => bail!("aliases can't be imported: {}", alias),
+        [] => bail!("invalid address: {}", row.address),
+        _ => bail!("more than one address: {}", row.address),
+    };
+
+    let platform = match row.platform.as_deref() {
+        None | Some("") => Platform::Manual,
+        Some(p) => match Platform::parse(p) {
+            Some(platform) => platform,
+            None => bail!("unknown platform: {}", p),
+        },
+    };
+
+    let name = if row.name.trim().is_empty() {
+        "no name".to_owned()
+    } else {
+        row.name
+    };
+
+    Ok(Participant {
+        channel_id: row.channel_id,
+        name,
+        account,
+        platform,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rsnano_core::Account;
+    use std::fs::File;
+
+    const ADDRESS: &str = "nano_37391u1nrr1j7tdn8w9zathoio5suz9bar18jksqheeiy4obwz3pkgp9aqz6";
+
+    #[test]
+    fn import_valid_rows() {
+        let csv = format!(
+            "channel_id,name,address,platform\nabc,Alice,{},youtube\ntwitch-bob,Bob,xrb_{},\n",
+            ADDRESS,
+            &ADDRESS[5..]
+        );
+        let report = import_participants(csv.as_bytes()).unwrap();
+        assert!(report.rejected.is_empty());
+        assert_eq!(
+            report.participants,
+            vec![
+                Participant {
+                    channel_id: "abc".to_owned(),
+                    name: "Alice".to_owned(),
+                    account: Account::decode_account(ADDRESS).unwrap(),
+                    platform: Platform::YouTube,
+                },
+                Participant {
+                    channel_id: "twitch-bob".to_owned(),
+                    name: "Bob".to_owned(),
+                    account: Account::decode_account(ADDRESS).unwrap(),
+                    platform: Platform::Manual,
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn reject_invalid_rows() {
+        let csv = format!(
+            "channel_id,name,address\nabc,Alice,nano_invalid\n,Bob,{}\ncarol,Carol,@carol\n",
+            ADDRESS
+        );
+        let report = import_participants(csv.as_bytes()).unwrap();
+        assert!(report.participants.is_empty());
+        assert_eq!(
+            report
+                .rejected
+                .iter()
+                .map(|(line, _)| *line)
+                .collect::<Vec<_>>(),
+            vec![2, 3, 4]
+        );
+    }
+
+    #[test]
+    fn export_empty_history() {
+        let database = Database::open_in_memory().unwrap();
+        let dir = std::env::temp_dir().join(format!("raffle-bot-export-{}", std::process::id()));
+        let files = export_history(&database, &[], &dir, ExportFormat::Json).unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(std::fs::read_to_string(&files[0]).unwrap(), "[]");
+    }
+
+    #[test]
+    fn import_exported_participants() {
+        let database = Database::open_in_memory().unwrap();
+        let dir =
+            std::env::temp_dir().join(format!("raffle-bot-participants-{}", std::process::id()));
+        let participants = vec![Participant::new_test_instance()];
+        let files = export_history(&database, &participants, &dir, ExportFormat::Csv).unwrap();
+        let report = import_participants(File::open(&files[2]).unwrap()).unwrap();
+        assert_eq!(report.participants, participants);
+    }
+}
@@ -1,13 +1,34 @@
-use crate::{chat_messages::ChatMessage, logic::RaffleLogic};
+use crate::{
+    amounts::parse_xno,
+    chat_messages::{ChatMessage, Platform},
+    cli::DEFAULT_EXPORT_DIR,
+    database::Database,
+    draw_animation::AnimationMode,
+    export::{export_history, ExportFormat},
+    logic::RaffleLogic,
+    participants::Participant,
+    prediction::Prediction,
+    quiz::Quiz,
+    schedule::{Schedule, SCHEDULE_SETTING},
+};
 use eframe::{
     egui::{self, CentralPanel, IconData, SidePanel, TopBottomPanel, ViewportBuilder},
     NativeOptions,
 };
-use rsnano_nullable_clock::SteadyClock;
-use std::sync::{Arc, Mutex};
+use rsnano_nullable_clock::{SteadyClock, Timestamp};
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-pub(crate) fn run_gui(logic: Arc<Mutex<RaffleLogic>>, clock: Arc<SteadyClock>) -> eframe::Result {
+pub(crate) fn run_gui(
+    logic: Arc<Mutex<RaffleLogic>>,
+    clock: Arc<SteadyClock>,
+    database: Arc<Mutex<Database>>,
+) -> eframe::Result {
     let icon_data = load_icon();
+    let schedule = logic.lock().unwrap().schedule().to_string();
 
     let options = NativeOptions {
         viewport: ViewportBuilder::default()
@@ -23,15 +44,28 @@ pub(crate) fn run_gui(logic: Arc<Mutex<RaffleLogic>>, clock: Arc<SteadyClock>) -
             Ok(Box::new(AdminGui {
                 logic,
                 clock,
+                database,
                 message: String::new(),
                 user: String::new(),
+                export_status: String::new(),
+                schedule,
+                schedule_status: String::new(),
+                quiz_question: String::new(),
+                quiz_answer: String::new(),
+                quiz_seconds: "60".to_owned(),
+                quiz_status: String::new(),
+                prediction_question: String::new(),
+                prediction_options: "yes | no".to_owned(),
+                prediction_pool: String::new(),
+                prediction_status: String::new(),
             }))
         }),
     )
 }
 
 fn load_icon() -> IconData {
-    let icon_image = image::open("assets/icon-256.png").expect("Unable to open icon PNG file");
+    let icon_image = image::load_from_memory(include_bytes!("../assets/icon-256.png"))
+        .expect("Unable to load icon PNG file");
 
     let width = icon_image.width();
     let height = icon_image.height();
@@ -44,12 +78,102 @@ fn load_icon() -> IconData {
     }
 }
 
-#[derive(Default)]
 struct AdminGui {
     clock: Arc<SteadyClock>,
     logic: Arc<Mutex<RaffleLogic>>,
+    database: Arc<Mutex<Database>>,
     message: String,
     user: String,
+    export_status: String,
+    schedule: String,
+    schedule_status: String,
+    quiz_question: String,
+    /// Accepted answers separated by `|`
+    quiz_answer: String,
+    quiz_seconds: String,
+    quiz_status: String,
+    prediction_question: String,
+    /// Options separated by `|`
+    prediction_options: String,
+    /// Prize pool in XNO
+    prediction_pool: String,
+    prediction_status: String,
+}
+
+/// Persists and applies the schedule and returns a status message for the user
+fn apply_schedule(logic: &mut RaffleLogic, database: &Mutex<Database>, text: &str) -> String {
+    let schedule = match Schedule::parse(text) {
+        Ok(schedule) => schedule,
+        Err(e) => return e.to_string(),
+    };
+    let saved = database
+        .lock()
+        .unwrap()
+        .save_setting(SCHEDULE_SETTING, &schedule.to_string());
+    logic.set_schedule(schedule);
+    match saved {
+        Ok(()) => "schedule applied".to_owned(),
+        Err(e) => format!("could not save schedule: {}", e),
+    }
+}
+
+/// Starts a quiz for the current prize and returns a status message for the user
+fn start_quiz(
+    logic: &mut RaffleLogic,
+    question: &str,
+    answer: &str,
+    seconds: &str,
+    now: Timestamp,
+) -> String {
+    let Ok(seconds) = seconds.trim().parse() else {
+        return format!("invalid time limit: {}", seconds);
+    };
+    let quiz = Quiz::new(
+        question,
+        answer,
+        logic.prize(),
+        Duration::from_secs(seconds),
+        now,
+    );
+    match quiz {
+        Ok(quiz) => {
+            logic.start_quiz(quiz, now);
+            "quiz started".to_owned()
+        }
+        Err(e) => e.to_string(),
+    }
+}
+
+/// Opens a prediction and returns a status message for the user
+fn open_prediction(logic: &mut RaffleLogic, question: &str, options: &str, pool: &str) -> String {
+    let Ok(pool) = parse_xno(pool.trim()) else {
+        return format!("invalid prize pool: {}", pool);
+    };
+    match Prediction::new(question, options, pool) {
+        Ok(prediction) => {
+            logic.open_prediction(prediction);
+            "prediction opened".to_owned()
+        }
+        Err(e) => e.to_string(),
+    }
+}
+
+/// Exports the raffle history and returns a status message for the user
+fn export(
+    database: &Mutex<Database>,
+    participants: &[Participant],
+    format: ExportFormat,
+) -> String {
+    let database = database.lock().unwrap();
+    match export_history(
+        &database,
+        participants,
+        Path::new(DEFAULT_EXPORT_DIR),
+        format,
+    ) {
+        Ok(_) => format!("exported to {}/", DEFAULT_EXPORT_DIR),
+        Err(e) => format!("export failed: {}", e),
+    }
 }
 
 impl eframe::App for AdminGui {
@@ -66,10 +190,86 @@ impl eframe::App for AdminGui {
                     if ui.button("stop").clicked() {
                         logic.stop();
                     }
+                    if ui.button("end stream").clicked() {
+                        logic.end_stream(now);
+                    }
                 } else if ui.button("start").clicked() {
                     logic.start();
                 }
 
+                ui.label("Schedule:");
+                ui.text_edit_singleline(&mut self.schedule);
+                if ui.button("apply schedule").clicked() {
+                    self.schedule_status =
+                        apply_schedule(&mut logic, &self.database, &self.schedule);
+                }
+                ui.label(self.schedule_status.as_str());
+
+                ui.label("Quiz question:");
+                ui.text_edit_singleline(&mut self.quiz_question);
+                ui.label("Answer:");
+                ui.text_edit_singleline(&mut self.quiz_answer);
+                ui.label("Time limit (s):");
+                ui.text_edit_singleline(&mut self.quiz_seconds);
+                if let Some(quiz) = logic.quiz() {
+                    ui.label(format!(
+                        "quiz running, {}s left",
+                        quiz.time_left(now).as_secs()
+                    ));
+                    if ui.button("cancel quiz").clicked() {
+                        logic.cancel_quiz();
+                    }
+                } else if ui.button("start quiz").clicked() {
+                    self.quiz_status = start_quiz(
+                        &mut logic,
+                        &self.quiz_question,
+                        &self.quiz_answer,
+                        &self.quiz_seconds,
+                        now,
+                    );
+                }
+                ui.label(self.quiz_status.as_str());
+
+                if let Some(prediction) = logic.prediction().cloned() {
+                    ui.label(format!("Prediction: {}", prediction.question));
+                    let mut resolved = None;
+                    for (i, option) in prediction.options.iter().enumerate() {
+                        let votes = prediction.vote_count(i);
+                        if ui
+                            .button(format!("{} wins ({} votes)", option, votes))
+                            .clicked()
+                        {
+                            resolved = Some(i);
+                        }
+                    }
+                    if prediction.is_open() && ui.button("close voting").clicked() {
+                        logic.close_prediction();
+                    }
+                    if ui.button("cancel prediction").clicked() {
+                        logic.cancel_prediction();
+                    }
+                    if let Some(option) = resolved {
+                        logic.resolve_prediction(option);
+                        self.prediction_status = "prediction resolved".to_owned();
+                    }
+                } else {
+                    ui.label("Prediction:");
+                    ui.text_edit_singleline(&mut self.prediction_question);
+                    ui.label("Options:");
+                    ui.text_edit_singleline(&mut self.prediction_options);
+                    ui.label("Prize pool:");
+                    ui.text_edit_singleline(&mut self.prediction_pool);
+                    if ui.button("open prediction").clicked() {
+                        self.prediction_status = open_prediction(
+                            &mut logic,
+                            &self.prediction_question,
+                            &self.prediction_options,
+                            &self.prediction_pool,
+                        );
+                    }
+                }
+                ui.label(self.prediction_status.as_str());
+
                 ui.label("User:");
                 ui.text_edit_singleline(&mut self.user);
                 ui.label("Message:");
@@ -79,6 +279,7 @@ impl eframe::App for AdminGui {
                         message: self.message.clone(),
                         author_name: Some(self.user.clone()),
                         author_channel_id: self.user.clone(),
+                        platform: Platform::Manual,
                     });
                     self.message = String::new();
                 }
@@ -93,6 +294,7 @@ impl eframe::App for AdminGui {
                             message:
                                 "nano_1iawmcfwmmdyr7xmnordt71gpnhnao8rsk4nywq5khtmedocaj6bafk4fb8h"
                                     .to_owned(),
+                            platform: Platform::Manual,
                         });
                         }
                     }
@@ -100,6 +302,18 @@ impl eframe::App for AdminGui {
                 if ui.button("run raffle now").clicked() {
                     logic.run_raffle_now(now);
                 }
+                let mut animation = logic.animation();
+                egui::ComboBox::from_label("animation")
+                    .selected_text(animation.map_or("auto", |a| a.as_str()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut animation, None, "auto");
+                        for mode in AnimationMode::ALL {
+                            ui.selectable_value(&mut animation, Some(mode), mode.as_str());
+                        }
+                    });
+                if animation != logic.animation() {
+                    logic.set_animation(animation);
+                }
                 let connected = if logic.spinner_connected(now) {
                     "ONLINE"
                 } else {
@@ -107,8 +321,62 @@ impl eframe::App for AdminGui {
                 };
                 ui.label(format!("Spinner {}", connected));
                 if let Some(win) = logic.current_win() {
-                    ui.label(format!("CURRENT WINNER: {}", win.winner));
+                    ui.label(format!("CURRENT WINNER: {}", win.winner.name));
+                    ui.label(format!("raffle {} #{}", win.raffle, win.id));
+                    if let Some(spinner) = logic.claimed_by() {
+                        ui.label(format!("claimed by spinner {}", spinner));
+                    }
+                }
+                if let Some(pending_for) = logic.spin_pending_for(now) {
+                    ui.label(format!(
+                        "waiting for confirmation since {}s",
+                        pending_for.as_secs()
+                    ));
+                    if ui.button("confirm winner").clicked() {
+                        logic.confirm_manually();
+                    }
+                }
+
+                let mut decisions = Vec::new();
+                for held in logic.held_payouts() {
+                    ui.separator();
+                    ui.label(format!(
+                        "HELD: {} Ӿ {} ({})",
+                        held.winner.name,
+                        held.winner.prize.format_balance(2),
+                        held.reason
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button("approve").clicked() {
+                            decisions.push((held.winner.raffle_id, true));
+                        }
+                        if ui.button("deny").clicked() {
+                            decisions.push((held.winner.raffle_id, false));
+                        }
+                    });
+                }
+                for (raffle_id, approve) in decisions {
+                    if approve {
+                        logic.approve_payout(raffle_id);
+                    } else {
+                        logic.deny_payout(raffle_id);
+                    }
                 }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("export CSV").clicked() {
+                        let participants = logic.participants();
+                        self.export_status =
+                            export(&self.database, &participants, ExportFormat::Csv);
+                    }
+                    if ui.button("export JSON").clicked() {
+                        let participants = logic.participants();
+                        self.export_status =
+                            export(&self.database, &participants, ExportFormat::Json);
+                    }
+                });
+                ui.label(self.export_status.as_str());
             });
 
         SidePanel::right("winners-panel")
@@ -116,9 +384,29 @@ impl eframe::App for AdminGui {
             .resizable(false)
             .show(ctx, |ui| {
                 let winners = logic.winners();
-                ui.heading(format!("Winners ({})", winners.len()));
-                for winner in winners {
-                    ui.label(winner);
+                let stats = winners.stream_stats();
+                ui.heading(format!("Winners ({})", stats.draws));
+                ui.label(format!("paid: Ӿ {}", stats.total_paid.format_balance(2)));
+                ui.label(format!("unique winners: {}", stats.unique_winners));
+                ui.label(format!(
+                    "avg participants: {:.1}",
+                    stats.average_participants
+                ));
+                let all_time = winners.all_time_stats();
+                ui.label(format!(
+                    "all time: {} draws, Ӿ {}",
+                    all_time.draws,
+                    all_time.total_paid.format_balance(2)
+                ));
+                ui.separator();
+                for winner in winners.in_current_stream() {
+                    let paid = if winner.paid() { "" } else { " (unpaid)" };
+                    ui.label(format!(
+                        "{} Ӿ {}{}",
+                        winner.name,
+                        winner.prize.format_balance(2),
+                        paid
+                    ));
                 }
             });
 
@@ -135,6 +423,37 @@ impl eframe::App for AdminGui {
 
         TopBottomPanel::top("timer-panel").show(ctx, |ui| {
             ui.heading(format!("{}s until raffle", logic.countdown(now).as_secs()));
+            if let Some(pot) = logic.jackpot() {
+                ui.label(format!("jackpot: Ӿ {}", pot.format_balance(2)));
+            }
+            let missing = logic.missing_participants();
+            if missing > 0 {
+                ui.label(format!("waiting for {} more participants", missing));
+            }
+            if !logic.schedule().is_empty() {
+                let (at, prize) = logic.next_draw(now);
+                let at = at
+                    .map(|at| at.format("%H:%M").to_string())
+                    .unwrap_or_default();
+                ui.label(format!(
+                    "next draw {} for Ӿ {}",
+                    at,
+                    prize.format_balance(2)
+                ));
+            }
+            for raffle in logic.raffles(now).into_iter().skip(1) {
+                let countdown = raffle
+                    .countdown_secs
+                    .map(|secs| format!(", {}s until draw", secs))
+                    .unwrap_or_default();
+                ui.label(format!(
+                    "{}: {} participants, Ӿ {}{}",
+                    raffle.name,
+                    raffle.participants,
+                    raffle.next_prize.format_balance(2),
+                    countdown
+                ));
+            }
         });
 
         CentralPanel::default().show(ctx, |ui| {
@@ -1,68 +1,390 @@
 use axum::{
-    extract::State,
-    http::{header, HeaderMap},
-    response::{Html, IntoResponse},
+    extract::{Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
     routing::{get, post},
     Json, Router,
 };
+use log::{info, warn};
+use rand::{rng, RngCore};
 use rsnano_nullable_clock::SteadyClock;
-use serde::Serialize;
-use std::sync::{Arc, Mutex};
-use tokio::net::TcpListener;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+use tokio::{net::TcpListener, sync::broadcast};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt};
+
+use crate::{
+    admin_api::{admin_routes, tokens_match},
+    assets::{get_asset, get_overlay_page, serve_template, Assets},
+    database::Database,
+    logic::{RaffleLogic, SpinError},
+    metrics::{metrics_routes, Metrics},
+    overlay_events::OverlayEvent,
+    public_api::{cors_layer, public_routes},
+    theme::{render_html, render_svg, Theme, ThemeSource},
+    winners::WinnerStats,
+};
+
+#[derive(Clone)]
+pub(crate) struct AppState {
+    pub logic: Arc<Mutex<RaffleLogic>>,
+    pub clock: Arc<SteadyClock>,
+    pub events: broadcast::Sender<OverlayEvent>,
+    pub database: Arc<Mutex<Database>>,
+    pub metrics: Arc<Metrics>,
+    /// Bearer token for the admin API. The admin API is disabled if it is None
+    pub admin_token: Option<String>,
+    /// Whether the public API includes the names of viewers
+    pub public_names: bool,
+    /// Only the spinner page opened with this token may claim and confirm spins
+    pub spinner_token: String,
+    pub assets: Arc<Assets>,
+    pub theme: Arc<ThemeSource>,
+}
 
-use crate::logic::RaffleLogic;
+#[cfg(test)]
+impl AppState {
+    pub(crate) fn new_test_instance() -> Self {
+        Self {
+            logic: Arc::new(Mutex::new(RaffleLogic::default())),
+            clock: Arc::new(SteadyClock::default()),
+            events: broadcast::channel(16).0,
+            database: Arc::new(Mutex::new(Database::open_in_memory().unwrap())),
+            metrics: Arc::new(Metrics::default()),
+            admin_token: None,
+            public_names: false,
+            spinner_token: "spinner-secret".to_owned(),
+            assets: Arc::new(Assets::new(None)),
+            theme: Arc::new(ThemeSource::default()),
+        }
+    }
+}
 
-pub(crate) async fn run_http_server(logic: Arc<Mutex<RaffleLogic>>, clock: Arc<SteadyClock>) {
-    let app = Router::new()
+pub(crate) struct HttpConfig {
+    pub bind: String,
+    pub admin_token: Option<String>,
+    /// Names are only published with `RAFFLE_PUBLIC_NAMES=on`
+    pub public_names: bool,
+    /// A random token is generated and stored in the database if it isn't set
+    pub spinner_token: Option<String>,
+    /// Origins that may call the public API from a browser
+    pub cors_origins: Option<String>,
+    /// Files in this directory replace the embedded overlay assets
+    pub assets_dir: Option<PathBuf>,
+}
+
+impl HttpConfig {
+    /// Reads the `RAFFLE_HTTP_BIND`, `RAFFLE_ADMIN_TOKEN`, `RAFFLE_PUBLIC_NAMES`,
+    /// `RAFFLE_SPINNER_TOKEN`, `RAFFLE_CORS_ORIGINS` and `RAFFLE_ASSETS_DIR` env vars
+    pub(crate) fn from_env() -> Self {
+        Self {
+            bind: std::env::var("RAFFLE_HTTP_BIND").unwrap_or_else(|_| DEFAULT_BIND.to_owned()),
+            admin_token: std::env::var("RAFFLE_ADMIN_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty()),
+            public_names: std::env::var("RAFFLE_PUBLIC_NAMES").as_deref() == Ok("on"),
+            spinner_token: std::env::var("RAFFLE_SPINNER_TOKEN")
+                .ok()
+                .filter(|t| !t.is_empty()),
+            cors_origins: std::env::var("RAFFLE_CORS_ORIGINS").ok(),
+            assets_dir: std::env::var_os("RAFFLE_ASSETS_DIR").map(PathBuf::from),
+        }
+    }
+}
+
+const DEFAULT_BIND: &str = "127.0.0.1:8080";
+
+/// Key of the generated spinner token in the settings table
+pub(crate) const SPINNER_TOKEN_SETTING: &str = "spinner_token";
+
+pub(crate) async fn run_http_server(
+    logic: Arc<Mutex<RaffleLogic>>,
+    clock: Arc<SteadyClock>,
+    events: broadcast::Sender<OverlayEvent>,
+    database: Arc<Mutex<Database>>,
+    metrics: Arc<Metrics>,
+    config: HttpConfig,
+) {
+    let spinner_token = match config.spinner_token {
+        Some(token) => token,
+        None => stored_spinner_token(&database),
+    };
+    info!(
+        "spinner page: http://{}/ (run `raffle-bot spinner-url` for the link with the token)",
+        config.bind
+    );
+    let state = AppState {
+        logic,
+        clock,
+        events,
+        database,
+        metrics,
+        admin_token: config.admin_token,
+        public_names: config.public_names,
+        spinner_token,
+        assets: Arc::new(Assets::new(config.assets_dir)),
+        theme: Arc::new(ThemeSource::from_env()),
+    };
+    let mut app = Router::new()
         .route("/", get(get_html))
-        .route("/raffle", get(get_raffle))
+        .route("/events", get(get_events))
+        .route("/claim", post(post_claim))
         .route("/confirm", post(post_confirm))
         .route("/overlay.svg", get(get_overlay))
-        .with_state((logic, clock));
-    let listener = TcpListener::bind("0.0.0.0:8080").await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+        .route("/assets/{*path}", get(get_asset))
+        .route("/overlays/{name}", get(get_overlay_page))
+        .route("/theme", get(get_theme_preview))
+        .route("/theme.json", get(get_theme))
+        .route("/stats", get(get_stats))
+        .merge(metrics_routes())
+        .merge(public_routes(
+            config.cors_origins.as_deref().map(cors_layer),
+        ));
+    if state.admin_token.is_some() {
+        app = app.nest("/api/admin", admin_routes(state.clone()));
+    } else {
+        info!("RAFFLE_ADMIN_TOKEN not set, admin API is disabled");
+    }
+
+    info!("HTTP server listening on {}", config.bind);
+    let listener = TcpListener::bind(&config.bind).await.unwrap();
+    axum::serve(listener, app.with_state(state)).await.unwrap();
 }
 
-async fn get_html() -> Html<&'static str> {
-    Html(include_str!("../assets/index.html"))
+/// The link to the spinner page. It contains the token, so it isn't logged
+pub(crate) fn spinner_url(bind: &str, token: &str) -> String {
+    format!("http://{}/?token={}", bind, token)
 }
 
-async fn get_overlay() -> impl IntoResponse {
-    let mut headers = HeaderMap::new();
-    headers.insert(header::CONTENT_TYPE, "image/svg+xml".parse().unwrap());
-    (headers, include_str!("../assets/overlay.svg"))
+/// Loads the generated spinner token, so that the URL of the spinner page
+/// stays the same across restarts
+fn stored_spinner_token(database: &Mutex<Database>) -> String {
+    let database = database.lock().unwrap();
+    match database.setting(SPINNER_TOKEN_SETTING) {
+        Ok(Some(token)) => return token,
+        Ok(None) => {}
+        Err(e) => warn!("Could not load spinner token: {:?}", e),
+    }
+    let mut bytes = [0u8; 16];
+    rng().fill_bytes(&mut bytes);
+    let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    if let Err(e) = database.save_setting(SPINNER_TOKEN_SETTING, &token) {
+        warn!("Could not save spinner token: {:?}", e);
+    }
+    token
 }
 
-#[derive(Serialize)]
-struct SpinInstruction {
-    spin: bool,
-    participants: Vec<String>,
-    winner: usize,
-}
-
-async fn get_raffle(
-    State((logic, clock)): State<(Arc<Mutex<RaffleLogic>>, Arc<SteadyClock>)>,
-) -> Json<SpinInstruction> {
-    let mut guard = logic.lock().unwrap();
-    guard.ping(clock.now());
-    if let Some(win) = guard.current_win() {
-        let participants = win.participants.clone();
-        let winner = participants.iter().position(|i| i == &win.winner).unwrap();
-        Json(SpinInstruction {
-            spin: true,
-            participants,
-            winner,
-        })
-    } else {
-        Json(SpinInstruction {
-            spin: false,
-            participants: Vec::new(),
-            winner: 0,
+async fn get_html(State(state): State<AppState>) -> Response {
+    let theme = state.theme.load();
+    serve_template(&state.assets, "index.html", |t| {
+        render_html(t, &theme, false)
+    })
+    .await
+}
+
+/// The wheel with sample participants, for trying out a theme
+async fn get_theme_preview(State(state): State<AppState>) -> Response {
+    let theme = state.theme.load();
+    serve_template(&state.assets, "index.html", |t| {
+        render_html(t, &theme, true)
+    })
+    .await
+}
+
+async fn get_theme(State(state): State<AppState>) -> Json<Theme> {
+    Json(state.theme.load())
+}
+
+async fn get_overlay(State(state): State<AppState>) -> Response {
+    let theme = state.theme.load();
+    serve_template(&state.assets, "overlay.svg", |t| render_svg(t, &theme)).await
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    /// `spinner` if the client is the wheel which confirms spins
+    role: Option<String>,
+    /// Identifies the spinner, so that its claim stays valid while it is connected
+    spinner_id: Option<String>,
+    /// The spinner token, because an event source can't send headers.
+    /// Overlays that show names and chat need it too, unless names are public
+    token: Option<String>,
+}
+
+/// Pushes overlay events to the browser via Server-Sent Events
+async fn get_events(
+    State(state): State<AppState>,
+    Query(query): Query<EventsQuery>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let has_token = query
+        .token
+        .is_some_and(|t| tokens_match(&t, &state.spinner_token));
+    let is_spinner = has_token && query.role.as_deref() == Some("spinner");
+    let with_names = has_token || state.public_names;
+    let spinner_id = query.spinner_id.unwrap_or_default();
+    let updates = BroadcastStream::new(state.events.subscribe()).filter_map(|e| e.ok());
+    let snapshot = state
+        .logic
+        .lock()
+        .unwrap()
+        .overlay_snapshot(state.clock.now());
+
+    let logic = state.logic.clone();
+    let clock = state.clock.clone();
+    let stream = tokio_stream::iter(snapshot)
+        .chain(updates)
+        .filter_map(move |event| {
+            // The spinner is connected as long as it receives our heartbeats
+            if is_spinner && event == OverlayEvent::Heartbeat {
+                logic.lock().unwrap().ping(clock.now(), &spinner_id);
+            }
+            if with_names {
+                Some(event)
+            } else {
+                event.without_names()
+            }
         })
+        .map(|event| Event::default().json_data(&event));
+
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+/// Identifies the spin and the browser that animates it
+#[derive(Deserialize)]
+struct SpinRequest {
+    raffle_id: u64,
+    spinner_id: String,
+}
+
+async fn post_claim(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<SpinRequest>,
+) -> Result<(), (StatusCode, String)> {
+    check_spinner_token(&state, &headers)?;
+    let mut guard = state.logic.lock().unwrap();
+    guard
+        .claim_spin(request.raffle_id, &request.spinner_id, state.clock.now())
+        .map_err(conflict)
+}
+
+async fn post_confirm(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<SpinRequest>,
+) -> Result<(), (StatusCode, String)> {
+    check_spinner_token(&state, &headers)?;
+    let mut guard = state.logic.lock().unwrap();
+    guard
+        .confirm_spin(request.raffle_id, &request.spinner_id, state.clock.now())
+        .map_err(conflict)
+}
+
+/// Spins may only be claimed and confirmed with `Authorization: Bearer <spinner token>`
+fn check_spinner_token(state: &AppState, headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "));
+    match token {
+        Some(token) if tokens_match(token, &state.spinner_token) => Ok(()),
+        _ => Err((StatusCode::UNAUTHORIZED, "invalid spinner token".to_owned())),
     }
 }
 
-async fn post_confirm(State((logic, _)): State<(Arc<Mutex<RaffleLogic>>, Arc<SteadyClock>)>) {
-    let mut guard = logic.lock().unwrap();
-    guard.spin_finished();
+fn conflict(error: SpinError) -> (StatusCode, String) {
+    (StatusCode::CONFLICT, error.to_string())
+}
+
+#[derive(Serialize)]
+struct Stats {
+    stream: WinnerStats,
+    all_time: WinnerStats,
+}
+
+async fn get_stats(State(state): State<AppState>) -> Json<Stats> {
+    let guard = state.logic.lock().unwrap();
+    let winners = guard.winners();
+    Json(Stats {
+        stream: winners.stream_stats(),
+        all_time: winners.all_time_stats(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat_messages::Platform;
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn events_without_names() {
+        let events = received_events(AppState::new_test_instance(), "/events").await;
+        assert!(!events.contains("alice"), "{}", events);
+        assert!(!events.contains("hello"), "{}", events);
+        assert!(events.contains(r#""type":"registration","name":"***""#));
+    }
+
+    #[tokio::test]
+    async fn events_with_public_names() {
+        let mut state = AppState::new_test_instance();
+        state.public_names = true;
+        let events = received_events(state, "/events").await;
+        assert!(events.contains(r#""type":"registration","name":"alice""#));
+        assert!(events.contains("hello"));
+    }
+
+    #[tokio::test]
+    async fn events_with_spinner_token() {
+        let state = AppState::new_test_instance();
+        let uri = format!("/events?token={}", state.spinner_token);
+        let events = received_events(state, &uri).await;
+        assert!(events.contains(r#""type":"registration","name":"alice""#));
+        assert!(events.contains("hello"));
+
+        let events = received_events(AppState::new_test_instance(), "/events?token=wrong").await;
+        assert!(!events.contains("alice"));
+    }
+
+    /// Sends a chat message, a registration and a winner and returns
+    /// everything the client received up to the winner
+    async fn received_events(state: AppState, uri: &str) -> String {
+        let app = Router::new()
+            .route("/events", get(get_events))
+            .with_state(state.clone());
+        let request = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+
+        for event in [
+            OverlayEvent::Chat {
+                name: "alice".to_owned(),
+                message: "hello".to_owned(),
+                platform: Platform::Twitch,
+            },
+            OverlayEvent::Registration {
+                name: "alice".to_owned(),
+            },
+            OverlayEvent::Winner {
+                name: "alice".to_owned(),
+                prize: "1".to_owned(),
+            },
+        ] {
+            state.events.send(event).unwrap();
+        }
+
+        let mut body = response.into_body().into_data_stream();
+        let mut received = String::new();
+        while !received.contains(r#""type":"winner""#) {
+            let chunk = body.next().await.unwrap().unwrap();
+            received.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+        received
+    }
 }
@@ -0,0 +1,252 @@
+use crate::amounts::parse_xno;
+use anyhow::anyhow;
+use rsnano_core::{Account, Amount};
+use rsnano_nullable_clock::Timestamp;
+use std::time::Duration;
+
+/// Key of the unpaid pot in the settings table
+pub(crate) const JACKPOT_SETTING: &str = "jackpot_pot";
+
+/// How the jackpot grows and when it is paid out early
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct JackpotConfig {
+    pub per_minute: Amount,
+    /// Added for every viewer who enters for the first time
+    pub per_participant: Amount,
+    /// Add the donations to the bot's wallet to the pot
+    pub donations: bool,
+    /// The operator tops up the wallet from these accounts. They are no donations
+    pub funding_accounts: Vec<Account>,
+    /// Draw as soon as the prize reaches this value, even before the next scheduled draw
+    pub threshold: Option<Amount>,
+}
+
+impl JackpotConfig {
+    /// Returns the config if `RAFFLE_JACKPOT` is enabled. The pot grows by
+    /// `RAFFLE_JACKPOT_PER_MINUTE` and `RAFFLE_JACKPOT_PER_PARTICIPANT` (XNO) and
+    /// by donations if `RAFFLE_JACKPOT_DONATIONS` is `on`. Sends from the
+    /// comma separated `RAFFLE_FUNDING_ACCOUNTS` never count as donations.
+    /// `RAFFLE_JACKPOT_THRESHOLD` draws early once the prize reaches it
+    pub(crate) fn from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("RAFFLE_JACKPOT").as_deref() {
+            Err(_) | Ok("" | "0" | "off" | "false") => return Ok(None),
+            Ok(_) => {}
+        }
+        Ok(Some(Self {
+            per_minute: amount_from_env("RAFFLE_JACKPOT_PER_MINUTE")?.unwrap_or(Amount::raw(0)),
+            per_participant: amount_from_env("RAFFLE_JACKPOT_PER_PARTICIPANT")?
+                .unwrap_or(Amount::raw(0)),
+            donations: matches!(
+                std::env::var("RAFFLE_JACKPOT_DONATIONS").as_deref(),
+                Ok("1" | "on" | "true")
+            ),
+            funding_accounts: funding_accounts_from_env()?,
+            threshold: amount_from_env("RAFFLE_JACKPOT_THRESHOLD")?,
+        }))
+    }
+}
+
+fn funding_accounts_from_env() -> anyhow::Result<Vec<Account>> {
+    let Ok(value) = std::env::var("RAFFLE_FUNDING_ACCOUNTS") else {
+        return Ok(Vec::new());
+    };
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(|a| Account::decode_account(a).map_err(|_| anyhow!("invalid funding account: {}", a)))
+        .collect()
+}
+
+fn amount_from_env(var: &str) -> anyhow::Result<Option<Amount>> {
+    match std::env::var(var) {
+        Ok(value) => parse_xno(&value)
+            .map(Some)
+            .map_err(|_| anyhow!("invalid {}: {}", var, value)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// A pot that is added to the prize and grows until it is won
+pub(crate) struct Jackpot {
+    config: JackpotConfig,
+    pot: Amount,
+    last_growth: Option<Timestamp>,
+    /// The pot that was persisted last and when
+    saved: Option<(Timestamp, Amount)>,
+    /// Persist with the next tick, because the pot changed by more than the growth over time
+    save_now: bool,
+}
+
+impl Jackpot {
+    pub(crate) fn new(config: JackpotConfig) -> Self {
+        Self {
+            config,
+            pot: Amount::raw(0),
+            last_growth: None,
+            saved: None,
+            save_now: false,
+        }
+    }
+
+    pub(crate) fn pot(&self) -> Amount {
+        self.pot
+    }
+
+    /// Restores the pot that wasn't paid out before a restart
+    pub(crate) fn restore(&mut self, pot: Amount) {
+        self.pot = pot;
+    }
+
+    /// Grows the pot by the time since the last call
+    pub(crate) fn grow(&mut self, now: Timestamp) {
+        if let Some(last) = self.last_growth {
+            if now > last {
+                let millis = (now - last).as_millis();
+                let growth = self.config.per_minute.number().saturating_mul(millis) / 60_000;
+                self.pot += Amount::raw(growth);
+            }
+        }
+        self.last_growth = Some(now);
+    }
+
+    /// The pot doesn't grow over time while the raffle is stopped
+    pub(crate) fn pause(&mut self) {
+        self.last_growth = None;
+    }
+
+    pub(crate) fn participant_joined(&mut self) {
+        self.add(self.config.per_participant);
+    }
+
+    pub(crate) fn donation_received(&mut self, source: Account, amount: Amount) {
+        if self.config.donations && !self.config.funding_accounts.contains(&source) {
+            self.add(amount);
+        }
+    }
+
+    fn add(&mut self, amount: Amount) {
+        if amount.number() > 0 {
+            self.pot += amount;
+            self.save_now = true;
+        }
+    }
+
+    /// Donations are only receivable, so they can't always be paid out
+    pub(crate) fn cap(&mut self, max: Amount) {
+        if self.pot > max {
+            self.pot = max;
+            self.save_now = true;
+        }
+    }
+
+    /// The pot was won
+    pub(crate) fn reset(&mut self) {
+        self.pot = Amount::raw(0);
+        self.save_now = true;
+    }
+
+    pub(crate) fn threshold_reached(&self, prize: Amount) -> bool {
+        self.config.threshold.is_some_and(|t| prize >= t)
+    }
+
+    /// Returns the pot if it has to be persisted. The growth over time is
+    /// only persisted every few seconds
+    pub(crate) fn take_unsaved(&mut self, now: Timestamp) -> Option<Amount> {
+        let due = match self.saved {
+            None => true,
+            Some((saved_at, saved)) => {
+                saved != self.pot
+                    && (self.save_now || now < saved_at || now - saved_at >= SAVE_INTERVAL)
+            }
+        };
+        if !due {
+            return None;
+        }
+        self.saved = Some((now, self.pot));
+        self.save_now = false;
+        Some(self.pot)
+    }
+}
+
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> JackpotConfig {
+        JackpotConfig {
+            per_minute: Amount::nano(1),
+            per_participant: Amount::nano(2),
+            donations: true,
+            funding_accounts: vec![Account::from(1)],
+            threshold: Some(Amount::nano(10)),
+        }
+    }
+
+    #[test]
+    fn grow_over_time() {
+        let mut jackpot = Jackpot::new(config());
+        let start = Timestamp::new_test_instance();
+        jackpot.grow(start);
+        jackpot.grow(start + Duration::from_secs(90));
+        assert_eq!(jackpot.pot(), Amount::raw(Amount::nano(1).number() * 3 / 2));
+
+        jackpot.pause();
+        jackpot.grow(start + Duration::from_secs(600));
+        assert_eq!(jackpot.pot(), Amount::raw(Amount::nano(1).number() * 3 / 2));
+    }
+
+    #[test]
+    fn grow_by_participants_and_donations() {
+        let mut jackpot = Jackpot::new(JackpotConfig {
+            donations: false,
+            ..config()
+        });
+        jackpot.participant_joined();
+        jackpot.donation_received(Account::from(2), Amount::nano(5));
+        assert_eq!(jackpot.pot(), Amount::nano(2));
+        assert!(!jackpot.threshold_reached(Amount::nano(9)));
+        assert!(jackpot.threshold_reached(Amount::nano(10)));
+    }
+
+    #[test]
+    fn ignore_funding_accounts() {
+        let mut jackpot = Jackpot::new(config());
+        jackpot.donation_received(Account::from(1), Amount::nano(100));
+        jackpot.donation_received(Account::from(2), Amount::nano(3));
+        assert_eq!(jackpot.pot(), Amount::nano(3));
+    }
+
+    #[test]
+    fn cap_pot() {
+        let mut jackpot = Jackpot::new(config());
+        jackpot.donation_received(Account::from(2), Amount::nano(5));
+        jackpot.cap(Amount::nano(10));
+        assert_eq!(jackpot.pot(), Amount::nano(5));
+        jackpot.cap(Amount::nano(3));
+        assert_eq!(jackpot.pot(), Amount::nano(3));
+    }
+
+    #[test]
+    fn save_changes() {
+        let mut jackpot = Jackpot::new(config());
+        let now = Timestamp::new_test_instance();
+        assert_eq!(jackpot.take_unsaved(now), Some(Amount::raw(0)));
+        assert_eq!(jackpot.take_unsaved(now), None);
+
+        jackpot.grow(now);
+        jackpot.grow(now + Duration::from_secs(5));
+        assert_eq!(jackpot.take_unsaved(now + Duration::from_secs(5)), None);
+        assert!(jackpot
+            .take_unsaved(now + Duration::from_secs(10))
+            .is_some());
+
+        jackpot.reset();
+        assert_eq!(
+            jackpot.take_unsaved(now + Duration::from_secs(11)),
+            Some(Amount::raw(0))
+        );
+    }
+}
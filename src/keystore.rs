@@ -0,0 +1,335 @@
+use anyhow::{anyhow, bail, Context};
+use argon2::{Algorithm, Argon2, Params, Version};
+use blake2::{
+    digest::{Update, VariableOutput},
+    Blake2bVar,
+};
+use chacha20poly1305::{
+    aead::{Aead, Payload},
+    Key, KeyInit, XChaCha20Poly1305, XNonce,
+};
+use log::{info, warn};
+use rand::{rng, RngCore};
+use rsnano_core::{Account, Amount, Block, BlockHash, PrivateKey, PublicKey, StateBlockArgs};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Write, path::Path};
+use zeroize::Zeroizing;
+
+/// Signs the payout blocks. The private key never leaves this struct, so the
+/// rest of the bot can't leak it
+pub(crate) struct Signer {
+    key: PrivateKey,
+}
+
+impl Signer {
+    fn from_secret(secret: &[u8; 32]) -> anyhow::Result<Self> {
+        let hex = Zeroizing::new(to_hex(secret));
+        let key = PrivateKey::from_hex_str(hex.as_str()).map_err(|_| anyhow!("invalid key"))?;
+        Ok(Self { key })
+    }
+
+    pub fn account(&self) -> Account {
+        self.key.account()
+    }
+
+    pub fn send_block(
+        &self,
+        previous: BlockHash,
+        representative: PublicKey,
+        balance: Amount,
+        destination: Account,
+        work: u64,
+    ) -> Block {
+        StateBlockArgs {
+            key: &self.key,
+            previous,
+            representative,
+            balance,
+            link: destination.into(),
+            work,
+        }
+        .into()
+    }
+}
+
+/// Loads the key from the keystore in `NANO_KEYSTORE`. The password is read
+/// from the file in `NANO_KEYSTORE_PASSWORD_FILE` or asked for on the terminal.
+/// The raw hex key in `NANO_PRV_KEY` is still supported, but discouraged
+pub(crate) fn signer_from_env() -> anyhow::Result<Signer> {
+    if let Some(path) = std::env::var_os("NANO_KEYSTORE") {
+        let keystore = Keystore::load(Path::new(&path))?;
+        let password = match std::env::var_os("NANO_KEYSTORE_PASSWORD_FILE") {
+            Some(file) => read_password_file(Path::new(&file))?,
+            None => Zeroizing::new(rpassword::prompt_password("Keystore password: ")?),
+        };
+        return keystore.unlock(&password);
+    }
+
+    match std::env::var("NANO_PRV_KEY") {
+        Ok(hex) => {
+            let hex = Zeroizing::new(hex);
+            // Don't pass the key on to child processes like notify-send
+            std::env::remove_var("NANO_PRV_KEY");
+            warn!("NANO_PRV_KEY is insecure, use `raffle-bot keystore create` instead");
+            let key = PrivateKey::from_hex_str(hex.as_str())
+                .map_err(|_| anyhow!("invalid NANO_PRV_KEY"))?;
+            Ok(Signer { key })
+        }
+        Err(_) => bail!("neither NANO_KEYSTORE nor NANO_PRV_KEY is set"),
+    }
+}
+
+fn read_password_file(path: &Path) -> anyhow::Result<Zeroizing<String>> {
+    let content = Zeroizing::new(
+        std::fs::read_to_string(path)
+            .with_context(|| format!("could not read password file {}", path.display()))?,
+    );
+    Ok(Zeroizing::new(
+        content.trim_end_matches(['\r', '\n']).to_owned(),
+    ))
+}
+
+/// What the keystore contains
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SecretKind {
+    PrivateKey,
+    /// A wallet seed. The key is derived with the index that is stored in the keystore
+    Seed,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct KdfParams {
+    /// Memory in KiB
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: 64 * 1024,
+            t_cost: 3,
+            p_cost: 1,
+        }
+    }
+}
+
+/// The key or seed encrypted with XChaCha20-Poly1305. The encryption key is
+/// derived from the password with Argon2id
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct Keystore {
+    pub version: u32,
+    pub kind: SecretKind,
+    pub index: u32,
+    pub kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+const KEYSTORE_VERSION: u32 = 1;
+
+impl Keystore {
+    pub fn encrypt(
+        secret: &[u8; 32],
+        kind: SecretKind,
+        index: u32,
+        password: &str,
+        kdf: KdfParams,
+    ) -> anyhow::Result<Self> {
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; 24];
+        rng().fill_bytes(&mut salt);
+        rng().fill_bytes(&mut nonce);
+
+        let cipher = cipher(password, &salt, kdf)?;
+        let aad = associated_data(kind, index);
+        let ciphertext = cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: secret,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| anyhow!("encryption failed"))?;
+
+        Ok(Self {
+            version: KEYSTORE_VERSION,
+            kind,
+            index,
+            kdf,
+            salt: to_hex(&salt),
+            nonce: to_hex(&nonce),
+            ciphertext: to_hex(&ciphertext),
+        })
+    }
+
+    /// Decrypts the secret and derives the key from it
+    pub fn unlock(&self, password: &str) -> anyhow::Result<Signer> {
+        let key = self.decrypt(password)?;
+        Signer::from_secret(&key)
+    }
+
+    fn decrypt(&self, password: &str) -> anyhow::Result<Zeroizing<[u8; 32]>> {
+        if self.version != KEYSTORE_VERSION {
+            bail!("unsupported keystore version {}", self.version);
+        }
+        let cipher = cipher(password, &from_hex(&self.salt)?, self.kdf)?;
+        let nonce = from_hex(&self.nonce)?;
+        if nonce.len() != 24 {
+            bail!("invalid nonce");
+        }
+        let aad = associated_data(self.kind, self.index);
+        let plain = Zeroizing::new(
+            cipher
+                .decrypt(
+                    XNonce::from_slice(&nonce),
+                    Payload {
+                        msg: &from_hex(&self.ciphertext)?,
+                        aad: aad.as_bytes(),
+                    },
+                )
+                .map_err(|_| anyhow!("wrong password or corrupted keystore"))?,
+        );
+        let secret: Zeroizing<[u8; 32]> = Zeroizing::new(
+            plain
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow!("invalid secret length"))?,
+        );
+        Ok(match self.kind {
+            SecretKind::PrivateKey => secret,
+            SecretKind::Seed => derive_key(&secret, self.index),
+        })
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("could not read keystore {}", path.display()))?;
+        serde_json::from_str(&json).with_context(|| format!("invalid keystore {}", path.display()))
+    }
+
+    /// Creates the file only readable by the owner and never overwrites an existing keystore
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let file = options
+            .open(path)
+            .with_context(|| format!("could not create keystore {}", path.display()))?;
+        serde_json::to_writer_pretty(file, self)?;
+        info!("keystore written to {}", path.display());
+        Ok(())
+    }
+}
+
+/// The kind and index are authenticated, so they can't be changed in the file
+fn associated_data(kind: SecretKind, index: u32) -> String {
+    let kind = match kind {
+        SecretKind::PrivateKey => "private_key",
+        SecretKind::Seed => "seed",
+    };
+    format!("raffle-bot-keystore:{}:{}", kind, index)
+}
+
+fn cipher(password: &str, salt: &[u8], kdf: KdfParams) -> anyhow::Result<XChaCha20Poly1305> {
+    let params = Params::new(kdf.m_cost, kdf.t_cost, kdf.p_cost, Some(32))
+        .map_err(|e| anyhow!("invalid key derivation parameters: {}", e))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut key[..])
+        .map_err(|e| anyhow!("key derivation failed: {}", e))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key[..])))
+}
+
+/// Derives the private key like the Nano wallets do: blake2b(seed || index)
+pub(crate) fn derive_key(seed: &[u8; 32], index: u32) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0u8; 32]);
+    let mut hasher = Blake2bVar::new(32).expect("valid output size");
+    hasher.update(seed);
+    hasher.update(&index.to_be_bytes());
+    hasher
+        .finalize_variable(&mut key[..])
+        .expect("valid output size");
+    key
+}
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2);
+    for b in bytes {
+        let _ = write!(hex, "{:02X}", b);
+    }
+    hex
+}
+
+pub(crate) fn from_hex(hex: &str) -> anyhow::Result<Vec<u8>> {
+    let hex = hex.trim();
+    if !hex.len().is_multiple_of(2) {
+        bail!("hex string has an odd length");
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or_else(|| anyhow!("invalid hex string"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fast parameters, so that the tests don't take seconds
+    const TEST_KDF: KdfParams = KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    };
+
+    #[test]
+    fn derive_key_from_seed() {
+        let key = derive_key(&[0; 32], 0);
+        assert_eq!(
+            to_hex(&key[..]),
+            "9F0E444C69F77A49BD0BE89DB92C38FE713E0963165CCA12FAF5712D7657120F"
+        );
+    }
+
+    #[test]
+    fn encrypt_and_decrypt() {
+        let keystore =
+            Keystore::encrypt(&[7; 32], SecretKind::PrivateKey, 0, "secret", TEST_KDF).unwrap();
+        assert_eq!(*keystore.decrypt("secret").unwrap(), [7; 32]);
+        assert!(keystore.decrypt("wrong").is_err());
+    }
+
+    #[test]
+    fn seed_keystore_derives_key() {
+        let keystore = Keystore::encrypt(&[0; 32], SecretKind::Seed, 3, "pw", TEST_KDF).unwrap();
+        assert_eq!(*keystore.decrypt("pw").unwrap(), *derive_key(&[0; 32], 3));
+    }
+
+    #[test]
+    fn index_is_authenticated() {
+        let mut keystore =
+            Keystore::encrypt(&[0; 32], SecretKind::Seed, 3, "pw", TEST_KDF).unwrap();
+        keystore.index = 4;
+        assert!(keystore.decrypt("pw").is_err());
+    }
+
+    #[test]
+    fn hex_roundtrip() {
+        assert_eq!(
+            from_hex(&to_hex(&[0, 171, 255])).unwrap(),
+            vec![0, 171, 255]
+        );
+        assert!(from_hex("abc").is_err());
+        assert!(from_hex("zz").is_err());
+    }
+}
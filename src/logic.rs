@@ -1,23 +1,79 @@
 use crate::{
-    chat_messages::{ChatMessage, LatestChatMessages},
+    addresses::{find_addresses, AddressCandidate, Alias, AliasCache, AliasLookup},
+    chat_messages::{ChatMessage, LatestChatMessages, Platform},
+    draw_animation::AnimationMode,
+    draw_rules::DrawRules,
+    jackpot::JackpotConfig,
+    named_raffles::{NamedRaffle, RaffleConfig, RaffleOverview},
+    overlay_events::OverlayEvent,
     participants::{Participant, ParticipantRegistry},
-    raffle_runner::{RaffleResult, RaffleRunner},
+    payout_policy::{PayoutAudit, PayoutLimits, PolicyDecision, Spending},
+    prediction::{Prediction, PREDICTION_RAFFLE},
+    prize_distribution::PrizeDistribution,
+    quiz::{Quiz, QUIZ_RAFFLE},
+    raffle_runner::{DrawOutcome, RaffleResult, RaffleRunner},
+    schedule::Schedule,
+    spinner_fallback::SpinnerFallback,
+    winners::{WinnerHistory, WinnerRecord},
 };
 
+use chrono::NaiveDateTime;
 use rsnano_core::{Account, Amount};
 use rsnano_nullable_clock::Timestamp;
-use std::time::Duration;
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Display,
+    time::Duration,
+};
 
 #[derive(Default)]
 pub(crate) struct RaffleLogic {
     latest_messages: LatestChatMessages,
     participants: ParticipantRegistry,
     raffle_runner: RaffleRunner,
+    /// Raffles that run next to the main raffle
+    extra_raffles: Vec<NamedRaffle>,
+    /// Raffle ids are unique across all raffles
+    last_raffle_id: u64,
     current_win: Option<RaffleResult>,
     spin_finished: bool,
+    /// The spinner that animates the current spin
+    claimed_by: Option<String>,
+    /// The last heartbeat of the spinner that claimed the spin
+    claim_ping: Option<Timestamp>,
+    last_confirmed: Option<u64>,
+    spin_started: Option<Timestamp>,
+    spinner_alerted: bool,
+    spinner_fallback: SpinnerFallback,
     running: bool,
+    /// Stop once the final draw of the stream is finished
+    stop_after_draw: bool,
+    /// The final draw, once it was drawn. A spin that was already running
+    /// when the stream ended doesn't stop the raffle
+    final_raffle_id: Option<u64>,
     last_ping: Option<Timestamp>,
-    winners: Vec<String>,
+    winners: WinnerHistory,
+    aliases: AliasCache,
+    /// Aliases are ignored unless an alias resolver is configured
+    aliases_enabled: bool,
+    events: Vec<OverlayEvent>,
+    /// Channel ids of viewers who can't participate
+    banned: HashSet<String>,
+    /// Raffle ids of winners whose payout failed
+    failed_payouts: BTreeSet<u64>,
+    retries: Vec<Winner>,
+    payout_limits: PayoutLimits,
+    /// Payouts that exceeded a limit and wait for the operator
+    held_payouts: Vec<HeldPayout>,
+    /// Raffle ids of held payouts that the operator approved
+    approved_payouts: HashSet<u64>,
+    audit: Vec<PayoutAudit>,
+    quiz: Option<Quiz>,
+    /// The first participant who answered the quiz, rewarded with the next tick
+    quiz_solved: Option<(Quiz, Participant)>,
+    prediction: Option<Prediction>,
+    /// Payouts of a resolved prediction, sent with the next tick
+    prediction_payouts: Vec<Action>,
 }
 
 impl RaffleLogic {
@@ -31,29 +87,320 @@ impl RaffleLogic {
 
     pub fn stop(&mut self) {
         self.running = false;
+        self.stop_after_draw = false;
+        self.final_raffle_id = None;
         self.raffle_runner.reset();
+        for raffle in &mut self.extra_raffles {
+            raffle.runner.reset();
+        }
+    }
+
+    /// Runs the final draw of the schedule and stops afterwards. Without a
+    /// final draw or participants the raffle stops right away
+    pub fn end_stream(&mut self, now: Timestamp) {
+        if !self.running {
+            return;
+        }
+        if !self.participants.is_empty() && self.raffle_runner.run_final_draw(now) {
+            self.stop_after_draw = true;
+        } else {
+            self.stop();
+        }
     }
 
     pub fn handle_chat_message(&mut self, message: ChatMessage) {
-        for word in message.message.split_whitespace() {
-            if let Ok(account) = Account::decode_account(word) {
-                self.participants.add(Participant {
+        let name = message
+            .author_name
+            .clone()
+            .unwrap_or_else(|| "no name".to_string());
+
+        for candidate in find_addresses(&message.message) {
+            let account = match candidate {
+                AddressCandidate::Account(account) => Some(account),
+                AddressCandidate::Alias(_) if !self.aliases_enabled => None,
+                AddressCandidate::Alias(alias) => self.aliases.get_or_queue(AliasLookup {
+                    alias,
+                    channel_id: message.author_channel_id.clone(),
+                    name: name.clone(),
+                    platform: message.platform,
+                }),
+            };
+
+            if let Some(account) = account {
+                self.register(Participant {
                     channel_id: message.author_channel_id.clone(),
-                    name: message
-                        .author_name
-                        .clone()
-                        .unwrap_or_else(|| "no name".to_string()),
+                    name: name.clone(),
                     account,
+                    platform: message.platform,
                 });
             }
         }
+        self.check_quiz_answer(&message);
+        self.check_vote(&message);
+        self.events.push(OverlayEvent::Chat {
+            name,
+            message: message.message.clone(),
+            platform: message.platform,
+        });
         self.latest_messages.add(message);
     }
 
+    /// Only registered participants can win the quiz
+    fn check_quiz_answer(&mut self, message: &ChatMessage) {
+        let Some(quiz) = &self.quiz else {
+            return;
+        };
+        if !quiz.is_answered_by(&message.message) {
+            return;
+        }
+        let Some(participant) = self.participants.get(&message.author_channel_id).cloned() else {
+            return;
+        };
+        self.quiz_solved = self.quiz.take().map(|quiz| (quiz, participant));
+    }
+
+    /// Asks a question in the overlay. The first participant who types the
+    /// answer in chat before the time runs out wins the prize
+    pub fn start_quiz(&mut self, quiz: Quiz, now: Timestamp) {
+        let mut message = format!(
+            "Quiz for Ӿ {}! First to answer in chat within {}s wins",
+            quiz.prize.format_balance(2),
+            quiz.time_left(now).as_secs()
+        );
+        if !quiz.question.is_empty() {
+            message = format!("{}: {}", message, quiz.question);
+        }
+        self.events.push(OverlayEvent::Announcement { message });
+        self.quiz = Some(quiz);
+    }
+
+    pub fn quiz(&self) -> Option<&Quiz> {
+        self.quiz.as_ref()
+    }
+
+    pub fn cancel_quiz(&mut self) {
+        self.quiz = None;
+    }
+
+    fn finish_quiz(&mut self, now: Timestamp) -> Vec<Action> {
+        if let Some((quiz, winner)) = self.quiz_solved.take() {
+            self.last_raffle_id += 1;
+            let participants = self.participants.list();
+            let result = RaffleResult {
+                id: self.last_raffle_id,
+                raffle: QUIZ_RAFFLE.to_owned(),
+                winner,
+                animation: AnimationMode::auto(participants.len()),
+                participants,
+                prize: quiz.prize,
+                random: 0,
+                prize_reveal: None,
+            };
+            return self.reward_winner(result);
+        }
+
+        match self.quiz.take() {
+            Some(quiz) if quiz.expired(now) => {
+                let message = format!("Time is up! Nobody found the answer: {}", quiz.answer());
+                vec![self.announce(message)]
+            }
+            quiz => {
+                self.quiz = quiz;
+                Vec::new()
+            }
+        }
+    }
+
+    /// Only registered participants can vote, so that correct voters can be paid
+    fn check_vote(&mut self, message: &ChatMessage) {
+        let Some(prediction) = &mut self.prediction else {
+            return;
+        };
+        if self.participants.get(&message.author_channel_id).is_some() {
+            prediction.vote(&message.author_channel_id, &message.message);
+        }
+    }
+
+    /// Opens a prediction that registered viewers vote on with `!vote`
+    pub fn open_prediction(&mut self, prediction: Prediction) {
+        self.events.push(OverlayEvent::Announcement {
+            message: prediction.announcement(),
+        });
+        self.prediction = Some(prediction);
+    }
+
+    pub fn prediction(&self) -> Option<&Prediction> {
+        self.prediction.as_ref()
+    }
+
+    /// Stops accepting votes before the outcome is known
+    pub fn close_prediction(&mut self) {
+        if let Some(prediction) = &mut self.prediction {
+            prediction.close();
+        }
+    }
+
+    pub fn cancel_prediction(&mut self) {
+        if self.prediction.take().is_some() {
+            let action = self.announce("The prediction was cancelled".to_owned());
+            self.prediction_payouts.push(action);
+        }
+    }
+
+    /// Splits the pool among the viewers who voted for the given option. Every
+    /// correct voter is recorded and paid like the winner of a raffle.
+    /// Returns false if there is no prediction or no such option
+    pub fn resolve_prediction(&mut self, option: usize) -> bool {
+        let Some(prediction) = self.prediction.take_if(|p| option < p.options.len()) else {
+            return false;
+        };
+        let voters: Vec<Participant> = prediction
+            .voters()
+            .into_iter()
+            .filter_map(|channel_id| self.participants.get(channel_id).cloned())
+            .collect();
+        let correct: Vec<&Participant> = voters
+            .iter()
+            .filter(|v| prediction.voted_for(&v.channel_id) == Some(option))
+            .collect();
+        let outcome = &prediction.options[option];
+
+        let Some(share) = prediction.share(correct.len()) else {
+            let action = self.announce(format!("Nobody predicted {}", outcome));
+            self.prediction_payouts.push(action);
+            return true;
+        };
+        let action = self.announce(format!(
+            "{} viewers predicted {} and share Ӿ {}",
+            correct.len(),
+            outcome,
+            prediction.pool.format_balance(2)
+        ));
+        self.prediction_payouts.push(action);
+
+        // Each payout is recorded as its own raffle. Only the winner is entered,
+        // otherwise every payout would store all voters again
+        for winner in correct {
+            self.last_raffle_id += 1;
+            let result = RaffleResult {
+                id: self.last_raffle_id,
+                raffle: PREDICTION_RAFFLE.to_owned(),
+                winner: winner.clone(),
+                participants: vec![winner.clone()],
+                prize: share,
+                animation: AnimationMode::auto(1),
+                random: 0,
+                prize_reveal: None,
+            };
+            let send_prize = Action::SendToWinner((&result).into());
+            self.prediction_payouts.push(Action::RecordRaffle(result));
+            self.prediction_payouts.push(send_prize);
+        }
+        true
+    }
+
+    /// Stores the result of an alias lookup and registers every viewer who used it
+    pub fn alias_resolved(&mut self, alias: Alias, result: anyhow::Result<Option<Account>>) {
+        let (account, waiting) = self.aliases.resolved(alias, result);
+        let Some(account) = account else {
+            return;
+        };
+        for lookup in waiting {
+            self.register(Participant {
+                channel_id: lookup.channel_id,
+                name: lookup.name,
+                account,
+                platform: lookup.platform,
+            });
+        }
+    }
+
+    fn register(&mut self, participant: Participant) {
+        if self.banned.contains(&participant.channel_id) {
+            return;
+        }
+        let name = participant.name.clone();
+        if self.participants.add(participant) {
+            if let Some(jackpot) = self.raffle_runner.jackpot_mut() {
+                jackpot.participant_joined();
+            }
+            self.events.push(OverlayEvent::Registration { name });
+        }
+    }
+
+    /// Removes a participant. Returns false if nobody is registered with that channel id
+    pub fn kick(&mut self, channel_id: &str) -> bool {
+        self.participants.remove(channel_id)
+    }
+
+    /// Removes a participant and ignores all future registrations of the channel
+    pub fn ban(&mut self, channel_id: &str) {
+        self.kick(channel_id);
+        self.banned.insert(channel_id.to_owned());
+    }
+
+    pub fn set_banned(&mut self, channel_ids: Vec<String>) {
+        self.banned = channel_ids.into_iter().collect();
+    }
+
+    pub fn set_aliases_enabled(&mut self, enabled: bool) {
+        self.aliases_enabled = enabled;
+    }
+
+    pub fn set_spinner_fallback(&mut self, fallback: SpinnerFallback) {
+        self.spinner_fallback = fallback;
+    }
+
+    /// The raffle ids continue after the ones that were already used
+    pub fn set_next_raffle_id(&mut self, id: u64) {
+        self.last_raffle_id = id.saturating_sub(1);
+    }
+
+    pub fn add_raffle(&mut self, config: RaffleConfig) {
+        self.extra_raffles.push(NamedRaffle::new(config));
+    }
+
+    /// The main raffle followed by the additional raffles
+    pub fn raffles(&mut self, now: Timestamp) -> Vec<RaffleOverview> {
+        let running = self.running;
+        let main = (&mut self.raffle_runner, self.participants.len());
+        let participants = &self.participants;
+        let winners = &self.winners;
+        let extra = self.extra_raffles.iter_mut().map(|r| {
+            let entries = r.eligible(participants, winners).len();
+            (&mut r.runner, entries)
+        });
+        std::iter::once(main)
+            .chain(extra)
+            .map(|(runner, participants)| RaffleOverview {
+                name: runner.name().to_owned(),
+                participants,
+                countdown_secs: running.then(|| {
+                    let next = runner.next_raffle(now);
+                    if now >= next {
+                        0
+                    } else {
+                        (next - now).as_secs()
+                    }
+                }),
+                next_prize: runner.next_prize(),
+            })
+            .collect()
+    }
+
     pub fn set_prize(&mut self, prize: Amount) {
         self.raffle_runner.set_prize(prize);
     }
 
+    /// `None` means that the animation is chosen by the number of participants
+    pub fn animation(&self) -> Option<AnimationMode> {
+        self.raffle_runner.animation()
+    }
+
+    pub fn set_animation(&mut self, animation: Option<AnimationMode>) {
+        self.raffle_runner.set_animation(animation);
+    }
+
     pub fn raffle_interval(&self) -> Duration {
         self.raffle_runner.raffle_interval()
     }
@@ -62,6 +409,78 @@ impl RaffleLogic {
         self.raffle_runner.set_raffle_interval(interval);
     }
 
+    pub fn set_draw_rules(&mut self, rules: DrawRules) {
+        self.raffle_runner.set_draw_rules(rules);
+    }
+
+    pub fn set_prize_distribution(&mut self, distribution: PrizeDistribution) {
+        self.raffle_runner.set_prize_distribution(distribution);
+    }
+
+    /// How many viewers still have to enter before a raffle can be drawn
+    pub fn missing_participants(&self) -> usize {
+        self.raffle_runner
+            .draw_rules()
+            .required()
+            .saturating_sub(self.participants.len())
+    }
+
+    pub fn set_jackpot(&mut self, config: JackpotConfig) {
+        self.raffle_runner.set_jackpot(config);
+    }
+
+    /// The unpaid pot, if the jackpot mode is enabled
+    pub fn jackpot(&self) -> Option<Amount> {
+        self.raffle_runner.jackpot().map(|j| j.pot())
+    }
+
+    /// Restores the pot that wasn't won before a restart
+    pub fn restore_jackpot(&mut self, pot: Amount) {
+        if let Some(jackpot) = self.raffle_runner.jackpot_mut() {
+            jackpot.restore(pot);
+        }
+    }
+
+    /// Someone sent nano to the bot's wallet
+    pub fn donation_received(&mut self, source: Account, amount: Amount) {
+        if let Some(jackpot) = self.raffle_runner.jackpot_mut() {
+            jackpot.donation_received(source, amount);
+        }
+    }
+
+    /// The bot doesn't receive donations, so the pot may not exceed what the wallet can pay
+    pub fn wallet_balance(&mut self, balance: Amount) {
+        self.raffle_runner.cap_jackpot(balance);
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        self.raffle_runner.schedule()
+    }
+
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.raffle_runner.set_schedule(schedule);
+    }
+
+    /// Tells the logic the local time of day, which the schedule is based on
+    pub fn sync_local_time(&mut self, now: Timestamp, local: NaiveDateTime) {
+        self.raffle_runner.sync_local_time(now, local);
+        for raffle in &mut self.extra_raffles {
+            raffle.runner.sync_local_time(now, local);
+        }
+    }
+
+    /// Local time and prize of the next raffle
+    pub fn next_draw(&mut self, now: Timestamp) -> (Option<NaiveDateTime>, Amount) {
+        if !self.running {
+            return (None, self.raffle_runner.next_prize());
+        }
+        let next = self.raffle_runner.next_raffle(now);
+        (
+            self.raffle_runner.local_time(next),
+            self.raffle_runner.next_prize(),
+        )
+    }
+
     pub fn run_raffle_now(&mut self, now: Timestamp) {
         self.raffle_runner.run_raffle_now(now);
     }
@@ -70,13 +489,17 @@ impl RaffleLogic {
         self.raffle_runner.prize()
     }
 
-    pub fn ping(&mut self, now: Timestamp) {
+    /// A spinner received a heartbeat
+    pub fn ping(&mut self, now: Timestamp, spinner_id: &str) {
         self.last_ping = Some(now);
+        if self.claimed_by.as_deref() == Some(spinner_id) {
+            self.claim_ping = Some(now);
+        }
     }
 
     pub fn spinner_connected(&self, now: Timestamp) -> bool {
         self.last_ping
-            .map(|p| now - p < Duration::from_secs(3))
+            .map(|p| now - p < SPINNER_TIMEOUT)
             .unwrap_or_default()
     }
 
@@ -96,10 +519,103 @@ impl RaffleLogic {
         self.participants.list()
     }
 
-    pub fn winners(&self) -> &Vec<String> {
+    pub fn winners(&self) -> &WinnerHistory {
         &self.winners
     }
 
+    /// Adds a recorded winner. It counts as unpaid until `payout_sent` is called
+    pub fn add_winner(&mut self, record: WinnerRecord) {
+        self.winners.add(record);
+    }
+
+    pub fn payout_sent(&mut self, raffle_id: u64, block_hash: String) {
+        self.winners.set_block_hash(raffle_id, block_hash);
+        self.failed_payouts.remove(&raffle_id);
+    }
+
+    pub fn payout_failed(&mut self, raffle_id: u64) {
+        self.failed_payouts.insert(raffle_id);
+    }
+
+    /// Recorded winners who didn't receive their prize
+    pub fn failed_payouts(&self) -> Vec<&WinnerRecord> {
+        self.failed_payouts
+            .iter()
+            .filter_map(|id| self.winners.get(*id))
+            .collect()
+    }
+
+    /// Sends the prize of a failed payout again with the next tick.
+    /// Returns false if there is no failed payout for that raffle
+    pub fn retry_payout(&mut self, raffle_id: u64) -> bool {
+        let Some(record) = self.winners.get(raffle_id) else {
+            return false;
+        };
+        if !self.failed_payouts.remove(&raffle_id) {
+            return false;
+        }
+        self.retries.push(record.into());
+        true
+    }
+
+    pub fn set_payout_limits(&mut self, limits: PayoutLimits) {
+        self.payout_limits = limits;
+    }
+
+    /// Checks a payout against the limits. Payouts that were approved by the
+    /// operator skip the soft limits
+    pub fn check_payout(&mut self, winner: &Winner, spent: &Spending) -> PolicyDecision {
+        let approved = self.approved_payouts.remove(&winner.raffle_id);
+        self.payout_limits.check(winner.prize, spent, approved)
+    }
+
+    pub fn hold_payout(&mut self, winner: Winner, reason: String) {
+        self.held_payouts.push(HeldPayout { winner, reason });
+    }
+
+    pub fn held_payouts(&self) -> &[HeldPayout] {
+        &self.held_payouts
+    }
+
+    /// Sends a held payout with the next tick. Returns false if no payout is held for that raffle
+    pub fn approve_payout(&mut self, raffle_id: u64) -> bool {
+        let Some(held) = self.take_held_payout(raffle_id) else {
+            return false;
+        };
+        self.audit_operator_decision(&held.winner, "approved");
+        self.approved_payouts.insert(raffle_id);
+        self.retries.push(held.winner);
+        true
+    }
+
+    /// Moves a held payout to the failed payouts, from where it can be retried later
+    pub fn deny_payout(&mut self, raffle_id: u64) -> bool {
+        let Some(held) = self.take_held_payout(raffle_id) else {
+            return false;
+        };
+        self.audit_operator_decision(&held.winner, "denied");
+        self.failed_payouts.insert(raffle_id);
+        true
+    }
+
+    fn take_held_payout(&mut self, raffle_id: u64) -> Option<HeldPayout> {
+        let index = self
+            .held_payouts
+            .iter()
+            .position(|h| h.winner.raffle_id == raffle_id)?;
+        Some(self.held_payouts.remove(index))
+    }
+
+    fn audit_operator_decision(&mut self, winner: &Winner, decision: &'static str) {
+        self.audit.push(PayoutAudit {
+            raffle_id: winner.raffle_id,
+            account: winner.account,
+            amount: winner.prize,
+            decision,
+            reason: "operator".to_owned(),
+        });
+    }
+
     pub fn countdown(&mut self, now: Timestamp) -> Duration {
         if !self.running {
             return Duration::ZERO;
@@ -114,73 +630,382 @@ impl RaffleLogic {
     }
 
     pub fn tick(&mut self, now: Timestamp, random: u32) -> Vec<Action> {
-        if !self.running {
-            return Vec::new();
+        let mut actions: Vec<Action> = self
+            .aliases
+            .take_pending()
+            .into_iter()
+            .map(Action::ResolveAlias)
+            .collect();
+        actions.extend(self.audit.drain(..).map(Action::AuditPayout));
+        actions.extend(self.retries.drain(..).map(Action::SendToWinner));
+        actions.extend(self.finish_quiz(now));
+        actions.append(&mut self.prediction_payouts);
+
+        let running = self.running;
+        if let Some(jackpot) = self.raffle_runner.jackpot_mut() {
+            if running {
+                jackpot.grow(now);
+            } else {
+                jackpot.pause();
+            }
+            actions.extend(jackpot.take_unsaved(now).map(Action::SaveJackpot));
         }
 
-        let result = self
-            .raffle_runner
-            .try_run_raffle(&self.participants, now, random);
+        let countdown = self.countdown(now);
+        self.events.push(OverlayEvent::Countdown {
+            seconds: countdown.as_secs(),
+            running: self.running,
+            missing_participants: self.missing_participants(),
+            prize: self.raffle_runner.next_prize().format_balance(2),
+        });
 
-        if result.is_some() {
-            self.spin_finished = false;
-            self.current_win = result;
+        if !self.running {
+            return actions;
         }
 
-        let mut actions = Vec::new();
+        // The overlay shows one spin at a time, due raffles wait until it is confirmed
+        if self.current_win.is_none() {
+            actions.extend(self.draw_due_raffles(now, random));
+        }
+
+        actions.extend(self.apply_spinner_fallback(now));
 
         if self.spin_finished {
             if let Some(win) = self.current_win.take() {
-                self.winners.push(win.winner.clone());
+                let is_final = self.final_raffle_id == Some(win.id);
                 actions.extend(self.reward_winner(win));
                 self.spin_finished = false;
+                if is_final {
+                    self.stop();
+                }
+            }
+        }
+
+        actions
+    }
+
+    fn draw_due_raffles(&mut self, now: Timestamp, random: u32) -> Vec<Action> {
+        let id = self.last_raffle_id + 1;
+        let mut actions = Vec::new();
+        let result = self
+            .raffle_runner
+            .try_run_raffle(&self.participants, now, random, id);
+        match result {
+            Some(DrawOutcome::Drawn(result)) => {
+                if self.stop_after_draw {
+                    self.final_raffle_id = Some(result.id);
+                }
+                self.start_spin(result, now);
+                return actions;
             }
+            Some(DrawOutcome::Shortfall(shortfall)) => {
+                actions.push(self.announce(shortfall.message()));
+                // The final draw of the stream can't wait for more viewers
+                if self.stop_after_draw {
+                    self.stop();
+                    return actions;
+                }
+            }
+            None => {}
         }
 
+        for i in 0..self.extra_raffles.len() {
+            let raffle = &mut self.extra_raffles[i];
+            let eligible = raffle.eligible(&self.participants, &self.winners);
+            match raffle.runner.try_run_raffle(&eligible, now, random, id) {
+                Some(DrawOutcome::Drawn(result)) => {
+                    self.start_spin(result, now);
+                    break;
+                }
+                Some(DrawOutcome::Shortfall(shortfall)) => {
+                    let message = format!("{}: {}", raffle.runner.name(), shortfall.message());
+                    actions.push(self.announce(message));
+                }
+                None => {}
+            }
+        }
         actions
     }
 
-    pub fn spin_finished(&mut self) {
+    fn start_spin(&mut self, result: RaffleResult, now: Timestamp) {
+        self.last_raffle_id = result.id;
+        self.events
+            .push(OverlayEvent::Spin(result.spin_instruction()));
+        self.spin_finished = false;
+        self.claimed_by = None;
+        self.claim_ping = None;
+        self.spin_started = Some(now);
+        self.spinner_alerted = false;
+        self.current_win = Some(result);
+    }
+
+    fn announce(&mut self, message: String) -> Action {
+        self.events.push(OverlayEvent::Announcement {
+            message: message.clone(),
+        });
+        Action::Notify(message)
+    }
+
+    /// Takes the events that need to be pushed to the overlays
+    pub fn take_events(&mut self) -> Vec<OverlayEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// The events a newly connected overlay needs to show the current state
+    pub fn overlay_snapshot(&mut self, now: Timestamp) -> Vec<OverlayEvent> {
+        let mut events = vec![OverlayEvent::Countdown {
+            seconds: self.countdown(now).as_secs(),
+            running: self.running,
+            missing_participants: self.missing_participants(),
+            prize: self.raffle_runner.next_prize().format_balance(2),
+        }];
+        if let Some(win) = self.current_win() {
+            events.push(OverlayEvent::Spin(win.spin_instruction()));
+        }
+        events
+    }
+
+    /// The spinner that claimed the current spin
+    pub fn claimed_by(&self) -> Option<&str> {
+        self.claimed_by.as_deref()
+    }
+
+    /// A spinner announces that it animates the given raffle. Only the first spinner
+    /// gets the claim, all others may animate the spin but can't confirm it.
+    /// If the claiming spinner stops receiving heartbeats, another spinner takes over
+    pub fn claim_spin(
+        &mut self,
+        raffle_id: u64,
+        spinner_id: &str,
+        now: Timestamp,
+    ) -> Result<(), SpinError> {
+        self.check_pending_spin(raffle_id)?;
+        match &self.claimed_by {
+            Some(claimed_by) if claimed_by != spinner_id && !self.claim_stale(now) => {
+                Err(SpinError::ClaimedByOtherSpinner)
+            }
+            _ => {
+                self.claimed_by = Some(spinner_id.to_owned());
+                self.claim_ping = Some(now);
+                Ok(())
+            }
+        }
+    }
+
+    fn claim_stale(&self, now: Timestamp) -> bool {
+        self.claim_ping
+            .is_none_or(|p| now > p && now - p >= SPINNER_TIMEOUT)
+    }
+
+    /// The spinner finished the animation of the given raffle.
+    /// Confirming the same raffle more than once has no effect.
+    pub fn confirm_spin(
+        &mut self,
+        raffle_id: u64,
+        spinner_id: &str,
+        now: Timestamp,
+    ) -> Result<(), SpinError> {
+        if self.last_confirmed == Some(raffle_id) {
+            return Ok(());
+        }
+        self.claim_spin(raffle_id, spinner_id, now)?;
+        self.finish_spin(raffle_id);
+        Ok(())
+    }
+
+    /// The operator confirms the current spin, regardless of which spinner claimed it
+    pub fn confirm_manually(&mut self) {
+        if let Some(win) = self.current_win() {
+            self.finish_spin(win.id);
+        }
+    }
+
+    fn finish_spin(&mut self, raffle_id: u64) {
         self.spin_finished = true;
+        self.last_confirmed = Some(raffle_id);
+    }
+
+    /// How long the current spin has been waiting for its confirmation
+    pub fn spin_pending_for(&self, now: Timestamp) -> Option<Duration> {
+        let started = self.spin_started?;
+        self.current_win()?;
+        Some(if now > started {
+            now - started
+        } else {
+            Duration::ZERO
+        })
     }
 
-    fn reward_winner(&self, result: RaffleResult) -> Vec<Action> {
+    /// The timeout counts from the start of the spin and applies as soon as no
+    /// spinner is connected. A connected spinner gets at least
+    /// `CONNECTED_SPIN_TIMEOUT` to finish, so that only a hanging one is overruled
+    fn apply_spinner_fallback(&mut self, now: Timestamp) -> Option<Action> {
+        let pending_for = self.spin_pending_for(now)?;
+        let raffle_id = self.current_win()?.id;
+        let connected = self.spinner_connected(now);
+        let timed_out = |timeout: Duration| {
+            if connected {
+                pending_for >= timeout.max(CONNECTED_SPIN_TIMEOUT)
+            } else {
+                pending_for >= timeout
+            }
+        };
+        let reason = if connected {
+            "Spin not confirmed"
+        } else {
+            "Spinner offline"
+        };
+        match self.spinner_fallback {
+            SpinnerFallback::AutoConfirm(timeout) if timed_out(timeout) => {
+                self.finish_spin(raffle_id);
+                Some(Action::Notify(format!(
+                    "{}. Raffle #{} was confirmed automatically",
+                    reason, raffle_id
+                )))
+            }
+            SpinnerFallback::HoldAndAlert(timeout)
+                if timed_out(timeout) && !self.spinner_alerted =>
+            {
+                self.spinner_alerted = true;
+                Some(Action::Notify(format!(
+                    "{}! Raffle #{} waits for confirmation",
+                    reason, raffle_id
+                )))
+            }
+            _ => None,
+        }
+    }
+
+    fn check_pending_spin(&self, raffle_id: u64) -> Result<(), SpinError> {
+        match self.current_win() {
+            None => Err(SpinError::NoSpinPending),
+            Some(win) if win.id != raffle_id => Err(SpinError::WrongRaffle {
+                expected: win.id,
+                actual: raffle_id,
+            }),
+            Some(_) => Ok(()),
+        }
+    }
+
+    fn reward_winner(&mut self, result: RaffleResult) -> Vec<Action> {
+        self.events.push(OverlayEvent::Winner {
+            name: result.winner.name.clone(),
+            prize: result.prize.format_balance(2),
+        });
+
         let notify = Action::Notify(format!(
             "Congratulations {}! You've just won Ӿ {}",
-            result.winner,
+            result.winner.name,
             result.prize.format_balance(2)
         ));
 
-        let send_prize = Action::SendToWinner(Winner {
-            name: result.winner,
-            prize: result.prize,
-            account: result.destination,
-        });
+        let send_prize = Action::SendToWinner((&result).into());
 
-        vec![notify, send_prize]
+        vec![Action::RecordRaffle(result), notify, send_prize]
     }
 
     pub(crate) fn set_participants(&mut self, participants: Vec<Participant>) {
         self.participants.set(participants);
     }
+
+    pub(crate) fn set_winners(&mut self, winners: Vec<WinnerRecord>, current_stream: i64) {
+        self.failed_payouts = winners
+            .iter()
+            .filter(|w| !w.paid())
+            .map(|w| w.raffle_id)
+            .collect();
+        self.winners.set(winners);
+        self.winners.set_current_stream(current_stream);
+    }
 }
 
+/// A spinner is disconnected if it didn't receive a heartbeat for this long
+const SPINNER_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long a connected spinner may take at least before the fallback applies
+const CONNECTED_SPIN_TIMEOUT: Duration = Duration::from_secs(120);
+
 #[derive(PartialEq, Eq, Debug)]
 pub(crate) enum Action {
     SendToWinner(Winner),
     Notify(String),
+    ResolveAlias(Alias),
+    /// Persist a raffle whose spin has finished
+    RecordRaffle(RaffleResult),
+    /// Persist a decision of the operator about a held payout
+    AuditPayout(PayoutAudit),
+    /// Persist the unpaid jackpot
+    SaveJackpot(Amount),
+}
+
+/// Why a spinner can't claim or confirm a spin
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum SpinError {
+    NoSpinPending,
+    WrongRaffle { expected: u64, actual: u64 },
+    ClaimedByOtherSpinner,
+}
+
+impl Display for SpinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoSpinPending => write!(f, "no spin pending"),
+            Self::WrongRaffle { expected, actual } => {
+                write!(f, "spin for raffle {} expected, got {}", expected, actual)
+            }
+            Self::ClaimedByOtherSpinner => write!(f, "spin was claimed by another spinner"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub(crate) struct Winner {
+    pub raffle_id: u64,
     pub name: String,
+    pub channel_id: String,
+    pub platform: Platform,
     pub prize: Amount,
     pub account: Account,
 }
 
+/// A payout that exceeded a limit
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct HeldPayout {
+    pub winner: Winner,
+    pub reason: String,
+}
+
+impl From<&RaffleResult> for Winner {
+    fn from(result: &RaffleResult) -> Self {
+        Self {
+            raffle_id: result.id,
+            name: result.winner.name.clone(),
+            channel_id: result.winner.channel_id.clone(),
+            platform: result.winner.platform,
+            prize: result.prize,
+            account: result.winner.account,
+        }
+    }
+}
+
+impl From<&WinnerRecord> for Winner {
+    fn from(record: &WinnerRecord) -> Self {
+        Self {
+            raffle_id: record.raffle_id,
+            name: record.name.clone(),
+            channel_id: record.channel_id.clone(),
+            platform: record.platform,
+            prize: record.prize,
+            account: record.account,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        draw_rules::ShortfallAction, named_raffles::Eligibility, overlay_events::SpinInstruction,
+    };
 
     #[test]
     fn initial_state() {
@@ -218,11 +1043,122 @@ mod tests {
                 account: Account::decode_account(
                     "nano_37391u1nrr1j7tdn8w9zathoio5suz9bar18jksqheeiy4obwz3pkgp9aqz6"
                 )
-                .unwrap()
+                .unwrap(),
+                platform: message.platform,
             }
         );
     }
 
+    #[test]
+    fn register_viewer_with_xrb_prefix() {
+        let mut app = RaffleLogic::default();
+        app.handle_chat_message(ChatMessage {
+            message: "xrb_37391u1nrr1j7tdn8w9zathoio5suz9bar18jksqheeiy4obwz3pkgp9aqz6".to_owned(),
+            ..ChatMessage::new_test_instance()
+        });
+        assert_eq!(app.participants().len(), 1);
+    }
+
+    #[test]
+    fn resolve_alias() {
+        let mut app = RaffleLogic::default();
+        app.set_aliases_enabled(true);
+        let message = ChatMessage {
+            message: "@alice".to_owned(),
+            ..ChatMessage::new_test_instance()
+        };
+        app.handle_chat_message(message.clone());
+        assert_eq!(app.participants().len(), 0);
+
+        let actions = app.tick(Timestamp::new_test_instance(), 0);
+        let [Action::ResolveAlias(alias)] = actions.as_slice() else {
+            panic!("alias lookup expected, got {:?}", actions);
+        };
+        assert_eq!(alias, &Alias::new("alice", None));
+
+        app.alias_resolved(alias.clone(), Ok(Some(Account::from(7))));
+        assert_eq!(app.participants()[0].account, Account::from(7));
+
+        // second time the cached result is used
+        app.handle_chat_message(message);
+        assert!(app.tick(Timestamp::new_test_instance(), 0).is_empty());
+    }
+
+    #[test]
+    fn ignore_aliases_without_resolver() {
+        let mut app = RaffleLogic::default();
+        app.handle_chat_message(ChatMessage {
+            message: "@alice".to_owned(),
+            ..ChatMessage::new_test_instance()
+        });
+        assert!(app.tick(Timestamp::new_test_instance(), 0).is_empty());
+    }
+
+    #[test]
+    fn push_registration_event() {
+        let mut app = RaffleLogic::default();
+        let message = ChatMessage::new_test_instance_for_account(Account::from(42));
+        app.handle_chat_message(message.clone());
+        app.handle_chat_message(message);
+        let registrations: Vec<_> = app
+            .take_events()
+            .into_iter()
+            .filter(|e| matches!(e, OverlayEvent::Registration { .. }))
+            .collect();
+        assert_eq!(
+            registrations,
+            vec![OverlayEvent::Registration {
+                name: "John Doe".to_owned()
+            }]
+        );
+        assert!(app.take_events().is_empty());
+    }
+
+    #[test]
+    fn push_chat_event() {
+        let mut app = RaffleLogic::default();
+        app.handle_chat_message(ChatMessage::new_test_instance());
+        assert_eq!(
+            app.take_events(),
+            vec![OverlayEvent::Chat {
+                name: "John Doe".to_owned(),
+                message: "test message".to_owned(),
+                platform: Platform::YouTube,
+            }]
+        );
+    }
+
+    #[test]
+    fn push_spin_event() {
+        let mut logic = RaffleLogic::default();
+        logic.start();
+        let start = Timestamp::new_test_instance();
+        logic.tick(start, 0);
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(
+            42,
+        )));
+        logic.take_events();
+
+        logic.tick(start + logic.raffle_interval(), 0);
+
+        let events = logic.take_events();
+        assert!(events.contains(&OverlayEvent::Spin(SpinInstruction {
+            raffle_id: 1,
+            raffle: "main".to_owned(),
+            animation: AnimationMode::Wheel,
+            participants: vec!["John Doe".to_owned()],
+            winner: 0,
+            total_participants: 1,
+            prize_reveal: None,
+        })));
+        assert_eq!(
+            logic
+                .overlay_snapshot(start + logic.raffle_interval())
+                .len(),
+            2
+        );
+    }
+
     #[test]
     fn tick_empty() {
         let mut logic = RaffleLogic::default();
@@ -239,31 +1175,641 @@ mod tests {
         logic.tick(start, 0);
         let account = Account::from(42);
         let msg = ChatMessage::new_test_instance_for_account(account);
-        let viewer = msg.author_name.as_ref().unwrap().clone();
+        let viewer = Participant {
+            channel_id: msg.author_channel_id.clone(),
+            name: msg.author_name.as_ref().unwrap().clone(),
+            account,
+            platform: msg.platform,
+        };
         logic.handle_chat_message(msg.clone());
         let actions = logic.tick(start + logic.raffle_interval(), 0);
         assert_eq!(actions.len(), 0);
         assert_eq!(
             logic.current_win(),
             Some(&RaffleResult {
+                id: 1,
+                raffle: "main".to_owned(),
                 winner: viewer.clone(),
                 participants: vec![viewer.clone()],
                 prize: logic.prize(),
-                destination: account
+                animation: AnimationMode::Wheel,
+                random: 0,
+                prize_reveal: None,
             })
         );
-        logic.spin_finished();
+        logic
+            .confirm_spin(1, "spinner", start + logic.raffle_interval())
+            .unwrap();
 
         let actions = logic.tick(start + logic.raffle_interval(), 0);
         assert!(actions.len() > 1);
         assert_eq!(
             actions.last().unwrap(),
             &Action::SendToWinner(Winner {
-                name: viewer,
+                raffle_id: 1,
+                name: viewer.name,
+                channel_id: viewer.channel_id,
+                platform: viewer.platform,
                 prize: logic.prize(),
                 account
             })
         );
         assert!(logic.current_win().is_none());
     }
+
+    fn logic_with_pending_spin() -> RaffleLogic {
+        let mut logic = RaffleLogic::default();
+        logic.start();
+        let start = Timestamp::new_test_instance();
+        logic.tick(start, 0);
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(
+            42,
+        )));
+        logic.tick(start + logic.raffle_interval(), 0);
+        logic
+    }
+
+    #[test]
+    fn reject_confirmation_without_pending_spin() {
+        let mut logic = RaffleLogic::default();
+        let now = Timestamp::new_test_instance();
+        assert_eq!(
+            logic.confirm_spin(1, "spinner", now),
+            Err(SpinError::NoSpinPending)
+        );
+    }
+
+    #[test]
+    fn reject_confirmation_for_wrong_raffle() {
+        let mut logic = logic_with_pending_spin();
+        let now = Timestamp::new_test_instance();
+        assert_eq!(
+            logic.confirm_spin(2, "spinner", now),
+            Err(SpinError::WrongRaffle {
+                expected: 1,
+                actual: 2
+            })
+        );
+        assert!(logic.current_win().is_some());
+    }
+
+    #[test]
+    fn only_claiming_spinner_can_confirm() {
+        let mut logic = logic_with_pending_spin();
+        let now = Timestamp::new_test_instance();
+        logic.claim_spin(1, "first", now).unwrap();
+        assert_eq!(
+            logic.claim_spin(1, "second", now),
+            Err(SpinError::ClaimedByOtherSpinner)
+        );
+        assert_eq!(
+            logic.confirm_spin(1, "second", now),
+            Err(SpinError::ClaimedByOtherSpinner)
+        );
+        assert_eq!(logic.claimed_by(), Some("first"));
+        logic.confirm_spin(1, "first", now).unwrap();
+        assert!(logic.current_win().is_none());
+    }
+
+    #[test]
+    fn take_over_claim_of_disconnected_spinner() {
+        let mut logic = logic_with_pending_spin();
+        let now = Timestamp::new_test_instance();
+        logic.claim_spin(1, "first", now).unwrap();
+        logic.ping(now + Duration::from_secs(2), "first");
+        assert_eq!(
+            logic.claim_spin(1, "second", now + Duration::from_secs(4)),
+            Err(SpinError::ClaimedByOtherSpinner)
+        );
+
+        logic
+            .confirm_spin(1, "second", now + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(logic.claimed_by(), Some("second"));
+        assert!(logic.current_win().is_none());
+    }
+
+    #[test]
+    fn confirmation_is_idempotent() {
+        let mut logic = logic_with_pending_spin();
+        let now = Timestamp::new_test_instance();
+        logic.confirm_spin(1, "spinner", now).unwrap();
+        logic.confirm_spin(1, "spinner", now).unwrap();
+
+        let actions = logic.tick(Timestamp::new_test_instance() + logic.raffle_interval(), 0);
+        assert_eq!(
+            actions
+                .iter()
+                .filter(|a| matches!(a, Action::SendToWinner(_)))
+                .count(),
+            1
+        );
+
+        logic.confirm_spin(1, "spinner", now).unwrap();
+        let actions = logic.tick(Timestamp::new_test_instance() + logic.raffle_interval(), 0);
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn auto_confirm_when_spinner_offline() {
+        let mut logic = logic_with_pending_spin();
+        logic.set_spinner_fallback(SpinnerFallback::AutoConfirm(Duration::from_secs(10)));
+        let drawn = Timestamp::new_test_instance() + logic.raffle_interval();
+
+        assert!(logic.tick(drawn + Duration::from_secs(9), 0).is_empty());
+
+        let actions = logic.tick(drawn + Duration::from_secs(10), 0);
+        assert!(matches!(actions.first(), Some(Action::Notify(_))));
+        assert!(matches!(actions.last(), Some(Action::SendToWinner(_))));
+    }
+
+    #[test]
+    fn no_fallback_while_spinner_spins() {
+        let mut logic = logic_with_pending_spin();
+        logic.set_spinner_fallback(SpinnerFallback::AutoConfirm(Duration::from_secs(10)));
+        let drawn = Timestamp::new_test_instance() + logic.raffle_interval();
+        for secs in [10, 60, 119] {
+            let now = drawn + Duration::from_secs(secs);
+            logic.ping(now, "spinner");
+            assert!(logic.tick(now, 0).is_empty());
+        }
+
+        // a connected spinner which never confirms doesn't block the payout
+        let now = drawn + CONNECTED_SPIN_TIMEOUT;
+        logic.ping(now, "spinner");
+        let actions = logic.tick(now, 0);
+        assert!(matches!(actions.last(), Some(Action::SendToWinner(_))));
+    }
+
+    #[test]
+    fn fallback_when_spinner_disconnects() {
+        let mut logic = logic_with_pending_spin();
+        logic.set_spinner_fallback(SpinnerFallback::AutoConfirm(Duration::from_secs(10)));
+        let drawn = Timestamp::new_test_instance() + logic.raffle_interval();
+        logic.ping(drawn + Duration::from_secs(1), "spinner");
+        logic
+            .claim_spin(1, "spinner", drawn + Duration::from_secs(1))
+            .unwrap();
+
+        assert!(logic.tick(drawn + Duration::from_secs(9), 0).is_empty());
+        let actions = logic.tick(drawn + Duration::from_secs(10), 0);
+        assert!(matches!(actions.last(), Some(Action::SendToWinner(_))));
+    }
+
+    #[test]
+    fn hold_and_alert_once() {
+        let mut logic = logic_with_pending_spin();
+        logic.set_spinner_fallback(SpinnerFallback::HoldAndAlert(Duration::from_secs(10)));
+        let drawn = Timestamp::new_test_instance() + logic.raffle_interval();
+
+        let actions = logic.tick(drawn + Duration::from_secs(10), 0);
+        assert!(matches!(actions.as_slice(), [Action::Notify(_)]));
+        assert!(logic.tick(drawn + Duration::from_secs(11), 0).is_empty());
+        assert!(logic.current_win().is_some());
+
+        logic.confirm_manually();
+        let actions = logic.tick(drawn + Duration::from_secs(12), 0);
+        assert!(matches!(actions.last(), Some(Action::SendToWinner(_))));
+    }
+
+    #[test]
+    fn ban_viewer() {
+        let mut logic = RaffleLogic::default();
+        let message = ChatMessage::new_test_instance_for_account(Account::from(42));
+        logic.handle_chat_message(message.clone());
+        logic.ban(&message.author_channel_id);
+        assert!(logic.participants().is_empty());
+
+        logic.handle_chat_message(message);
+        assert!(logic.participants().is_empty());
+    }
+
+    #[test]
+    fn retry_failed_payout() {
+        let mut logic = RaffleLogic::default();
+        let record = WinnerRecord {
+            block_hash: None,
+            ..WinnerRecord::new_test_instance()
+        };
+        logic.set_winners(vec![record.clone()], 1);
+        assert_eq!(logic.failed_payouts(), vec![&record]);
+
+        assert!(logic.retry_payout(record.raffle_id));
+        assert!(!logic.retry_payout(record.raffle_id));
+        let actions = logic.tick(Timestamp::new_test_instance(), 0);
+        assert_eq!(actions, vec![Action::SendToWinner((&record).into())]);
+
+        logic.payout_sent(record.raffle_id, "ABC".to_owned());
+        assert!(logic.failed_payouts().is_empty());
+        assert!(logic.winners().get(record.raffle_id).unwrap().paid());
+    }
+
+    #[test]
+    fn approve_held_payout() {
+        let mut logic = RaffleLogic::default();
+        logic.set_payout_limits(PayoutLimits {
+            max_payout: Some(Amount::nano(1)),
+            ..Default::default()
+        });
+        let record = WinnerRecord {
+            prize: Amount::nano(2),
+            ..WinnerRecord::new_test_instance()
+        };
+        let spent = Spending {
+            last_hour: Amount::raw(0),
+            last_day: Amount::raw(0),
+            stream: Amount::raw(0),
+            recipient: Amount::raw(0),
+        };
+        let winner: Winner = (&record).into();
+        assert!(matches!(
+            logic.check_payout(&winner, &spent),
+            PolicyDecision::Hold(_)
+        ));
+        logic.hold_payout(winner, "too much".to_owned());
+        assert_eq!(logic.held_payouts().len(), 1);
+
+        assert!(logic.approve_payout(record.raffle_id));
+        assert!(!logic.deny_payout(record.raffle_id));
+        assert!(logic.held_payouts().is_empty());
+        let actions = logic.tick(Timestamp::new_test_instance(), 0);
+        assert!(matches!(&actions[0], Action::AuditPayout(a) if a.decision == "approved"));
+        assert_eq!(actions[1], Action::SendToWinner((&record).into()));
+        assert_eq!(
+            logic.check_payout(&(&record).into(), &spent),
+            PolicyDecision::Allow
+        );
+    }
+
+    #[test]
+    fn scheduled_draw_with_special_prize() {
+        let mut logic = RaffleLogic::default();
+        logic.set_schedule(Schedule::parse("hourly prize 5").unwrap());
+        let start = Timestamp::new_test_instance();
+        let local =
+            NaiveDateTime::parse_from_str("2025-03-01 20:50:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        logic.sync_local_time(start, local);
+        logic.start();
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(1)));
+
+        assert_eq!(logic.countdown(start), Duration::from_secs(600));
+        logic.tick(start + Duration::from_secs(599), 0);
+        assert!(logic.current_win().is_none());
+        logic.tick(start + Duration::from_secs(600), 0);
+        assert_eq!(logic.current_win().unwrap().prize, Amount::nano(5));
+        assert_eq!(
+            logic.countdown(start + Duration::from_secs(600)),
+            Duration::from_secs(3600)
+        );
+    }
+
+    #[test]
+    fn stop_after_final_draw() {
+        let mut logic = RaffleLogic::default();
+        logic.set_schedule(Schedule::parse("final prize 10").unwrap());
+        logic.start();
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(1)));
+        let now = Timestamp::new_test_instance();
+        logic.end_stream(now);
+        logic.tick(now, 0);
+        assert_eq!(logic.current_win().unwrap().prize, Amount::nano(10));
+        assert!(logic.running());
+
+        logic.confirm_manually();
+        let actions = logic.tick(now, 0);
+        assert!(matches!(actions.last(), Some(Action::SendToWinner(_))));
+        assert!(!logic.running());
+    }
+
+    #[test]
+    fn end_stream_during_spin() {
+        let mut logic = logic_with_pending_spin();
+        logic.set_schedule(Schedule::parse("final prize 10").unwrap());
+        let now = Timestamp::new_test_instance() + logic.raffle_interval();
+        logic.end_stream(now);
+
+        // the running spin is rewarded, but the final draw is still to come
+        logic.confirm_manually();
+        let actions = logic.tick(now, 0);
+        assert!(matches!(actions.last(), Some(Action::SendToWinner(_))));
+        assert!(logic.running());
+
+        logic.tick(now, 0);
+        assert_eq!(logic.current_win().unwrap().prize, Amount::nano(10));
+        assert!(logic.running());
+
+        logic.confirm_manually();
+        let actions = logic.tick(now, 0);
+        assert!(matches!(actions.last(), Some(Action::SendToWinner(_))));
+        assert!(!logic.running());
+    }
+
+    #[test]
+    fn end_stream_without_final_draw_stops() {
+        let mut logic = RaffleLogic::default();
+        logic.start();
+        logic.end_stream(Timestamp::new_test_instance());
+        assert!(!logic.running());
+    }
+
+    #[test]
+    fn roll_prize_over_when_too_few_participants() {
+        let mut logic = RaffleLogic::default();
+        logic.set_draw_rules(DrawRules {
+            min_participants: 2,
+            when_not_met: ShortfallAction::RollOver,
+        });
+        logic.set_prize(Amount::nano(1));
+        logic.start();
+        let start = Timestamp::new_test_instance();
+        logic.tick(start, 0);
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(1)));
+        assert_eq!(logic.missing_participants(), 1);
+
+        let due = start + logic.raffle_interval();
+        let actions = logic.tick(due, 0);
+        assert!(matches!(&actions[..], [Action::Notify(_)]));
+        assert!(logic.current_win().is_none());
+        assert_eq!(logic.next_draw(due).1, Amount::nano(2));
+    }
+
+    #[test]
+    fn announce_postponement_once() {
+        let mut logic = RaffleLogic::default();
+        logic.set_draw_rules(DrawRules {
+            min_participants: 2,
+            when_not_met: ShortfallAction::Postpone,
+        });
+        logic.start();
+        let start = Timestamp::new_test_instance();
+        logic.tick(start, 0);
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(1)));
+        let due = start + logic.raffle_interval();
+        assert_eq!(logic.tick(due, 0).len(), 1);
+        assert!(logic.tick(due, 0).is_empty());
+        let announcements = logic
+            .take_events()
+            .into_iter()
+            .filter(|e| matches!(e, OverlayEvent::Announcement { .. }))
+            .count();
+        assert_eq!(announcements, 1);
+
+        logic.handle_chat_message(ChatMessage {
+            author_channel_id: "def".to_owned(),
+            ..ChatMessage::new_test_instance_for_account(Account::from(2))
+        });
+        logic.tick(due, 0);
+        assert!(logic.current_win().is_some());
+    }
+
+    #[test]
+    fn jackpot_is_drawn_when_threshold_reached() {
+        let mut logic = RaffleLogic::default();
+        logic.set_prize(Amount::nano(1));
+        logic.set_jackpot(JackpotConfig {
+            per_minute: Amount::raw(0),
+            per_participant: Amount::nano(1),
+            donations: true,
+            funding_accounts: Vec::new(),
+            threshold: Some(Amount::nano(5)),
+        });
+        logic.start();
+        let now = Timestamp::new_test_instance();
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(1)));
+        logic.donation_received(Account::from(2), Amount::nano(2));
+        logic.tick(now, 0);
+        assert!(logic.current_win().is_none());
+
+        logic.donation_received(Account::from(2), Amount::nano(1));
+        let actions = logic.tick(now, 0);
+        assert_eq!(logic.current_win().unwrap().prize, Amount::nano(5));
+        assert_eq!(logic.jackpot(), Some(Amount::raw(0)));
+        assert!(actions.contains(&Action::SaveJackpot(Amount::nano(4))));
+    }
+
+    #[test]
+    fn keep_jackpot_when_prize_rolls_over() {
+        let mut logic = RaffleLogic::default();
+        logic.set_prize(Amount::nano(1));
+        logic.set_draw_rules(DrawRules {
+            min_participants: 2,
+            when_not_met: ShortfallAction::RollOver,
+        });
+        logic.set_jackpot(JackpotConfig {
+            per_minute: Amount::raw(0),
+            per_participant: Amount::raw(0),
+            donations: false,
+            funding_accounts: Vec::new(),
+            threshold: None,
+        });
+        logic.restore_jackpot(Amount::nano(5));
+        logic.start();
+        let start = Timestamp::new_test_instance();
+        logic.tick(start, 0);
+
+        let due = start + logic.raffle_interval();
+        let actions = logic.tick(due, 0);
+        assert!(!actions.contains(&Action::SaveJackpot(Amount::raw(0))));
+        assert_eq!(logic.jackpot(), Some(Amount::nano(5)));
+        assert_eq!(logic.next_draw(due).1, Amount::nano(7));
+    }
+
+    #[test]
+    fn jackpot_does_not_exceed_balance() {
+        let mut logic = RaffleLogic::default();
+        logic.set_prize(Amount::nano(1));
+        logic.set_jackpot(JackpotConfig {
+            per_minute: Amount::raw(0),
+            per_participant: Amount::raw(0),
+            donations: true,
+            funding_accounts: Vec::new(),
+            threshold: None,
+        });
+        logic.donation_received(Account::from(2), Amount::nano(5));
+        logic.wallet_balance(Amount::nano(4));
+        assert_eq!(logic.jackpot(), Some(Amount::nano(3)));
+        let now = Timestamp::new_test_instance();
+        assert_eq!(logic.next_draw(now).1, Amount::nano(4));
+    }
+
+    #[test]
+    fn reveal_randomly_drawn_prize() {
+        let mut logic = RaffleLogic::default();
+        logic.set_prize_distribution(
+            PrizeDistribution::parse("weighted 2=1; bigwin 3 1/1").unwrap(),
+        );
+        logic.start();
+        let now = Timestamp::new_test_instance();
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(1)));
+        logic.tick(now, 0);
+        logic.tick(now + logic.raffle_interval(), 7);
+
+        let win = logic.current_win().unwrap();
+        assert_eq!(win.prize, Amount::nano(3));
+        assert_eq!(win.random, 7);
+        let reveal = win.spin_instruction().prize_reveal.unwrap();
+        assert!(reveal.big_win);
+        assert_eq!(reveal.teasers.len(), 2);
+    }
+
+    #[test]
+    fn extra_raffle_draws_from_eligible_entries() {
+        let mut logic = RaffleLogic::default();
+        logic.set_raffle_interval(Duration::from_secs(3600));
+        logic.add_raffle(twitch_raffle());
+        let start = Timestamp::new_test_instance();
+        let local =
+            NaiveDateTime::parse_from_str("2025-03-01 20:00:00", "%Y-%m-%d %H:%M:%S").unwrap();
+        logic.sync_local_time(start, local);
+        logic.start();
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(1)));
+        logic.tick(start, 0);
+
+        let due = start + Duration::from_secs(300);
+        assert!(matches!(&logic.tick(due, 0)[..], [Action::Notify(_)]));
+        assert!(logic.current_win().is_none());
+
+        logic.handle_chat_message(ChatMessage {
+            author_channel_id: "twitch-def".to_owned(),
+            platform: Platform::Twitch,
+            ..ChatMessage::new_test_instance_for_account(Account::from(2))
+        });
+        logic.tick(due, 0);
+        let win = logic.current_win().unwrap();
+        assert_eq!(win.raffle, "quick");
+        assert_eq!(win.prize, Amount::nano(2));
+        assert_eq!(win.winner.channel_id, "twitch-def");
+        let raffles = logic.raffles(due);
+        assert_eq!(raffles[0].participants, 2);
+        assert_eq!(raffles[1].participants, 1);
+    }
+
+    #[test]
+    fn loaded_participants_enter_extra_raffles() {
+        let mut logic = RaffleLogic::default();
+        logic.add_raffle(twitch_raffle());
+        let youtube = Participant::new_test_instance();
+        let twitch = Participant {
+            channel_id: "twitch-def".to_owned(),
+            platform: Platform::Twitch,
+            ..Participant::new_test_instance()
+        };
+        logic.set_participants(vec![youtube, twitch]);
+        let raffles = logic.raffles(Timestamp::new_test_instance());
+        assert_eq!(raffles[1].participants, 1);
+    }
+
+    fn twitch_raffle() -> RaffleConfig {
+        RaffleConfig {
+            name: "quick".to_owned(),
+            prize: Amount::nano(2),
+            schedule: Schedule::parse("every 5m").unwrap(),
+            rules: DrawRules::default(),
+            eligibility: Eligibility {
+                platform: Some(Platform::Twitch),
+                first_win_only: false,
+            },
+        }
+    }
+
+    #[test]
+    fn first_participant_with_correct_answer_wins_quiz() {
+        let mut logic = RaffleLogic::default();
+        let now = Timestamp::new_test_instance();
+        logic.handle_chat_message(ChatMessage::new_test_instance_for_account(Account::from(1)));
+        let quiz = Quiz::new(
+            "Who?",
+            "Colin",
+            Amount::nano(3),
+            Duration::from_secs(60),
+            now,
+        );
+        logic.start_quiz(quiz.unwrap(), now);
+
+        logic.handle_chat_message(ChatMessage {
+            message: "colin!".to_owned(),
+            author_channel_id: "unregistered".to_owned(),
+            ..ChatMessage::new_test_instance()
+        });
+        assert!(logic.quiz().is_some());
+
+        logic.handle_chat_message(ChatMessage {
+            message: "it's Colln".to_owned(),
+            ..ChatMessage::new_test_instance()
+        });
+        let actions = logic.tick(now + Duration::from_secs(5), 0);
+        assert!(logic.quiz().is_none());
+        assert!(matches!(&actions[0], Action::RecordRaffle(r) if r.raffle == "quiz"));
+        assert_eq!(
+            actions.last(),
+            Some(&Action::SendToWinner(Winner {
+                raffle_id: 1,
+                name: "John Doe".to_owned(),
+                channel_id: "abc".to_owned(),
+                platform: Platform::YouTube,
+                prize: Amount::nano(3),
+                account: Account::from(1),
+            }))
+        );
+    }
+
+    #[test]
+    fn quiz_times_out() {
+        let mut logic = RaffleLogic::default();
+        let now = Timestamp::new_test_instance();
+        let quiz = Quiz::new("", "nano", Amount::nano(1), Duration::from_secs(60), now);
+        logic.start_quiz(quiz.unwrap(), now);
+        assert!(logic.tick(now + Duration::from_secs(59), 0).is_empty());
+        let actions = logic.tick(now + Duration::from_secs(60), 0);
+        assert!(matches!(&actions[..], [Action::Notify(_)]));
+        assert!(logic.quiz().is_none());
+    }
+
+    #[test]
+    fn split_prediction_pool_among_correct_voters() {
+        let mut logic = RaffleLogic::default();
+        let viewer = |channel_id: &str, account: u64| ChatMessage {
+            author_channel_id: channel_id.to_owned(),
+            ..ChatMessage::new_test_instance_for_account(Account::from(account))
+        };
+        for (channel_id, account) in [("a", 1), ("b", 2), ("c", 3)] {
+            logic.handle_chat_message(viewer(channel_id, account));
+        }
+        let prediction = Prediction::new("Will the test pass?", "yes|no", Amount::nano(2));
+        logic.open_prediction(prediction.unwrap());
+
+        let vote = |channel_id: &str, message: &str| ChatMessage {
+            author_channel_id: channel_id.to_owned(),
+            message: message.to_owned(),
+            ..ChatMessage::new_test_instance()
+        };
+        logic.handle_chat_message(vote("a", "!vote yes"));
+        logic.handle_chat_message(vote("b", "!vote 2"));
+        logic.handle_chat_message(vote("c", "!vote 1"));
+        logic.handle_chat_message(vote("unregistered", "!vote 1"));
+        assert_eq!(logic.prediction().unwrap().vote_count(0), 2);
+
+        assert!(!logic.resolve_prediction(2));
+        assert!(logic.resolve_prediction(0));
+        assert!(logic.prediction().is_none());
+        let actions = logic.tick(Timestamp::new_test_instance(), 0);
+        assert!(actions.iter().all(|a| match a {
+            Action::RecordRaffle(result) => result.participants.len() == 1,
+            _ => true,
+        }));
+        let payouts: Vec<_> = actions
+            .into_iter()
+            .filter_map(|a| match a {
+                Action::SendToWinner(w) => Some((w.raffle_id, w.channel_id, w.prize)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            payouts,
+            vec![
+                (1, "a".to_owned(), Amount::nano(1)),
+                (2, "c".to_owned(), Amount::nano(1))
+            ]
+        );
+    }
 }
@@ -1,13 +1,39 @@
+mod addresses;
+mod admin_api;
+mod alias_resolver;
+mod amounts;
+mod assets;
 mod backend;
 mod chat_messages;
+mod chat_script;
+mod cli;
+mod database;
+mod draw_animation;
+mod draw_rules;
+mod dry_run;
+mod export;
 mod gui;
 mod http_server;
+mod jackpot;
+mod keystore;
 mod logic;
+mod metrics;
+mod named_raffles;
+mod overlay_events;
 mod participants;
 mod participants_file;
+mod payout_policy;
+mod prediction;
+mod prize_distribution;
 mod prize_sender;
+mod public_api;
+mod quiz;
 mod raffle_runner;
+mod schedule;
+mod spinner_fallback;
+mod theme;
 mod twitch_chat_listener;
+mod winners;
 mod youtube_chat_listener;
 
 use std::{
@@ -16,13 +42,25 @@ use std::{
     time::Duration,
 };
 
-use backend::run_backend;
+use backend::{run_backend, RunMode};
+use cli::{lock_database, run_command};
+use database::{unix_now, Database};
+use draw_animation::AnimationMode;
+use draw_rules::DrawRules;
+use dry_run::DryRunConfig;
 use gui::run_gui;
-use log::info;
+use jackpot::{JackpotConfig, JACKPOT_SETTING};
+use keystore::signer_from_env;
+use log::{error, info, warn};
 use logic::RaffleLogic;
+use named_raffles::RaffleConfig;
 use participants_file::ParticipantsFile;
-use rsnano_core::{Amount, PrivateKey};
+use payout_policy::PayoutLimits;
+use prize_distribution::PrizeDistribution;
+use rsnano_core::Amount;
 use rsnano_nullable_clock::SteadyClock;
+use schedule::{Schedule, SCHEDULE_SETTING};
+use spinner_fallback::SpinnerFallback;
 use tokio::sync::oneshot::{self};
 
 fn main() {
@@ -30,9 +68,37 @@ fn main() {
         env::set_var("RUST_LOG", "raffle_bot=debug")
     }
     env_logger::init();
-    let priv_key = std::env::var("NANO_PRV_KEY").expect("env var NANO_PRV_KEY not set!");
-    let priv_key = PrivateKey::from_hex_str(priv_key).unwrap();
-    info!("using account: {}", priv_key.account().encode_account());
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run_command(&args, DATABASE_PATH) {
+        Ok(true) => return,
+        Ok(false) => {}
+        Err(e) => {
+            error!("{:?}", e);
+            std::process::exit(1);
+        }
+    }
+
+    let dry_run = DryRunConfig::from_env().expect("invalid dry run config");
+    let mode = match dry_run {
+        Some(config) => {
+            warn!("DRY RUN: no prizes are paid out and nothing is sent to the network");
+            info!("using dry run config {:?}", config);
+            RunMode::DryRun(config)
+        }
+        None => {
+            let signer = signer_from_env().expect("could not load the private key");
+            info!("using account: {}", signer.account().encode_account());
+            RunMode::Live(signer)
+        }
+    };
+    let dry_run = matches!(mode, RunMode::DryRun(_));
+    let database_path = if dry_run {
+        DRY_RUN_DATABASE_PATH
+    } else {
+        DATABASE_PATH
+    };
+    // Held until the bot exits, so that nothing else changes the participants meanwhile
+    let _lock = lock_database(database_path).expect("could not lock database");
     let prize = std::env::var("NANO_PRIZE")
         .ok()
         .map(|s| Amount::decode_dec(s).unwrap());
@@ -48,15 +114,99 @@ fn main() {
         info!("using interval of {}s", interval);
         logic.set_raffle_interval(Duration::from_secs(interval));
     }
-    let mut participants_file = ParticipantsFile::default();
-    logic.set_participants(participants_file.load());
+    let fallback = SpinnerFallback::from_env().expect("invalid SPINNER_FALLBACK");
+    info!("using spinner fallback {:?}", fallback);
+    logic.set_spinner_fallback(fallback);
+    let animation = AnimationMode::from_env().expect("invalid RAFFLE_ANIMATION");
+    logic.set_animation(animation);
+    let rules = DrawRules::from_env().expect("invalid draw rules");
+    info!("using draw rules {:?}", rules);
+    logic.set_draw_rules(rules);
+    let distribution = PrizeDistribution::from_env().expect("invalid prize distribution");
+    info!("using prize distribution {}", distribution);
+    logic.set_prize_distribution(distribution);
+    for raffle in RaffleConfig::from_env().expect("invalid extra raffles") {
+        info!(
+            "using raffle {} with schedule {}",
+            raffle.name, raffle.schedule
+        );
+        logic.add_raffle(raffle);
+    }
+    let limits = PayoutLimits::from_env().expect("invalid payout limits");
+    info!("using payout limits {:?}", limits);
+    logic.set_payout_limits(limits);
+    // A dry run must not mix its fake raffles into the real history
+    let mut participants_file = if dry_run {
+        ParticipantsFile::new(DRY_RUN_PARTICIPANTS_PATH)
+    } else {
+        ParticipantsFile::default()
+    };
+    let participants = participants_file
+        .load()
+        .expect("could not load participants file");
+    logic.set_participants(participants);
+    let database = Database::open(database_path).expect("could not open database");
+    let stream_id = database
+        .start_stream(unix_now())
+        .expect("could not start stream session");
+    logic.set_winners(
+        database.winner_history().expect("could not load winners"),
+        stream_id,
+    );
+    logic.set_banned(database.bans().expect("could not load bans"));
+    if let Some(jackpot) = JackpotConfig::from_env().expect("invalid jackpot config") {
+        info!("using jackpot {:?}", jackpot);
+        logic.set_jackpot(jackpot);
+        if let Some(pot) = database
+            .setting(JACKPOT_SETTING)
+            .expect("could not load jackpot")
+        {
+            let pot = pot.parse().expect("invalid jackpot");
+            info!("restored jackpot of {}", Amount::raw(pot).format_balance(2));
+            logic.restore_jackpot(Amount::raw(pot));
+        }
+    }
+    if let Some(schedule) = database
+        .setting(SCHEDULE_SETTING)
+        .expect("could not load schedule")
+    {
+        let schedule = Schedule::parse(&schedule).expect("invalid schedule");
+        info!("using schedule {}", schedule);
+        logic.set_schedule(schedule);
+    }
+    logic.set_next_raffle_id(
+        database
+            .max_raffle_id()
+            .expect("could not load last raffle id")
+            + 1,
+    );
+    let database = Arc::new(Mutex::new(database));
     let logic = Arc::new(Mutex::new(logic));
     let clock = Arc::new(SteadyClock::default());
     let (tx_stop, rx_stop) = oneshot::channel::<()>();
 
     std::thread::scope(|s| {
-        s.spawn(|| run_backend(&logic, &clock, priv_key, participants_file, rx_stop));
-        run_gui(logic.clone(), clock.clone()).unwrap();
+        s.spawn(|| {
+            run_backend(
+                &logic,
+                &clock,
+                mode,
+                participants_file,
+                &database,
+                stream_id,
+                rx_stop,
+            )
+        });
+        run_gui(logic.clone(), clock.clone(), database.clone()).unwrap();
         tx_stop.send(()).unwrap();
-    })
+    });
+
+    let ended = database.lock().unwrap().end_stream(stream_id, unix_now());
+    if let Err(e) = ended {
+        warn!("Could not end stream session: {:?}", e);
+    }
 }
+
+const DATABASE_PATH: &str = "raffle.db";
+const DRY_RUN_DATABASE_PATH: &str = "raffle-dry-run.db";
+const DRY_RUN_PARTICIPANTS_PATH: &str = "participants-dry-run.json";
@@ -0,0 +1,432 @@
+use crate::{chat_messages::Platform, http_server::AppState};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use rsnano_core::Amount;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Counters for the `/metrics` endpoint and the state of the connections for
+/// `/healthz` and `/readyz`
+pub(crate) struct Metrics {
+    messages: Mutex<BTreeMap<&'static str, u64>>,
+    registrations: AtomicU64,
+    draws: AtomicU64,
+    payouts_sent: AtomicU64,
+    payouts_failed: AtomicU64,
+    payout_duration: Mutex<Histogram>,
+    pow_duration: Mutex<Histogram>,
+    wallet_balance: Mutex<Option<Amount>>,
+    rpc_up: AtomicBool,
+    /// When each chat source was last seen working. `None` if it never connected
+    chat_sources: Mutex<BTreeMap<&'static str, Option<Instant>>>,
+    last_tick: Mutex<Option<Instant>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            messages: Default::default(),
+            registrations: Default::default(),
+            draws: Default::default(),
+            payouts_sent: Default::default(),
+            payouts_failed: Default::default(),
+            payout_duration: Mutex::new(Histogram::new(PAYOUT_BUCKETS)),
+            pow_duration: Mutex::new(Histogram::new(POW_BUCKETS)),
+            wallet_balance: Default::default(),
+            rpc_up: Default::default(),
+            chat_sources: Default::default(),
+            last_tick: Default::default(),
+        }
+    }
+}
+
+/// Payouts wait at least 15s, so that the wheel has stopped
+const PAYOUT_BUCKETS: &[f64] = &[5.0, 10.0, 15.0, 20.0, 30.0, 60.0, 120.0, 300.0];
+const POW_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0, 60.0];
+
+/// A chat source is considered disconnected if it wasn't seen for this long.
+/// Twitch pings every 5 minutes even if the chat is quiet
+const CHAT_TIMEOUT: Duration = Duration::from_secs(6 * 60);
+/// The ticker runs every second
+const TICK_TIMEOUT: Duration = Duration::from_secs(30);
+
+impl Metrics {
+    pub fn chat_message(&self, platform: Platform) {
+        *self
+            .messages
+            .lock()
+            .unwrap()
+            .entry(platform.as_str())
+            .or_default() += 1;
+    }
+
+    pub fn registration(&self) {
+        self.registrations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn draw(&self) {
+        self.draws.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn payout(&self, sent: bool, duration: Duration) {
+        if sent {
+            self.payouts_sent.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.payouts_failed.fetch_add(1, Ordering::Relaxed);
+        }
+        self.payout_duration
+            .lock()
+            .unwrap()
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn pow_generated(&self, duration: Duration) {
+        self.pow_duration
+            .lock()
+            .unwrap()
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn set_wallet_balance(&self, balance: Amount) {
+        *self.wallet_balance.lock().unwrap() = Some(balance);
+    }
+
+    pub fn set_rpc_up(&self, up: bool) {
+        self.rpc_up.store(up, Ordering::Relaxed);
+    }
+
+    /// Registers a chat source, which is not ready until it is seen
+    pub fn chat_source_started(&self, platform: Platform) {
+        self.chat_sources
+            .lock()
+            .unwrap()
+            .entry(platform.as_str())
+            .or_default();
+    }
+
+    pub fn chat_source_seen(&self, platform: Platform, now: Instant) {
+        self.chat_sources
+            .lock()
+            .unwrap()
+            .insert(platform.as_str(), Some(now));
+    }
+
+    pub fn chat_source_lost(&self, platform: Platform) {
+        self.chat_sources
+            .lock()
+            .unwrap()
+            .insert(platform.as_str(), None);
+    }
+
+    pub fn ticked(&self, now: Instant) {
+        *self.last_tick.lock().unwrap() = Some(now);
+    }
+
+    pub fn health(&self, now: Instant) -> Health {
+        let recent = |seen: &Option<Instant>, timeout| {
+            seen.is_some_and(|s| now.saturating_duration_since(s) < timeout)
+        };
+        Health {
+            ticker: recent(&self.last_tick.lock().unwrap(), TICK_TIMEOUT),
+            rpc: self.rpc_up.load(Ordering::Relaxed),
+            chat: self
+                .chat_sources
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(name, seen)| (*name, recent(seen, CHAT_TIMEOUT)))
+                .collect(),
+        }
+    }
+
+    /// Renders all metrics in the Prometheus text format. The gauges which
+    /// live in the raffle logic are passed in
+    pub fn render(&self, gauges: &Gauges, now: Instant) -> String {
+        let mut out = String::new();
+        let counter = |out: &mut String, name: &str, help: &str| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} counter", name, help, name);
+        };
+        let gauge = |out: &mut String, name: &str, help: &str| {
+            let _ = writeln!(out, "# HELP {} {}\n# TYPE {} gauge", name, help, name);
+        };
+
+        counter(
+            &mut out,
+            "raffle_chat_messages_total",
+            "Chat messages received per platform",
+        );
+        for (platform, count) in self.messages.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "raffle_chat_messages_total{{platform=\"{}\"}} {}",
+                platform, count
+            );
+        }
+
+        counter(
+            &mut out,
+            "raffle_registrations_total",
+            "Viewer registrations",
+        );
+        let _ = writeln!(
+            out,
+            "raffle_registrations_total {}",
+            self.registrations.load(Ordering::Relaxed)
+        );
+
+        counter(&mut out, "raffle_draws_total", "Raffles that were drawn");
+        let _ = writeln!(
+            out,
+            "raffle_draws_total {}",
+            self.draws.load(Ordering::Relaxed)
+        );
+
+        counter(&mut out, "raffle_payouts_total", "Payouts by result");
+        let _ = writeln!(
+            out,
+            "raffle_payouts_total{{status=\"sent\"}} {}\nraffle_payouts_total{{status=\"failed\"}} {}",
+            self.payouts_sent.load(Ordering::Relaxed),
+            self.payouts_failed.load(Ordering::Relaxed)
+        );
+
+        self.payout_duration.lock().unwrap().render(
+            &mut out,
+            "raffle_payout_duration_seconds",
+            "Time from the start of a payout until it was sent or failed",
+        );
+        self.pow_duration.lock().unwrap().render(
+            &mut out,
+            "raffle_pow_duration_seconds",
+            "Time needed to generate the proof of work for a payout",
+        );
+
+        gauge(
+            &mut out,
+            "raffle_participants",
+            "Viewers registered for the next raffle",
+        );
+        let _ = writeln!(out, "raffle_participants {}", gauges.participants);
+
+        gauge(
+            &mut out,
+            "raffle_spinner_connected",
+            "Whether a spinner overlay is connected",
+        );
+        let _ = writeln!(
+            out,
+            "raffle_spinner_connected {}",
+            gauges.spinner_connected as u8
+        );
+
+        if let Some(balance) = *self.wallet_balance.lock().unwrap() {
+            gauge(
+                &mut out,
+                "raffle_wallet_balance_xno",
+                "Balance of the prize wallet",
+            );
+            let _ = writeln!(out, "raffle_wallet_balance_xno {}", xno(balance));
+        }
+
+        let health = self.health(now);
+        gauge(
+            &mut out,
+            "raffle_rpc_up",
+            "Whether the node RPC is reachable",
+        );
+        let _ = writeln!(out, "raffle_rpc_up {}", health.rpc as u8);
+        gauge(
+            &mut out,
+            "raffle_chat_source_up",
+            "Whether a chat source is connected",
+        );
+        for (platform, up) in &health.chat {
+            let _ = writeln!(
+                out,
+                "raffle_chat_source_up{{platform=\"{}\"}} {}",
+                platform, *up as u8
+            );
+        }
+        out
+    }
+}
+
+/// Converts the whole XNO separately, so that whole amounts are exact
+fn xno(amount: Amount) -> f64 {
+    let raw_per_xno = Amount::nano(1).number();
+    let whole = amount.number() / raw_per_xno;
+    let fraction = amount.number() % raw_per_xno;
+    whole as f64 + fraction as f64 / raw_per_xno as f64
+}
+
+/// Values that are read from the raffle logic when the metrics are scraped
+pub(crate) struct Gauges {
+    pub participants: usize,
+    pub spinner_connected: bool,
+}
+
+#[derive(Serialize)]
+pub(crate) struct Health {
+    /// Whether the backend loop is still running
+    pub ticker: bool,
+    pub rpc: bool,
+    pub chat: BTreeMap<&'static str, bool>,
+}
+
+impl Health {
+    fn live(&self) -> bool {
+        self.ticker
+    }
+
+    /// Ready means that viewers can register and winners can be paid
+    fn ready(&self) -> bool {
+        self.ticker && self.rpc && !self.chat.is_empty() && self.chat.values().all(|up| *up)
+    }
+}
+
+struct Histogram {
+    bounds: &'static [f64],
+    /// Cumulative count per bound
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Self {
+            bounds,
+            buckets: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    fn observe(&mut self, value: f64) {
+        for (bound, bucket) in self.bounds.iter().zip(self.buckets.iter_mut()) {
+            if value <= *bound {
+                *bucket += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}\n# TYPE {} histogram", name, help, name);
+        for (bound, bucket) in self.bounds.iter().zip(&self.buckets) {
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, bucket);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+pub(crate) fn metrics_routes() -> Router<AppState> {
+    Router::new()
+        .route("/metrics", get(get_metrics))
+        .route("/healthz", get(get_healthz))
+        .route("/readyz", get(get_readyz))
+}
+
+async fn get_metrics(State(state): State<AppState>) -> Response {
+    let gauges = {
+        let guard = state.logic.lock().unwrap();
+        Gauges {
+            participants: guard.participants().len(),
+            spinner_connected: guard.spinner_connected(state.clock.now()),
+        }
+    };
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&gauges, Instant::now()),
+    )
+        .into_response()
+}
+
+/// Fails if the backend is stuck
+async fn get_healthz(State(state): State<AppState>) -> Response {
+    let health = state.metrics.health(Instant::now());
+    health_response(health.live(), health)
+}
+
+/// Fails if a chat source or the node RPC is not connected
+async fn get_readyz(State(state): State<AppState>) -> Response {
+    let health = state.metrics.health(Instant::now());
+    health_response(health.ready(), health)
+}
+
+fn health_response(ok: bool, health: Health) -> Response {
+    let status = if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(health)).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn histogram_buckets_are_cumulative() {
+        let mut histogram = Histogram::new(&[1.0, 5.0]);
+        histogram.observe(0.5);
+        histogram.observe(3.0);
+        histogram.observe(10.0);
+        let mut out = String::new();
+        histogram.render(&mut out, "test", "help");
+        assert!(out.contains("test_bucket{le=\"1\"} 1\n"));
+        assert!(out.contains("test_bucket{le=\"5\"} 2\n"));
+        assert!(out.contains("test_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("test_sum 13.5\n"));
+    }
+
+    #[test]
+    fn render_counters() {
+        let metrics = Metrics::default();
+        metrics.chat_message(Platform::Twitch);
+        metrics.chat_message(Platform::Twitch);
+        metrics.payout(false, Duration::from_secs(20));
+        metrics.set_wallet_balance(Amount::nano(3));
+        let gauges = Gauges {
+            participants: 4,
+            spinner_connected: true,
+        };
+        let out = metrics.render(&gauges, Instant::now());
+        assert!(out.contains("raffle_chat_messages_total{platform=\"twitch\"} 2\n"));
+        assert!(out.contains("raffle_payouts_total{status=\"failed\"} 1\n"));
+        assert!(out.contains("raffle_participants 4\n"));
+        assert!(out.contains("raffle_spinner_connected 1\n"));
+        assert!(out.contains("raffle_wallet_balance_xno 3\n"));
+    }
+
+    #[test]
+    fn ready_when_everything_is_connected() {
+        let metrics = Metrics::default();
+        let now = Instant::now();
+        metrics.ticked(now);
+        metrics.chat_source_started(Platform::Twitch);
+        assert!(metrics.health(now).live());
+        assert!(!metrics.health(now).ready());
+
+        metrics.chat_source_seen(Platform::Twitch, now);
+        metrics.set_rpc_up(true);
+        assert!(metrics.health(now).ready());
+        assert!(!metrics.health(now + CHAT_TIMEOUT).chat["twitch"]);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::{
+    amounts::parse_xno,
+    chat_messages::Platform,
+    draw_rules::DrawRules,
+    participants::{Participant, ParticipantRegistry},
+    raffle_runner::RaffleRunner,
+    schedule::Schedule,
+    winners::WinnerHistory,
+};
+use anyhow::{anyhow, bail};
+use rsnano_core::Amount;
+use serde::Serialize;
+
+/// Name of the raffle that is configured by the other `RAFFLE_*` variables
+pub(crate) const MAIN_RAFFLE: &str = "main";
+
+/// Who may enter a raffle
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Eligibility {
+    /// Only viewers of this platform may enter
+    pub platform: Option<Platform>,
+    /// Viewers who already won in this stream can't win again
+    pub first_win_only: bool,
+}
+
+impl Eligibility {
+    pub(crate) fn allows(&self, participant: &Participant, winners: &WinnerHistory) -> bool {
+        if self.platform.is_some_and(|p| p != participant.platform) {
+            return false;
+        }
+        !self.first_win_only
+            || !winners
+                .in_current_stream()
+                .any(|w| w.channel_id == participant.channel_id)
+    }
+}
+
+/// A raffle that runs next to the main raffle with its own entries, rules and schedule
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RaffleConfig {
+    pub name: String,
+    pub prize: Amount,
+    pub schedule: Schedule,
+    pub rules: DrawRules,
+    pub eligibility: Eligibility,
+}
+
+impl RaffleConfig {
+    /// Reads the raffles listed in `RAFFLE_EXTRA`, e.g. `quick,big`. Each raffle is
+    /// configured by `RAFFLE_<NAME>_SCHEDULE` (required), `RAFFLE_<NAME>_PRIZE` (XNO),
+    /// `RAFFLE_<NAME>_MIN_PARTICIPANTS`, `RAFFLE_<NAME>_PLATFORM` and
+    /// `RAFFLE_<NAME>_FIRST_WIN_ONLY`
+    pub(crate) fn from_env() -> anyhow::Result<Vec<Self>> {
+        let Ok(names) = std::env::var("RAFFLE_EXTRA") else {
+            return Ok(Vec::new());
+        };
+        let configs = names
+            .split(',')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .map(|name| Self::from_vars(name, |var| std::env::var(var).ok()))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for (i, config) in configs.iter().enumerate() {
+            if configs[..i].iter().any(|c| c.name == config.name) {
+                bail!("raffle {} is configured twice", config.name);
+            }
+        }
+        Ok(configs)
+    }
+
+    fn from_vars(name: &str, var: impl Fn(&str) -> Option<String>) -> anyhow::Result<Self> {
+        if name == MAIN_RAFFLE || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            bail!("invalid raffle name: {}", name);
+        }
+        let prefix = format!("RAFFLE_{}_", name.to_ascii_uppercase());
+        let var = |key: &str| var(&format!("{}{}", prefix, key));
+
+        let schedule = var("SCHEDULE").ok_or_else(|| anyhow!("{}SCHEDULE is missing", prefix))?;
+        let schedule = Schedule::parse(&schedule)?;
+        if schedule.is_empty() {
+            bail!("{}SCHEDULE is empty", prefix);
+        }
+        let prize = match var("PRIZE") {
+            Some(prize) => parse_xno(&prize).map_err(|_| anyhow!("invalid prize: {}", prize))?,
+            None => Amount::nano(1),
+        };
+        let mut rules = DrawRules::default();
+        if let Some(min) = var("MIN_PARTICIPANTS") {
+            rules.min_participants = min
+                .parse()
+                .map_err(|_| anyhow!("invalid {}MIN_PARTICIPANTS: {}", prefix, min))?;
+        }
+        let platform = var("PLATFORM")
+            .map(|p| Platform::parse(&p).ok_or_else(|| anyhow!("invalid platform: {}", p)))
+            .transpose()?;
+        let first_win_only = matches!(var("FIRST_WIN_ONLY").as_deref(), Some("1" | "on" | "true"));
+
+        Ok(Self {
+            name: name.to_owned(),
+            prize,
+            schedule,
+            rules,
+            eligibility: Eligibility {
+                platform,
+                first_win_only,
+            },
+        })
+    }
+}
+
+/// An additional raffle. Its entries are the registered viewers it is eligible for
+pub(crate) struct NamedRaffle {
+    pub runner: RaffleRunner,
+    pub eligibility: Eligibility,
+}
+
+impl NamedRaffle {
+    pub(crate) fn new(config: RaffleConfig) -> Self {
+        let mut runner = RaffleRunner::default();
+        runner.set_name(config.name);
+        runner.set_prize(config.prize);
+        runner.set_schedule(config.schedule);
+        runner.set_draw_rules(config.rules);
+        Self {
+            runner,
+            eligibility: config.eligibility,
+        }
+    }
+
+    /// The registered viewers that may win right now
+    pub(crate) fn eligible(
+        &self,
+        participants: &ParticipantRegistry,
+        winners: &WinnerHistory,
+    ) -> ParticipantRegistry {
+        let mut eligible = ParticipantRegistry::default();
+        eligible.set(
+            participants
+                .list()
+                .into_iter()
+                .filter(|p| self.eligibility.allows(p, winners))
+                .collect(),
+        );
+        eligible
+    }
+}
+
+/// State of a raffle for the operator
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct RaffleOverview {
+    pub name: String,
+    pub participants: usize,
+    /// `None` while the raffles are stopped
+    pub countdown_secs: Option<u64>,
+    pub next_prize: Amount,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::winners::WinnerRecord;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> anyhow::Result<RaffleConfig> {
+        let vars: HashMap<_, _> = vars.iter().cloned().collect();
+        RaffleConfig::from_vars("quick", |var| vars.get(var).map(|v| v.to_string()))
+    }
+
+    #[test]
+    fn parse_config() {
+        let config = config(&[
+            ("RAFFLE_QUICK_SCHEDULE", "every 5m"),
+            ("RAFFLE_QUICK_PRIZE", "0.1"),
+            ("RAFFLE_QUICK_MIN_PARTICIPANTS", "3"),
+            ("RAFFLE_QUICK_PLATFORM", "twitch"),
+            ("RAFFLE_QUICK_FIRST_WIN_ONLY", "on"),
+        ])
+        .unwrap();
+        assert_eq!(config.name, "quick");
+        assert_eq!(config.prize, parse_xno("0.1").unwrap());
+        assert_eq!(config.schedule.to_string(), "every 5m");
+        assert_eq!(config.rules.min_participants, 3);
+        assert_eq!(
+            config.eligibility,
+            Eligibility {
+                platform: Some(Platform::Twitch),
+                first_win_only: true
+            }
+        );
+    }
+
+    #[test]
+    fn parse_invalid_config() {
+        assert!(config(&[]).is_err());
+        assert!(config(&[("RAFFLE_QUICK_SCHEDULE", "sometimes")]).is_err());
+        assert!(RaffleConfig::from_vars("main", |_| Some("hourly".to_owned())).is_err());
+        assert!(RaffleConfig::from_vars("a-b", |_| Some("hourly".to_owned())).is_err());
+    }
+
+    #[test]
+    fn first_win_only() {
+        let eligibility = Eligibility {
+            platform: None,
+            first_win_only: true,
+        };
+        let viewer = Participant::new_test_instance();
+        let mut winners = WinnerHistory::default();
+        winners.set_current_stream(1);
+        assert!(eligibility.allows(&viewer, &winners));
+
+        winners.add(WinnerRecord {
+            stream_id: 1,
+            channel_id: viewer.channel_id.clone(),
+            ..WinnerRecord::new_test_instance()
+        });
+        assert!(!eligibility.allows(&viewer, &winners));
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{chat_messages::Platform, draw_animation::AnimationMode};
+use serde::Serialize;
+
+/// Events that are pushed to the browser overlays
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub(crate) enum OverlayEvent {
+    /// Play the draw animation so that it stops at the winner
+    Spin(SpinInstruction),
+    Countdown {
+        seconds: u64,
+        running: bool,
+        /// Viewers that still have to enter before the raffle can be drawn
+        missing_participants: usize,
+        /// Prize of the next raffle, which grows in the jackpot mode
+        prize: String,
+    },
+    Registration {
+        name: String,
+    },
+    Winner {
+        name: String,
+        prize: String,
+    },
+    Chat {
+        name: String,
+        message: String,
+        platform: Platform,
+    },
+    /// Explains the viewers why a raffle was postponed or skipped
+    Announcement {
+        message: String,
+    },
+    /// Sent every second, so that clients can detect a dead connection
+    Heartbeat,
+}
+
+impl OverlayEvent {
+    /// The event for clients without the spinner token if names aren't public.
+    /// Names are replaced and chat messages are dropped
+    pub(crate) fn without_names(self) -> Option<Self> {
+        match self {
+            OverlayEvent::Spin(mut spin) => {
+                spin.participants.fill(HIDDEN_NAME.to_owned());
+                Some(OverlayEvent::Spin(spin))
+            }
+            OverlayEvent::Registration { .. } => Some(OverlayEvent::Registration {
+                name: HIDDEN_NAME.to_owned(),
+            }),
+            OverlayEvent::Winner { prize, .. } => Some(OverlayEvent::Winner {
+                name: HIDDEN_NAME.to_owned(),
+                prize,
+            }),
+            OverlayEvent::Chat { .. } => None,
+            event => Some(event),
+        }
+    }
+}
+
+const HIDDEN_NAME: &str = "***";
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct SpinInstruction {
+    /// Must be sent back when the spin is confirmed
+    pub raffle_id: u64,
+    /// Name of the raffle that is drawn
+    pub raffle: String,
+    pub animation: AnimationMode,
+    /// The names that are shown. Large pools are sampled, but always include the winner
+    pub participants: Vec<String>,
+    /// Index of the winner in `participants`
+    pub winner: usize,
+    /// Number of participants before sampling
+    pub total_participants: usize,
+    /// Revealed after the animation, if the prize was drawn at random
+    pub prize_reveal: Option<PrizeReveal>,
+}
+
+/// The mystery box that opens after the winner was shown
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct PrizeReveal {
+    /// Amounts the box flickers through before it opens
+    pub teasers: Vec<String>,
+    pub prize: String,
+    pub big_win: bool,
+}
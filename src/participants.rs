@@ -1,3 +1,4 @@
+use crate::chat_messages::Platform;
 use rsnano_core::Account;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -7,6 +8,7 @@ pub(crate) struct Participant {
     pub channel_id: String,
     pub name: String,
     pub account: Account,
+    pub platform: Platform,
 }
 
 impl Participant {
@@ -15,6 +17,7 @@ impl Participant {
             channel_id: "abc".to_owned(),
             name: "John Doe".to_owned(),
             account: Account::from(42),
+            platform: Platform::YouTube,
         }
     }
 
@@ -24,6 +27,7 @@ impl Participant {
             name: format!("name for {}", channel_id),
             channel_id,
             account: Account::from(42),
+            platform: Platform::YouTube,
         }
     }
 }
@@ -32,14 +36,30 @@ impl Participant {
 pub(crate) struct ParticipantRegistry(HashMap<String, Participant>);
 
 impl ParticipantRegistry {
-    pub fn add(&mut self, participant: Participant) {
-        self.0.insert(participant.channel_id.clone(), participant);
+    /// Adds or replaces a participant. Returns true if the participant is new
+    pub fn add(&mut self, participant: Participant) -> bool {
+        self.0
+            .insert(participant.channel_id.clone(), participant)
+            .is_none()
+    }
+
+    /// Returns true if the participant was registered
+    pub fn remove(&mut self, channel_id: &str) -> bool {
+        self.0.remove(channel_id).is_some()
+    }
+
+    pub fn get(&self, channel_id: &str) -> Option<&Participant> {
+        self.0.get(channel_id)
     }
 
     pub fn len(&self) -> usize {
         self.0.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
     pub fn list(&self) -> Vec<Participant> {
         let mut result: Vec<_> = self.0.values().cloned().collect();
         result.sort_by(|a, b| a.channel_id.cmp(&b.channel_id));
@@ -105,12 +125,21 @@ mod tests {
             account: Account::from(99999),
             ..old.clone()
         };
-        participants.add(old);
-        participants.add(new.clone());
+        assert!(participants.add(old));
+        assert!(!participants.add(new.clone()));
         assert_eq!(participants.len(), 1);
         assert_eq!(participants.list(), vec![new]);
     }
 
+    #[test]
+    fn remove() {
+        let mut participants = ParticipantRegistry::default();
+        participants.add(Participant::new_test_instance_for_channel("a"));
+        assert!(participants.remove("a"));
+        assert!(!participants.remove("a"));
+        assert_eq!(participants.len(), 0);
+    }
+
     #[test]
     fn return_participants_ordered_by_channel_id() {
         let mut participants = ParticipantRegistry::default();
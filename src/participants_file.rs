@@ -1,22 +1,55 @@
-use crate::participants::Participant;
+use crate::{chat_messages::Platform, participants::Participant};
+use anyhow::{anyhow, bail, Context};
 use log::{info, warn};
-use std::collections::HashSet;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
-#[derive(Default)]
 pub(crate) struct ParticipantsFile {
+    path: PathBuf,
+    backups: usize,
     last_saved: HashSet<Participant>,
 }
 
+impl Default for ParticipantsFile {
+    fn default() -> Self {
+        Self::new(FILE_PATH)
+    }
+}
+
+/// The on-disk format of the participants file
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    version: u32,
+    participants: Vec<Participant>,
+}
+
 impl ParticipantsFile {
-    pub(crate) fn load(&mut self) -> Vec<Participant> {
-        let participants = load_participants();
+    pub(crate) fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            backups: DEFAULT_BACKUPS,
+            last_saved: HashSet::new(),
+        }
+    }
+
+    /// Loads the participants. A missing file means there are no participants yet,
+    /// but a file that can't be read or parsed is an error.
+    pub(crate) fn load(&mut self) -> anyhow::Result<Vec<Participant>> {
+        let participants = load_participants(&self.path)?;
         for p in &participants {
             self.last_saved.insert(p.clone());
         }
-        participants
+        Ok(participants)
     }
 
-    pub(crate) fn update(&mut self, participants: Vec<Participant>) {
+    /// Saves the participants if they changed. Returns true if there was a change
+    pub(crate) fn update(&mut self, participants: Vec<Participant>) -> bool {
         let participants: HashSet<Participant> = HashSet::from_iter(participants);
         if self
             .last_saved
@@ -25,36 +58,236 @@ impl ParticipantsFile {
             .is_none()
         {
             // nothing changed
-            return;
+            return false;
         }
 
         self.last_saved = participants;
 
-        match std::fs::write(
-            FILE_PATH,
-            serde_json::to_string_pretty(&self.last_saved).unwrap(),
-        ) {
+        match self.write() {
             Ok(_) => {
                 info!("Participants file written")
             }
             Err(e) => warn!("Could not save participants file: {:?}", e),
         }
+        true
     }
+
+    fn write(&self) -> anyhow::Result<()> {
+        let mut participants: Vec<_> = self.last_saved.iter().cloned().collect();
+        participants.sort_by(|a, b| a.channel_id.cmp(&b.channel_id));
+        let envelope = Envelope {
+            version: CURRENT_VERSION,
+            participants,
+        };
+        let json = serde_json::to_string_pretty(&envelope)?;
+
+        // Write to a temp file first, so that a crash never leaves a half written file behind
+        let tmp_path = self.path.with_extension("json.tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        drop(file);
+
+        self.rotate_backups()?;
+        std::fs::rename(&tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    fn rotate_backups(&self) -> anyhow::Result<()> {
+        if self.backups == 0 || !self.path.exists() {
+            return Ok(());
+        }
+
+        for i in (1..self.backups).rev() {
+            let from = self.backup_path(i);
+            if from.exists() {
+                std::fs::rename(&from, self.backup_path(i + 1))?;
+            }
+        }
+        std::fs::copy(&self.path, self.backup_path(1))?;
+        Ok(())
+    }
+
+    fn backup_path(&self, index: usize) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".bak.{}", index));
+        path.into()
+    }
+}
+
+fn load_participants(path: &Path) -> anyhow::Result<Vec<Participant>> {
+    if !path.exists() {
+        warn!(
+            "{} not found. Starting without participants",
+            path.display()
+        );
+        return Ok(Vec::new());
+    }
+
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Could not read {}", path.display()))?;
+
+    parse_participants(&json).with_context(|| {
+        format!(
+            "{} is corrupt. Fix it or restore one of the backups ({}.bak.*)",
+            path.display(),
+            path.display()
+        )
+    })
 }
 
-fn load_participants() -> Vec<Participant> {
-    let Ok(json) = std::fs::read_to_string(FILE_PATH) else {
-        warn!("Could not read participants.json");
-        return Vec::new();
-    };
+fn parse_participants(json: &str) -> anyhow::Result<Vec<Participant>> {
+    let mut value: Value = serde_json::from_str(json)?;
+    let mut version = file_version(&value)?;
+
+    if version > CURRENT_VERSION {
+        bail!(
+            "file version {} is newer than the supported version {}",
+            version,
+            CURRENT_VERSION
+        );
+    }
+
+    while version < CURRENT_VERSION {
+        value = migrate(version, value)?;
+        version += 1;
+        info!("Migrated participants file to version {}", version);
+    }
 
-    match serde_json::from_str(&json) {
-        Ok(participants) => participants,
-        Err(e) => {
-            warn!("Could not deserialize participants file: {:?}", e);
-            Vec::new()
+    let envelope: Envelope = serde_json::from_value(value)?;
+    Ok(envelope.participants)
+}
+
+fn file_version(value: &Value) -> anyhow::Result<u32> {
+    match value {
+        // The first version was a plain list of participants without an envelope
+        Value::Array(_) => Ok(1),
+        Value::Object(obj) => obj
+            .get("version")
+            .and_then(Value::as_u64)
+            .map(|v| v as u32)
+            .ok_or_else(|| anyhow!("version field missing")),
+        _ => bail!("unexpected file format"),
+    }
+}
+
+/// Migrates the file content from the given version to the next version
+fn migrate(from_version: u32, value: Value) -> anyhow::Result<Value> {
+    match from_version {
+        1 => Ok(serde_json::json!({
+            "version": 2,
+            "participants": value,
+        })),
+        2 => {
+            let mut value = value;
+            value["version"] = 3.into();
+            let participants = value["participants"]
+                .as_array_mut()
+                .ok_or_else(|| anyhow!("participants missing"))?;
+            for p in participants {
+                if p.get("platform").is_none() {
+                    let channel_id = p["channel_id"].as_str().unwrap_or_default();
+                    p["platform"] = Platform::from_channel_id(channel_id).as_str().into();
+                }
+            }
+            Ok(value)
         }
+        _ => bail!("no migration for version {}", from_version),
     }
 }
 
 const FILE_PATH: &str = "participants.json";
+const CURRENT_VERSION: u32 = 3;
+const DEFAULT_BACKUPS: usize = 5;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file() {
+        let path = test_path("missing");
+        let mut file = ParticipantsFile::new(&path);
+        assert_eq!(file.load().unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn write_and_load() {
+        let path = test_path("write_and_load");
+        let participants = vec![
+            Participant::new_test_instance_for_channel("a"),
+            Participant::new_test_instance_for_channel("b"),
+        ];
+
+        ParticipantsFile::new(&path).update(participants.clone());
+
+        assert_eq!(ParticipantsFile::new(&path).load().unwrap(), participants);
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn migrate_version_1() {
+        let participants = vec![Participant::new_test_instance()];
+        let json = serde_json::to_string(&participants).unwrap();
+        assert_eq!(parse_participants(&json).unwrap(), participants);
+    }
+
+    #[test]
+    fn migrate_version_2() {
+        let json = r#"{
+            "version": 2,
+            "participants": [{
+                "channel_id": "twitch-bob",
+                "name": "bob",
+                "account": "nano_37391u1nrr1j7tdn8w9zathoio5suz9bar18jksqheeiy4obwz3pkgp9aqz6"
+            }]
+        }"#;
+        let participants = parse_participants(json).unwrap();
+        assert_eq!(participants[0].platform, Platform::Twitch);
+    }
+
+    #[test]
+    fn fail_on_corrupt_file() {
+        let path = test_path("corrupt");
+        std::fs::write(&path, "[{\"channel_id\": ").unwrap();
+        assert!(ParticipantsFile::new(&path).load().is_err());
+    }
+
+    #[test]
+    fn fail_on_newer_version() {
+        assert!(parse_participants("{\"version\": 999, \"participants\": []}").is_err());
+    }
+
+    #[test]
+    fn rotate_backups() {
+        let path = test_path("backups");
+        let mut file = ParticipantsFile::new(&path);
+        file.backups = 2;
+        for channel in ["a", "b", "c", "d"] {
+            let mut participants = file.last_saved.iter().cloned().collect::<Vec<_>>();
+            participants.push(Participant::new_test_instance_for_channel(channel));
+            file.update(participants);
+        }
+
+        assert_eq!(
+            parse_participants(&std::fs::read_to_string(file.backup_path(1)).unwrap())
+                .unwrap()
+                .len(),
+            3
+        );
+        assert_eq!(
+            parse_participants(&std::fs::read_to_string(file.backup_path(2)).unwrap())
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(!file.backup_path(3).exists());
+    }
+
+    fn test_path(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raffle-bot-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir.join("participants.json")
+    }
+}
@@ -0,0 +1,233 @@
+use crate::amounts::parse_xno;
+use anyhow::anyhow;
+use rsnano_core::{Account, Amount};
+use serde::Serialize;
+
+/// Guardrails for the prize payouts. Every limit is optional
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PayoutLimits {
+    pub max_payout: Option<Amount>,
+    pub max_per_hour: Option<Amount>,
+    pub max_per_day: Option<Amount>,
+    /// Total a single account may receive during a stream
+    pub max_per_recipient: Option<Amount>,
+    /// Hard cap for the whole stream, which not even the operator can override
+    pub max_per_stream: Option<Amount>,
+    pub when_exceeded: LimitAction,
+}
+
+/// What happens to a payout that exceeds one of the soft limits
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) enum LimitAction {
+    /// Wait until the operator approves or denies the payout
+    #[default]
+    Hold,
+    Reject,
+}
+
+/// Amounts that were already paid out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Spending {
+    pub last_hour: Amount,
+    pub last_day: Amount,
+    pub stream: Amount,
+    /// Paid to the same account during this stream
+    pub recipient: Amount,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum PolicyDecision {
+    Allow,
+    Hold(String),
+    Reject(String),
+}
+
+impl PayoutLimits {
+    /// Reads the limits in XNO from `RAFFLE_MAX_PAYOUT`, `RAFFLE_MAX_PER_HOUR`,
+    /// `RAFFLE_MAX_PER_DAY`, `RAFFLE_MAX_PER_RECIPIENT` and `RAFFLE_MAX_PER_STREAM`.
+    /// `RAFFLE_LIMIT_ACTION` is either `hold` (default) or `reject`
+    pub fn from_env() -> anyhow::Result<Self> {
+        let when_exceeded = match std::env::var("RAFFLE_LIMIT_ACTION").as_deref() {
+            Err(_) | Ok("hold") => LimitAction::Hold,
+            Ok("reject") => LimitAction::Reject,
+            Ok(other) => return Err(anyhow!("invalid RAFFLE_LIMIT_ACTION: {}", other)),
+        };
+        Ok(Self {
+            max_payout: amount_from_env("RAFFLE_MAX_PAYOUT")?,
+            max_per_hour: amount_from_env("RAFFLE_MAX_PER_HOUR")?,
+            max_per_day: amount_from_env("RAFFLE_MAX_PER_DAY")?,
+            max_per_recipient: amount_from_env("RAFFLE_MAX_PER_RECIPIENT")?,
+            max_per_stream: amount_from_env("RAFFLE_MAX_PER_STREAM")?,
+            when_exceeded,
+        })
+    }
+
+    /// Payouts that were approved by the operator only have to respect the stream cap
+    pub fn check(&self, amount: Amount, spent: &Spending, approved: bool) -> PolicyDecision {
+        if exceeds(spent.stream, amount, self.max_per_stream) {
+            return PolicyDecision::Reject(format!(
+                "stream cap of {} XNO reached",
+                format_xno(self.max_per_stream)
+            ));
+        }
+        if approved {
+            return PolicyDecision::Allow;
+        }
+
+        let violation = if exceeds(Amount::raw(0), amount, self.max_payout) {
+            Some(format!(
+                "prize exceeds the maximum payout of {} XNO",
+                format_xno(self.max_payout)
+            ))
+        } else if exceeds(spent.last_hour, amount, self.max_per_hour) {
+            Some(format!(
+                "hourly budget of {} XNO exceeded",
+                format_xno(self.max_per_hour)
+            ))
+        } else if exceeds(spent.last_day, amount, self.max_per_day) {
+            Some(format!(
+                "daily budget of {} XNO exceeded",
+                format_xno(self.max_per_day)
+            ))
+        } else if exceeds(spent.recipient, amount, self.max_per_recipient) {
+            Some(format!(
+                "recipient cap of {} XNO exceeded",
+                format_xno(self.max_per_recipient)
+            ))
+        } else {
+            None
+        };
+
+        match (violation, self.when_exceeded) {
+            (None, _) => PolicyDecision::Allow,
+            (Some(reason), LimitAction::Hold) => PolicyDecision::Hold(reason),
+            (Some(reason), LimitAction::Reject) => PolicyDecision::Reject(reason),
+        }
+    }
+}
+
+fn exceeds(spent: Amount, amount: Amount, limit: Option<Amount>) -> bool {
+    limit.is_some_and(|limit| spent.number().saturating_add(amount.number()) > limit.number())
+}
+
+fn format_xno(amount: Option<Amount>) -> String {
+    amount.map(|a| a.format_balance(2)).unwrap_or_default()
+}
+
+fn amount_from_env(var: &str) -> anyhow::Result<Option<Amount>> {
+    match std::env::var(var) {
+        Ok(value) => parse_xno(&value)
+            .map(Some)
+            .map_err(|_| anyhow!("invalid {}: {}", var, value)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// An entry of the audit trail, which records every decision about a payout
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct PayoutAudit {
+    pub raffle_id: u64,
+    pub account: Account,
+    pub amount: Amount,
+    /// `allowed`, `held`, `rejected`, `approved` or `denied`
+    pub decision: &'static str,
+    pub reason: String,
+}
+
+impl PayoutAudit {
+    pub fn new(
+        raffle_id: u64,
+        account: Account,
+        amount: Amount,
+        decision: &PolicyDecision,
+    ) -> Self {
+        let (decision, reason) = match decision {
+            PolicyDecision::Allow => ("allowed", String::new()),
+            PolicyDecision::Hold(reason) => ("held", reason.clone()),
+            PolicyDecision::Reject(reason) => ("rejected", reason.clone()),
+        };
+        Self {
+            raffle_id,
+            account,
+            amount,
+            decision,
+            reason,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nothing_spent() -> Spending {
+        Spending {
+            last_hour: Amount::raw(0),
+            last_day: Amount::raw(0),
+            stream: Amount::raw(0),
+            recipient: Amount::raw(0),
+        }
+    }
+
+    #[test]
+    fn no_limits() {
+        let limits = PayoutLimits::default();
+        assert_eq!(
+            limits.check(Amount::nano(1000), &nothing_spent(), false),
+            PolicyDecision::Allow
+        );
+    }
+
+    #[test]
+    fn hold_when_budget_exceeded() {
+        let limits = PayoutLimits {
+            max_per_hour: Some(Amount::nano(2)),
+            ..Default::default()
+        };
+        let spent = Spending {
+            last_hour: Amount::nano(1),
+            ..nothing_spent()
+        };
+        assert_eq!(
+            limits.check(Amount::nano(1), &spent, false),
+            PolicyDecision::Allow
+        );
+        assert!(matches!(
+            limits.check(Amount::nano(2), &spent, false),
+            PolicyDecision::Hold(_)
+        ));
+        assert_eq!(
+            limits.check(Amount::nano(2), &spent, true),
+            PolicyDecision::Allow
+        );
+    }
+
+    #[test]
+    fn reject_when_configured() {
+        let limits = PayoutLimits {
+            max_payout: Some(Amount::nano(1)),
+            when_exceeded: LimitAction::Reject,
+            ..Default::default()
+        };
+        assert!(matches!(
+            limits.check(Amount::nano(2), &nothing_spent(), false),
+            PolicyDecision::Reject(_)
+        ));
+    }
+
+    #[test]
+    fn stream_cap_cant_be_approved() {
+        let limits = PayoutLimits {
+            max_per_stream: Some(Amount::nano(5)),
+            ..Default::default()
+        };
+        let spent = Spending {
+            stream: Amount::nano(5),
+            ..nothing_spent()
+        };
+        assert!(matches!(
+            limits.check(Amount::nano(1), &spent, true),
+            PolicyDecision::Reject(_)
+        ));
+    }
+}
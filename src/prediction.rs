@@ -0,0 +1,169 @@
+use anyhow::bail;
+use rsnano_core::Amount;
+use std::collections::HashMap;
+
+/// Name under which the payouts of a prediction are recorded
+pub(crate) const PREDICTION_RAFFLE: &str = "prediction";
+
+/// A question with several options. Registered viewers vote in chat and the
+/// pool is split among the viewers who voted for the right option
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Prediction {
+    pub question: String,
+    pub options: Vec<String>,
+    pub pool: Amount,
+    /// Option index by channel id
+    votes: HashMap<String, usize>,
+    open: bool,
+}
+
+impl Prediction {
+    /// The options are separated by `|`
+    pub(crate) fn new(question: &str, options: &str, pool: Amount) -> anyhow::Result<Self> {
+        let options: Vec<String> = options
+            .split('|')
+            .map(str::trim)
+            .filter(|o| !o.is_empty())
+            .map(str::to_owned)
+            .collect();
+        if options.len() < 2 {
+            bail!("a prediction needs at least two options");
+        }
+        if pool.number() == 0 {
+            bail!("the prize pool is empty");
+        }
+        Ok(Self {
+            question: question.trim().to_owned(),
+            options,
+            pool,
+            votes: HashMap::new(),
+            open: true,
+        })
+    }
+
+    /// Tells the viewers how to vote
+    pub(crate) fn announcement(&self) -> String {
+        let options: Vec<String> = self
+            .options
+            .iter()
+            .enumerate()
+            .map(|(i, option)| format!("!vote {} ({})", i + 1, option))
+            .collect();
+        format!(
+            "Prediction for Ӿ {}: {} Vote with {}",
+            self.pool.format_balance(2),
+            self.question,
+            options.join(", ")
+        )
+    }
+
+    pub(crate) fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// No more votes are accepted
+    pub(crate) fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Records a chat message like `!vote 2` or `!vote yes`. A viewer may change
+    /// the vote while the prediction is open. Returns false if the message isn't a vote
+    pub(crate) fn vote(&mut self, channel_id: &str, message: &str) -> bool {
+        if !self.open {
+            return false;
+        }
+        let Some(option) = self.parse_vote(message) else {
+            return false;
+        };
+        self.votes.insert(channel_id.to_owned(), option);
+        true
+    }
+
+    fn parse_vote(&self, message: &str) -> Option<usize> {
+        let (command, choice) = message.trim().split_once(' ')?;
+        if !command.eq_ignore_ascii_case("!vote") {
+            return None;
+        }
+        let choice = choice.trim();
+        match choice.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).filter(|i| *i < self.options.len()),
+            Err(_) => self
+                .options
+                .iter()
+                .position(|o| o.eq_ignore_ascii_case(choice)),
+        }
+    }
+
+    pub(crate) fn vote_count(&self, option: usize) -> usize {
+        self.votes.values().filter(|v| **v == option).count()
+    }
+
+    /// Channel ids of all voters, ordered like the participant list
+    pub(crate) fn voters(&self) -> Vec<&str> {
+        let mut voters: Vec<&str> = self.votes.keys().map(String::as_str).collect();
+        voters.sort();
+        voters
+    }
+
+    pub(crate) fn voted_for(&self, channel_id: &str) -> Option<usize> {
+        self.votes.get(channel_id).copied()
+    }
+
+    /// What each correct voter receives. The remainder of the division stays in the wallet
+    pub(crate) fn share(&self, correct_voters: usize) -> Option<Amount> {
+        if correct_voters == 0 {
+            return None;
+        }
+        Some(Amount::raw(self.pool.number() / correct_voters as u128))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prediction() -> Prediction {
+        Prediction::new("Will the test pass?", "yes | no", Amount::nano(1)).unwrap()
+    }
+
+    #[test]
+    fn vote_by_number_or_name() {
+        let mut prediction = prediction();
+        assert!(prediction.vote("a", "!vote 1"));
+        assert!(prediction.vote("b", "!VOTE No"));
+        assert!(!prediction.vote("c", "!vote 3"));
+        assert!(!prediction.vote("c", "I vote yes"));
+        assert_eq!(prediction.vote_count(0), 1);
+        assert_eq!(prediction.voted_for("b"), Some(1));
+        assert_eq!(prediction.voters(), vec!["a", "b"]);
+    }
+
+    #[test]
+    fn change_vote_until_closed() {
+        let mut prediction = prediction();
+        prediction.vote("a", "!vote yes");
+        prediction.vote("a", "!vote no");
+        assert_eq!(prediction.voted_for("a"), Some(1));
+
+        prediction.close();
+        assert!(!prediction.vote("a", "!vote yes"));
+        assert_eq!(prediction.voted_for("a"), Some(1));
+    }
+
+    #[test]
+    fn split_pool() {
+        let prediction = prediction();
+        assert_eq!(prediction.share(0), None);
+        assert_eq!(prediction.share(1), Some(Amount::nano(1)));
+        assert_eq!(
+            prediction.share(3),
+            Some(Amount::raw(Amount::nano(1).number() / 3))
+        );
+    }
+
+    #[test]
+    fn invalid_prediction() {
+        assert!(Prediction::new("?", "yes", Amount::nano(1)).is_err());
+        assert!(Prediction::new("?", "yes|no", Amount::raw(0)).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use crate::{amounts::parse_xno, draw_animation::SplitMix64};
+use anyhow::{anyhow, bail};
+use rsnano_core::Amount;
+use std::fmt::Display;
+
+/// Draws the prize at random instead of paying the fixed prize
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PrizeDistribution {
+    kind: DistributionKind,
+    big_win: Option<BigWin>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+enum DistributionKind {
+    /// The fixed prize of the raffle
+    #[default]
+    Fixed,
+    Uniform {
+        min: Amount,
+        max: Amount,
+    },
+    /// Amounts with their relative weights
+    Weighted(Vec<(Amount, u32)>),
+}
+
+/// A rare prize that replaces the normal one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BigWin {
+    prize: Amount,
+    one_in: u32,
+}
+
+/// A prize that was drawn from the distribution
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct DrawnPrize {
+    pub amount: Amount,
+    pub big_win: bool,
+}
+
+impl PrizeDistribution {
+    /// Reads `RAFFLE_PRIZE_DISTRIBUTION`
+    pub(crate) fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("RAFFLE_PRIZE_DISTRIBUTION") {
+            Ok(s) => Self::parse(&s),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Parses parts separated by `;`: `fixed`, `uniform 0.1-1`,
+    /// `weighted 0.1=70, 0.5=25, 2=5` and `bigwin 10 1/100`
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        let mut distribution = Self::default();
+        for part in s.split(';').map(str::trim).filter(|p| !p.is_empty()) {
+            let (name, args) = part.split_once(' ').unwrap_or((part, ""));
+            let args = args.trim();
+            match name {
+                "fixed" if args.is_empty() => distribution.kind = DistributionKind::Fixed,
+                "uniform" => {
+                    let (min, max) = args
+                        .split_once('-')
+                        .ok_or_else(|| anyhow!("invalid range: {}", args))?;
+                    let (min, max) = (parse_amount(min)?, parse_amount(max)?);
+                    if min > max {
+                        bail!("invalid range: {}", args);
+                    }
+                    distribution.kind = DistributionKind::Uniform { min, max };
+                }
+                "weighted" => {
+                    let table = args
+                        .split(',')
+                        .map(|entry| {
+                            let (amount, weight) = entry
+                                .split_once('=')
+                                .ok_or_else(|| anyhow!("invalid weighted prize: {}", entry))?;
+                            let weight = weight
+                                .trim()
+                                .parse()
+                                .map_err(|_| anyhow!("invalid weight: {}", weight))?;
+                            Ok((parse_amount(amount)?, weight))
+                        })
+                        .collect::<anyhow::Result<Vec<(Amount, u32)>>>()?;
+                    if table.iter().all(|(_, weight)| *weight == 0) {
+                        bail!("weighted prizes need at least one weight above 0");
+                    }
+                    distribution.kind = DistributionKind::Weighted(table);
+                }
+                "bigwin" => {
+                    let (prize, chance) = args
+                        .split_once(' ')
+                        .ok_or_else(|| anyhow!("invalid big win: {}", args))?;
+                    let one_in = chance
+                        .trim()
+                        .strip_prefix("1/")
+                        .and_then(|n| n.parse().ok())
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| anyhow!("invalid big win chance: {}", chance))?;
+                    distribution.big_win = Some(BigWin {
+                        prize: parse_amount(prize)?,
+                        one_in,
+                    });
+                }
+                _ => bail!("invalid prize distribution: {}", part),
+            }
+        }
+        Ok(distribution)
+    }
+
+    pub(crate) fn is_fixed(&self) -> bool {
+        self.kind == DistributionKind::Fixed && self.big_win.is_none()
+    }
+
+    /// Draws the prize. The same random value that picked the winner is
+    /// used, so the prize can be verified from the recorded raffle
+    pub(crate) fn draw(&self, fixed: Amount, random: u32, raffle_id: u64) -> DrawnPrize {
+        let mut rng = SplitMix64(u64::from(random) ^ raffle_id.rotate_left(32));
+        if let Some(big_win) = self.big_win {
+            if rng.next().is_multiple_of(u64::from(big_win.one_in)) {
+                return DrawnPrize {
+                    amount: big_win.prize,
+                    big_win: true,
+                };
+            }
+        }
+
+        let amount = match &self.kind {
+            DistributionKind::Fixed => fixed,
+            DistributionKind::Uniform { min, max } => {
+                let span = max.number() - min.number();
+                let random = (u128::from(rng.next()) << 64) | u128::from(rng.next());
+                let offset = if span == u128::MAX {
+                    random
+                } else {
+                    random % (span + 1)
+                };
+                Amount::raw(min.number() + offset)
+            }
+            DistributionKind::Weighted(table) => {
+                let total: u64 = table.iter().map(|(_, w)| u64::from(*w)).sum();
+                let mut pick = rng.next() % total;
+                table
+                    .iter()
+                    .find(|(_, weight)| {
+                        let found = pick < u64::from(*weight);
+                        pick = pick.saturating_sub(u64::from(*weight));
+                        found
+                    })
+                    .map_or(fixed, |(amount, _)| *amount)
+            }
+        };
+        DrawnPrize {
+            amount,
+            big_win: false,
+        }
+    }
+
+    /// Amounts the overlay shows before it reveals the prize
+    pub(crate) fn teasers(&self, fixed: Amount) -> Vec<Amount> {
+        let mut teasers = match &self.kind {
+            DistributionKind::Fixed => vec![fixed],
+            DistributionKind::Uniform { min, max } => {
+                let mid = Amount::raw(min.number() + (max.number() - min.number()) / 2);
+                vec![*min, mid, *max]
+            }
+            DistributionKind::Weighted(table) => table.iter().map(|(amount, _)| *amount).collect(),
+        };
+        teasers.extend(self.big_win.map(|b| b.prize));
+        teasers
+    }
+}
+
+fn parse_amount(s: &str) -> anyhow::Result<Amount> {
+    let s = s.trim();
+    parse_xno(s).map_err(|_| anyhow!("invalid amount: {}", s))
+}
+
+impl Display for PrizeDistribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            DistributionKind::Fixed => write!(f, "fixed")?,
+            DistributionKind::Uniform { min, max } => write!(
+                f,
+                "uniform {}-{}",
+                min.format_balance(2),
+                max.format_balance(2)
+            )?,
+            DistributionKind::Weighted(table) => {
+                write!(f, "weighted ")?;
+                for (i, (amount, weight)) in table.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}={}", amount.format_balance(2), weight)?;
+                }
+            }
+        }
+        if let Some(big_win) = self.big_win {
+            write!(
+                f,
+                "; bigwin {} 1/{}",
+                big_win.prize.format_balance(2),
+                big_win.one_in
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let distribution =
+            PrizeDistribution::parse("weighted 0.1=70, 2=30; bigwin 10 1/100").unwrap();
+        assert_eq!(
+            distribution.kind,
+            DistributionKind::Weighted(vec![
+                (parse_xno("0.1").unwrap(), 70),
+                (Amount::nano(2), 30)
+            ])
+        );
+        assert_eq!(
+            distribution.big_win,
+            Some(BigWin {
+                prize: Amount::nano(10),
+                one_in: 100
+            })
+        );
+        assert!(PrizeDistribution::parse("fixed").unwrap().is_fixed());
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(PrizeDistribution::parse("uniform 2-1").is_err());
+        assert!(PrizeDistribution::parse("weighted 1=0").is_err());
+        assert!(PrizeDistribution::parse("bigwin 10 1/0").is_err());
+        assert!(PrizeDistribution::parse("lottery").is_err());
+    }
+
+    #[test]
+    fn uniform_stays_in_range() {
+        let distribution = PrizeDistribution::parse("uniform 1-2").unwrap();
+        for random in 0..100 {
+            let prize = distribution.draw(Amount::nano(5), random, 1);
+            assert!(prize.amount >= Amount::nano(1) && prize.amount <= Amount::nano(2));
+            assert!(!prize.big_win);
+        }
+    }
+
+    #[test]
+    fn draw_is_reproducible() {
+        let distribution = PrizeDistribution::parse("weighted 1=1, 2=1, 3=1").unwrap();
+        let prizes: Vec<_> = (0..50)
+            .map(|random| distribution.draw(Amount::nano(5), random, 7))
+            .collect();
+        for (random, prize) in prizes.iter().enumerate() {
+            assert_eq!(distribution.draw(Amount::nano(5), random as u32, 7), *prize);
+        }
+        assert!(prizes.iter().any(|p| p.amount == Amount::nano(1)));
+        assert!(prizes.iter().any(|p| p.amount == Amount::nano(3)));
+    }
+
+    #[test]
+    fn big_win_always_with_chance_one() {
+        let distribution = PrizeDistribution::parse("bigwin 10 1/1").unwrap();
+        assert_eq!(
+            distribution.draw(Amount::nano(1), 42, 1),
+            DrawnPrize {
+                amount: Amount::nano(10),
+                big_win: true
+            }
+        );
+    }
+}
@@ -1,45 +1,94 @@
 use std::{
+    collections::HashSet,
     sync::Arc,
     time::{Duration, Instant},
 };
 
-use anyhow::anyhow;
-use log::info;
-use rsnano_core::{Account, Amount, Block, PrivateKey, StateBlockArgs};
+use anyhow::{anyhow, bail};
+use log::{info, warn};
+
+use crate::{dry_run::SimulatedSender, keystore::Signer, metrics::Metrics};
+use rsnano_core::{Account, Amount, Block, BlockHash};
 use rsnano_rpc_client::NanoRpcClient;
 use rsnano_rpc_messages::{AccountInfoArgs, BlockSubTypeDto, ProcessArgs};
 use rsnano_work::WorkPool;
+use serde_json::{json, Value};
 use tokio::{task::spawn_blocking, time::sleep};
 
 const MIN_DELAY: Duration = Duration::from_secs(15);
+const RPC_URL: &str = "http://[::1]:7076";
 
-pub(crate) struct PrizeSender {
-    sender_key: PrivateKey,
-    work_pool: Arc<WorkPool>,
+/// Sends the prizes, either through the node or simulated in a dry run
+pub(crate) enum PrizeSender {
+    Node(NodeSender),
+    Simulated(SimulatedSender),
 }
 
 impl PrizeSender {
-    pub(crate) fn new(sender_key: PrivateKey) -> Self {
-        let work_pool = WorkPool::builder().gpu_only().finish();
-        Self {
-            sender_key,
-            work_pool: work_pool.into(),
+    /// Sends the prize and returns the hash of the send block
+    pub(crate) async fn send_prize(
+        &self,
+        destination: Account,
+        prize: Amount,
+    ) -> anyhow::Result<BlockHash> {
+        match self {
+            Self::Node(sender) => sender.send_prize(destination, prize).await,
+            Self::Simulated(sender) => sender.send_prize(destination, prize).await,
         }
     }
 
-    pub(crate) async fn send_prize(
+    /// Looks for a send of the prize since the given unix time whose hash isn't
+    /// one of the recorded payouts. Such a send was published, but the bot
+    /// didn't learn about it, for example because it crashed while sending
+    pub(crate) async fn find_unrecorded_send(
         &self,
         destination: Account,
         prize: Amount,
-    ) -> anyhow::Result<()> {
-        let rpc = NanoRpcClient::new("http://[::1]:7076".parse()?);
+        since: u64,
+        recorded: &HashSet<String>,
+    ) -> anyhow::Result<Option<String>> {
+        match self {
+            Self::Node(sender) => {
+                let history = account_history(sender.signer.account()).await?;
+                Ok(find_send(&history, destination, prize, since, recorded))
+            }
+            Self::Simulated(_) => Ok(None),
+        }
+    }
+}
+
+/// Signs the send blocks and publishes them through the node RPC
+pub(crate) struct NodeSender {
+    signer: Signer,
+    work_pool: Arc<WorkPool>,
+    metrics: Arc<Metrics>,
+}
+
+impl NodeSender {
+    pub(crate) fn new(signer: Signer, metrics: Arc<Metrics>) -> Self {
+        let work_pool = WorkPool::builder().gpu_only().finish();
+        Self {
+            signer,
+            work_pool: work_pool.into(),
+            metrics,
+        }
+    }
+
+    async fn send_prize(&self, destination: Account, prize: Amount) -> anyhow::Result<BlockHash> {
+        let rpc = NanoRpcClient::new(RPC_URL.parse()?);
         let info = rpc
             .account_info(
-                AccountInfoArgs::build(self.sender_key.account())
+                AccountInfoArgs::build(self.signer.account())
                     .include_representative()
                     .finish(),
             )
             .await?;
+        if info.balance < prize {
+            bail!(
+                "insufficient balance: {} XNO",
+                info.balance.format_balance(2)
+            );
+        }
 
         let start = Instant::now();
         let work_pool = self.work_pool.clone();
@@ -52,28 +101,271 @@ impl PrizeSender {
         .await?;
 
         let elapsed = start.elapsed();
+        self.metrics.pow_generated(elapsed);
         if elapsed < MIN_DELAY {
             sleep(MIN_DELAY - elapsed).await;
         }
 
-        let block: Block = StateBlockArgs {
-            key: &self.sender_key,
-            previous: info.frontier,
-            representative: info
-                .representative
-                .ok_or_else(|| anyhow!("no rep field!"))?
-                .into(),
-            balance: info.balance - prize,
-            link: destination.into(),
+        let representative = info
+            .representative
+            .ok_or_else(|| anyhow!("no rep field!"))?
+            .into();
+        let block: Block = self.signer.send_block(
+            info.frontier,
+            representative,
+            info.balance - prize,
+            destination,
             work,
-        }
-        .into();
+        );
 
+        let hash = block.hash();
         let args = ProcessArgs::build(block.json_representation())
             .subtype(BlockSubTypeDto::Send)
             .finish();
 
         rpc.process(args).await?;
-        Ok(())
+        self.metrics.set_wallet_balance(info.balance - prize);
+        Ok(hash)
+    }
+}
+
+/// Checks every 30s whether the node RPC is reachable and reports the wallet balance.
+/// New receivable blocks of the wallet are reported as donations
+pub(crate) async fn monitor_wallet<F, B>(
+    account: Account,
+    on_received: F,
+    on_balance: B,
+    metrics: Arc<Metrics>,
+) where
+    F: Fn(Account, Amount) + Send + Sync,
+    B: Fn(Amount) + Send + Sync,
+{
+    let mut donations = DonationTracker::default();
+    loop {
+        match wallet_balance(account).await {
+            Ok(balance) => {
+                metrics.set_rpc_up(true);
+                metrics.set_wallet_balance(balance);
+                on_balance(balance);
+            }
+            Err(e) => {
+                warn!("Node RPC not reachable: {:?}", e);
+                metrics.set_rpc_up(false);
+            }
+        }
+        match receivable_blocks(account).await {
+            Ok(blocks) => {
+                for block in donations.update(blocks) {
+                    info!(
+                        "Received donation of {} from {}",
+                        block.amount.format_balance(2),
+                        block.source.encode_account()
+                    );
+                    on_received(block.source, block.amount);
+                }
+            }
+            Err(e) => warn!("Could not check receivable blocks: {:?}", e),
+        }
+        sleep(Duration::from_secs(30)).await;
+    }
+}
+
+async fn wallet_balance(account: Account) -> anyhow::Result<Amount> {
+    let rpc = NanoRpcClient::new(RPC_URL.parse()?);
+    let info = rpc
+        .account_info(AccountInfoArgs::build(account).finish())
+        .await?;
+    Ok(info.balance)
+}
+
+/// A send to the bot's wallet which wasn't received yet
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Receivable {
+    hash: String,
+    source: Account,
+    amount: Amount,
+}
+
+/// The bot never receives, so every donation stays receivable. Blocks are
+/// told apart by hash, so payouts and top ups of the balance don't matter
+#[derive(Default)]
+struct DonationTracker {
+    /// The receivable blocks of the last poll. None until the first poll, so
+    /// blocks from before a restart aren't counted twice
+    known: Option<HashSet<String>>,
+}
+
+impl DonationTracker {
+    /// Returns the blocks which weren't receivable during the last poll
+    fn update(&mut self, blocks: Vec<Receivable>) -> Vec<Receivable> {
+        let new = match &self.known {
+            Some(known) => blocks
+                .iter()
+                .filter(|b| !known.contains(&b.hash))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        self.known = Some(blocks.into_iter().map(|b| b.hash).collect());
+        new
+    }
+}
+
+async fn receivable_blocks(account: Account) -> anyhow::Result<Vec<Receivable>> {
+    let response: Value = reqwest::Client::new()
+        .post(RPC_URL)
+        .json(&json!({
+            "action": "receivable",
+            "account": account.encode_account(),
+            "count": RECEIVABLE_COUNT.to_string(),
+            "source": "true",
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    parse_receivable(&response)
+}
+
+fn parse_receivable(response: &Value) -> anyhow::Result<Vec<Receivable>> {
+    if let Some(error) = response["error"].as_str() {
+        bail!("RPC error: {}", error);
+    }
+    // The node returns an empty string instead of an empty object
+    let Some(blocks) = response["blocks"].as_object() else {
+        return Ok(Vec::new());
+    };
+    blocks
+        .iter()
+        .map(|(hash, block)| {
+            let amount = block["amount"]
+                .as_str()
+                .and_then(|a| a.parse::<u128>().ok())
+                .ok_or_else(|| anyhow!("invalid amount in block {}", hash))?;
+            let source = block["source"]
+                .as_str()
+                .and_then(|s| Account::decode_account(s).ok())
+                .ok_or_else(|| anyhow!("invalid source in block {}", hash))?;
+            Ok(Receivable {
+                hash: hash.clone(),
+                source,
+                amount: Amount::raw(amount),
+            })
+        })
+        .collect()
+}
+
+async fn account_history(account: Account) -> anyhow::Result<Value> {
+    let response: Value = reqwest::Client::new()
+        .post(RPC_URL)
+        .json(&json!({
+            "action": "account_history",
+            "account": account.encode_account(),
+            "count": HISTORY_COUNT.to_string(),
+        }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    if let Some(error) = response["error"].as_str() {
+        bail!("RPC error: {}", error);
+    }
+    Ok(response)
+}
+
+fn find_send(
+    history: &Value,
+    destination: Account,
+    prize: Amount,
+    since: u64,
+    recorded: &HashSet<String>,
+) -> Option<String> {
+    let destination = destination.encode_account();
+    let prize = prize.number().to_string();
+    history["history"].as_array()?.iter().find_map(|block| {
+        let timestamp = block["local_timestamp"].as_str()?.parse::<u64>().ok()?;
+        let hash = block["hash"].as_str()?;
+        let matches = block["type"].as_str() == Some("send")
+            && block["account"].as_str() == Some(destination.as_str())
+            && block["amount"].as_str() == Some(prize.as_str())
+            && timestamp >= since
+            && !recorded.contains(hash);
+        matches.then(|| hash.to_owned())
+    })
+}
+
+const RECEIVABLE_COUNT: usize = 1000;
+const HISTORY_COUNT: usize = 100;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "nano_37391u1nrr1j7tdn8w9zathoio5suz9bar18jksqheeiy4obwz3pkgp9aqz6";
+
+    fn receivable(hash: &str) -> Receivable {
+        Receivable {
+            hash: hash.to_owned(),
+            source: Account::decode_account(SOURCE).unwrap(),
+            amount: Amount::nano(1),
+        }
+    }
+
+    #[test]
+    fn parse_receivable_blocks() {
+        let response = json!({
+            "blocks": {
+                "AB12": { "amount": "1000000000000000000000000000000", "source": SOURCE }
+            }
+        });
+        assert_eq!(
+            parse_receivable(&response).unwrap(),
+            vec![receivable("AB12")]
+        );
+        assert_eq!(parse_receivable(&json!({ "blocks": "" })).unwrap(), vec![]);
+        assert!(parse_receivable(&json!({ "error": "Bad account number" })).is_err());
+    }
+
+    #[test]
+    fn find_unrecorded_send() {
+        let destination = Account::decode_account(SOURCE).unwrap();
+        let send = |hash: &str, timestamp: &str| {
+            json!({
+                "type": "send",
+                "account": SOURCE,
+                "amount": Amount::nano(1).number().to_string(),
+                "local_timestamp": timestamp,
+                "hash": hash,
+            })
+        };
+        let history = json!({ "history": [send("NEW", "2000"), send("OLD", "900")] });
+        let prize = Amount::nano(1);
+        let none = HashSet::new();
+        assert_eq!(
+            find_send(&history, destination, prize, 1000, &none),
+            Some("NEW".to_owned())
+        );
+        let recorded = HashSet::from(["NEW".to_owned()]);
+        assert_eq!(
+            find_send(&history, destination, prize, 1000, &recorded),
+            None
+        );
+        assert_eq!(
+            find_send(&history, destination, Amount::nano(2), 0, &none),
+            None
+        );
+    }
+
+    #[test]
+    fn only_new_blocks_are_donations() {
+        let mut tracker = DonationTracker::default();
+        assert_eq!(tracker.update(vec![receivable("A")]), vec![]);
+        assert_eq!(
+            tracker.update(vec![receivable("A"), receivable("B")]),
+            vec![receivable("B")]
+        );
+        assert_eq!(tracker.update(vec![receivable("B")]), vec![]);
     }
 }
@@ -0,0 +1,263 @@
+use crate::{
+    database::EntryRow, http_server::AppState, participants::Participant, winners::WinnerRecord,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use log::warn;
+use rsnano_core::Account;
+use serde::{Deserialize, Serialize};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Read-only routes for websites and community tools. Accounts are masked and
+/// names are only included if `public_names` is enabled
+pub(crate) fn public_routes(cors: Option<CorsLayer>) -> Router<AppState> {
+    let router = Router::new()
+        .route("/api/status", get(get_status))
+        .route("/api/participants", get(get_participants))
+        .route("/api/winners", get(get_winners))
+        .route("/api/raffles/{id}", get(get_raffle));
+    match cors {
+        Some(cors) => router.layer(cors),
+        None => router,
+    }
+}
+
+/// Allows browsers to call the API from other sites. `origins` is either `*`
+/// or a comma separated list like `https://a.example,https://b.example`
+pub(crate) fn cors_layer(origins: &str) -> CorsLayer {
+    let allow_origin = if origins.trim() == "*" {
+        AllowOrigin::any()
+    } else {
+        let origins: Vec<HeaderValue> = origins
+            .split(',')
+            .map(str::trim)
+            .filter(|o| !o.is_empty())
+            .filter_map(|o| match o.parse() {
+                Ok(origin) => Some(origin),
+                Err(_) => {
+                    warn!("Ignoring invalid CORS origin: {}", o);
+                    None
+                }
+            })
+            .collect();
+        AllowOrigin::list(origins)
+    };
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET])
+}
+
+/// Adds a `Cache-Control` header, so that a busy website doesn't hit the bot on every page view
+fn cached(max_age_secs: u64, body: impl Serialize) -> Response {
+    (
+        [(
+            header::CACHE_CONTROL,
+            format!("public, max-age={}", max_age_secs),
+        )],
+        Json(body),
+    )
+        .into_response()
+}
+
+/// Shows only the start and the end of an account, which is enough for a viewer
+/// to recognize their own address
+fn mask_account(account: &Account) -> String {
+    let encoded = account.encode_account();
+    match encoded.split_once('_') {
+        Some((prefix, rest)) if rest.len() > 10 => {
+            format!("{}_{}...{}", prefix, &rest[..5], &rest[rest.len() - 5..])
+        }
+        _ => encoded,
+    }
+}
+
+#[derive(Serialize)]
+struct Status {
+    running: bool,
+    countdown_secs: u64,
+    prize_xno: String,
+    participants: usize,
+    /// Id of the raffle which is currently spinning
+    current_raffle: Option<u64>,
+}
+
+async fn get_status(State(state): State<AppState>) -> Response {
+    let mut guard = state.logic.lock().unwrap();
+    let now = state.clock.now();
+    let status = Status {
+        running: guard.running(),
+        countdown_secs: guard.countdown(now).as_secs(),
+        prize_xno: guard.next_draw(now).1.format_balance(2),
+        participants: guard.participants().len(),
+        current_raffle: guard.current_win().map(|w| w.id),
+    };
+    cached(1, status)
+}
+
+#[derive(Serialize)]
+struct PublicParticipant {
+    name: Option<String>,
+    account: String,
+    platform: &'static str,
+}
+
+impl PublicParticipant {
+    fn new(participant: &Participant, with_name: bool) -> Self {
+        Self {
+            name: with_name.then(|| participant.name.clone()),
+            account: mask_account(&participant.account),
+            platform: participant.platform.as_str(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ParticipantsQuery {
+    /// Only returns the registrations of this account, so that viewers can check if they're in
+    account: Option<String>,
+}
+
+async fn get_participants(
+    State(state): State<AppState>,
+    Query(query): Query<ParticipantsQuery>,
+) -> Result<Response, (StatusCode, String)> {
+    let account = match &query.account {
+        Some(a) => Some(
+            Account::decode_account(a)
+                .map_err(|_| (StatusCode::BAD_REQUEST, format!("invalid account: {}", a)))?,
+        ),
+        None => None,
+    };
+
+    let participants: Vec<_> = state
+        .logic
+        .lock()
+        .unwrap()
+        .participants()
+        .iter()
+        .filter(|p| account.is_none_or(|a| p.account == a))
+        .map(|p| PublicParticipant::new(p, state.public_names))
+        .collect();
+    Ok(cached(5, participants))
+}
+
+#[derive(Serialize)]
+struct PublicWinner {
+    raffle_id: u64,
+    /// Unix timestamp in seconds
+    timestamp: u64,
+    name: Option<String>,
+    account: String,
+    prize_xno: String,
+    paid: bool,
+    participant_count: usize,
+}
+
+impl PublicWinner {
+    fn new(record: &WinnerRecord, with_name: bool) -> Self {
+        Self {
+            raffle_id: record.raffle_id,
+            timestamp: record.timestamp,
+            name: with_name.then(|| record.name.clone()),
+            account: mask_account(&record.account),
+            prize_xno: record.prize.format_balance(2),
+            paid: record.paid(),
+            participant_count: record.participant_count,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct WinnersQuery {
+    limit: Option<usize>,
+}
+
+/// The latest winners, newest first
+async fn get_winners(State(state): State<AppState>, Query(query): Query<WinnersQuery>) -> Response {
+    let limit = query.limit.unwrap_or(DEFAULT_WINNERS).min(MAX_WINNERS);
+    let winners: Vec<_> = state
+        .logic
+        .lock()
+        .unwrap()
+        .winners()
+        .all()
+        .iter()
+        .rev()
+        .take(limit)
+        .map(|w| PublicWinner::new(w, state.public_names))
+        .collect();
+    cached(10, winners)
+}
+
+const DEFAULT_WINNERS: usize = 20;
+const MAX_WINNERS: usize = 100;
+
+#[derive(Serialize)]
+struct PublicRaffle {
+    #[serde(flatten)]
+    winner: PublicWinner,
+    entries: Vec<PublicEntry>,
+}
+
+#[derive(Serialize)]
+struct PublicEntry {
+    name: Option<String>,
+    account: String,
+    won: bool,
+}
+
+impl PublicEntry {
+    fn new(entry: &EntryRow, with_name: bool) -> Self {
+        Self {
+            name: with_name.then(|| entry.name.clone()),
+            account: mask_account(&entry.account),
+            won: entry.won,
+        }
+    }
+}
+
+async fn get_raffle(
+    State(state): State<AppState>,
+    Path(id): Path<u64>,
+) -> Result<Response, (StatusCode, String)> {
+    let winner = state
+        .logic
+        .lock()
+        .unwrap()
+        .winners()
+        .get(id)
+        .map(|w| PublicWinner::new(w, state.public_names))
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("unknown raffle: {}", id)))?;
+
+    let entries = state
+        .database
+        .lock()
+        .unwrap()
+        .raffle_entries(id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .iter()
+        .map(|e| PublicEntry::new(e, state.public_names))
+        .collect();
+
+    // A drawn raffle never changes, apart from its payout status
+    Ok(cached(60, PublicRaffle { winner, entries }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mask() {
+        let account = Account::decode_account(
+            "nano_37391u1nrr1j7tdn8w9zathoio5suz9bar18jksqheeiy4obwz3pkgp9aqz6",
+        )
+        .unwrap();
+        assert_eq!(mask_account(&account), "nano_37391...9aqz6");
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::bail;
+use rsnano_core::Amount;
+use rsnano_nullable_clock::Timestamp;
+use std::time::Duration;
+
+/// Name under which quiz winners are recorded
+pub(crate) const QUIZ_RAFFLE: &str = "quiz";
+
+/// A question or secret word. The first participant who answers it in chat wins
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Quiz {
+    pub question: String,
+    answers: Vec<String>,
+    pub prize: Amount,
+    pub deadline: Timestamp,
+}
+
+impl Quiz {
+    /// Several accepted answers are separated by `|`
+    pub(crate) fn new(
+        question: &str,
+        answers: &str,
+        prize: Amount,
+        time_limit: Duration,
+        now: Timestamp,
+    ) -> anyhow::Result<Self> {
+        let answers: Vec<String> = answers
+            .split('|')
+            .map(str::trim)
+            .filter(|a| !words(a).is_empty())
+            .map(str::to_owned)
+            .collect();
+        if answers.is_empty() {
+            bail!("the quiz needs an answer");
+        }
+        if time_limit.is_zero() {
+            bail!("the quiz needs a time limit");
+        }
+        Ok(Self {
+            question: question.trim().to_owned(),
+            answers,
+            prize,
+            deadline: now + time_limit,
+        })
+    }
+
+    /// The answer that is shown if nobody found it
+    pub(crate) fn answer(&self) -> &str {
+        &self.answers[0]
+    }
+
+    pub(crate) fn expired(&self, now: Timestamp) -> bool {
+        now >= self.deadline
+    }
+
+    pub(crate) fn time_left(&self, now: Timestamp) -> Duration {
+        if self.expired(now) {
+            Duration::ZERO
+        } else {
+            self.deadline - now
+        }
+    }
+
+    /// Checks if the message contains one of the answers. Case, punctuation
+    /// and small typos are ignored
+    pub(crate) fn is_answered_by(&self, message: &str) -> bool {
+        let message = words(message);
+        self.answers.iter().any(|answer| {
+            let answer = words(answer).join(" ");
+            let len = answer.split(' ').count();
+            let tolerance = match answer.chars().count() {
+                0..=3 => 0,
+                4..=7 => 1,
+                _ => 2,
+            };
+            message
+                .windows(len.min(message.len()).max(1))
+                .any(|window| edit_distance(&window.join(" "), &answer) <= tolerance)
+        })
+    }
+}
+
+/// Lower case words without punctuation
+fn words(s: &str) -> Vec<String> {
+    s.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiz(answers: &str) -> Quiz {
+        Quiz::new(
+            "?",
+            answers,
+            Amount::nano(1),
+            Duration::from_secs(60),
+            Timestamp::new_test_instance(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fuzzy_answers() {
+        let quiz = quiz("Colin LeMahieu | Colin");
+        assert!(quiz.is_answered_by("colin lemahieu"));
+        assert!(quiz.is_answered_by("I think it's Colin LeMahiue!"));
+        assert!(quiz.is_answered_by("COLIN"));
+        assert!(!quiz.is_answered_by("satoshi"));
+    }
+
+    #[test]
+    fn short_answers_must_match_exactly() {
+        let quiz = quiz("42");
+        assert!(quiz.is_answered_by("it's 42"));
+        assert!(!quiz.is_answered_by("43"));
+        assert!(!quiz.is_answered_by(""));
+    }
+
+    #[test]
+    fn invalid_quiz() {
+        let now = Timestamp::new_test_instance();
+        let limit = Duration::from_secs(60);
+        assert!(Quiz::new("?", " | !", Amount::nano(1), limit, now).is_err());
+        assert!(Quiz::new("?", "a", Amount::nano(1), Duration::ZERO, now).is_err());
+    }
+
+    #[test]
+    fn time_limit() {
+        let quiz = quiz("nano");
+        let now = Timestamp::new_test_instance();
+        assert_eq!(quiz.time_left(now), Duration::from_secs(60));
+        assert!(!quiz.expired(now + Duration::from_secs(59)));
+        assert!(quiz.expired(now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("nano", "nano"), 0);
+    }
+}
@@ -1,35 +1,118 @@
-use crate::participants::ParticipantRegistry;
-use rsnano_core::{Account, Amount};
+use crate::{
+    draw_animation::{sample_visible, AnimationMode},
+    draw_rules::{DrawRules, Shortfall, ShortfallAction},
+    jackpot::{Jackpot, JackpotConfig},
+    named_raffles::MAIN_RAFFLE,
+    overlay_events::{PrizeReveal, SpinInstruction},
+    participants::{Participant, ParticipantRegistry},
+    prize_distribution::PrizeDistribution,
+    schedule::Schedule,
+};
+use chrono::{NaiveDateTime, TimeDelta};
+use rsnano_core::Amount;
 use rsnano_nullable_clock::Timestamp;
 use std::time::Duration;
 
 pub(crate) struct RaffleRunner {
+    name: String,
     next_raffle: Option<Timestamp>,
     prize: Amount,
     interval: Duration,
+    /// `None` chooses the animation by the number of participants
+    animation: Option<AnimationMode>,
+    schedule: Schedule,
+    /// Prize of the next raffle if the schedule overrides it
+    next_prize: Option<Amount>,
+    /// Maps the steady clock to the local time of day, which the schedule needs
+    local_clock: Option<(Timestamp, NaiveDateTime)>,
+    rules: DrawRules,
+    /// Prize of skipped raffles that is added to the next one
+    rolled_over: Amount,
+    /// Whether the postponement of the due raffle was already announced
+    postponed: bool,
+    jackpot: Option<Jackpot>,
+    distribution: PrizeDistribution,
 }
 
 impl Default for RaffleRunner {
     fn default() -> Self {
         Self {
+            name: MAIN_RAFFLE.to_owned(),
             next_raffle: None,
             prize: Amount::nano(1),
             interval: DEFAULT_RAFFLE_INTERVAL,
+            animation: None,
+            schedule: Schedule::default(),
+            next_prize: None,
+            local_clock: None,
+            rules: DrawRules::default(),
+            rolled_over: Amount::raw(0),
+            postponed: false,
+            jackpot: None,
+            distribution: PrizeDistribution::default(),
         }
     }
 }
 
+/// What happened when a raffle was due
 #[derive(Debug, PartialEq, Eq)]
+pub(crate) enum DrawOutcome {
+    Drawn(RaffleResult),
+    Shortfall(Shortfall),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub(crate) struct RaffleResult {
-    pub winner: String,
-    pub participants: Vec<String>,
+    /// Unique id of the raffle
+    pub id: u64,
+    /// Name of the raffle that was drawn
+    pub raffle: String,
+    pub winner: Participant,
+    pub participants: Vec<Participant>,
     pub prize: Amount,
-    pub destination: Account,
+    pub animation: AnimationMode,
+    /// Picked the winner and the prize, recorded so that the draw can be verified
+    pub random: u32,
+    /// Shown after the animation if the prize was drawn at random
+    pub prize_reveal: Option<PrizeReveal>,
+}
+
+impl RaffleResult {
+    pub fn spin_instruction(&self) -> SpinInstruction {
+        let winner = self
+            .participants
+            .iter()
+            .position(|p| p.channel_id == self.winner.channel_id)
+            .unwrap_or_default();
+        let names = self.participants.iter().map(|p| p.name.clone()).collect();
+        // Seeded by the id, so that a resent instruction shows the same names
+        let (participants, winner) =
+            sample_visible(names, winner, self.animation.max_visible(), self.id);
+        SpinInstruction {
+            raffle_id: self.id,
+            raffle: self.raffle.clone(),
+            animation: self.animation,
+            participants,
+            winner,
+            total_participants: self.participants.len(),
+            prize_reveal: self.prize_reveal.clone(),
+        }
+    }
 }
 
 impl RaffleRunner {
     pub fn reset(&mut self) {
         self.next_raffle = None;
+        self.next_prize = None;
+        self.postponed = false;
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: String) {
+        self.name = name;
     }
 
     pub fn set_prize(&mut self, prize: Amount) {
@@ -39,14 +122,105 @@ impl RaffleRunner {
     pub fn next_raffle(&mut self, now: Timestamp) -> Timestamp {
         match self.next_raffle {
             None => {
-                let next = now + self.interval;
+                let (next, prize) = self
+                    .next_scheduled(now)
+                    .unwrap_or((now + self.interval, None));
                 self.next_raffle = Some(next);
+                self.next_prize = prize;
                 next
             }
             Some(next) => next,
         }
     }
 
+    fn next_scheduled(&self, now: Timestamp) -> Option<(Timestamp, Option<Amount>)> {
+        let local = self.local_time(now)?;
+        let slot = self.schedule.next_after(local)?;
+        let wait = (slot.at - local).to_std().ok()?;
+        Some((now + wait, slot.prize))
+    }
+
+    pub fn local_time(&self, now: Timestamp) -> Option<NaiveDateTime> {
+        let (synced, local) = self.local_clock?;
+        if now >= synced {
+            Some(local + TimeDelta::from_std(now - synced).ok()?)
+        } else {
+            Some(local - TimeDelta::from_std(synced - now).ok()?)
+        }
+    }
+
+    pub fn sync_local_time(&mut self, now: Timestamp, local: NaiveDateTime) {
+        self.local_clock = Some((now, local));
+    }
+
+    pub fn schedule(&self) -> &Schedule {
+        &self.schedule
+    }
+
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.schedule = schedule;
+        self.reset();
+    }
+
+    /// Prize of the upcoming raffle
+    pub fn next_prize(&self) -> Amount {
+        let pot = self.jackpot.as_ref().map_or(Amount::raw(0), |j| j.pot());
+        self.next_prize.unwrap_or(self.prize) + self.rolled_over + pot
+    }
+
+    pub fn set_jackpot(&mut self, config: JackpotConfig) {
+        self.jackpot = Some(Jackpot::new(config));
+    }
+
+    pub fn jackpot(&self) -> Option<&Jackpot> {
+        self.jackpot.as_ref()
+    }
+
+    pub fn jackpot_mut(&mut self) -> Option<&mut Jackpot> {
+        self.jackpot.as_mut()
+    }
+
+    /// Limits the pot, so that the next prize doesn't exceed the spendable balance
+    pub fn cap_jackpot(&mut self, balance: Amount) {
+        let prize = self.next_prize.unwrap_or(self.prize) + self.rolled_over;
+        if let Some(jackpot) = &mut self.jackpot {
+            jackpot.cap(Amount::raw(balance.number().saturating_sub(prize.number())));
+        }
+    }
+
+    fn reset_jackpot(&mut self) {
+        if let Some(jackpot) = &mut self.jackpot {
+            jackpot.reset();
+        }
+    }
+
+    /// A jackpot is paid out early once it reaches its threshold
+    fn jackpot_due(&self) -> bool {
+        self.jackpot
+            .as_ref()
+            .is_some_and(|j| j.threshold_reached(self.next_prize()))
+    }
+
+    pub fn draw_rules(&self) -> DrawRules {
+        self.rules
+    }
+
+    pub fn set_draw_rules(&mut self, rules: DrawRules) {
+        self.rules = rules;
+    }
+
+    /// Starts the final draw of the schedule, if it has one
+    pub fn run_final_draw(&mut self, now: Timestamp) -> bool {
+        match self.schedule.final_draw() {
+            Some(draw) => {
+                self.next_raffle = Some(now);
+                self.next_prize = draw.prize;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn raffle_interval(&self) -> Duration {
         self.interval
     }
@@ -55,8 +229,17 @@ impl RaffleRunner {
         self.interval = interval;
     }
 
+    pub fn animation(&self) -> Option<AnimationMode> {
+        self.animation
+    }
+
+    pub fn set_animation(&mut self, animation: Option<AnimationMode>) {
+        self.animation = animation;
+    }
+
     pub fn run_raffle_now(&mut self, now: Timestamp) {
         self.next_raffle = Some(now);
+        self.next_prize = None;
     }
 
     pub fn prize(&self) -> Amount {
@@ -68,25 +251,100 @@ impl RaffleRunner {
         participants: &ParticipantRegistry,
         now: Timestamp,
         random: u32,
-    ) -> Option<RaffleResult> {
+        id: u64,
+    ) -> Option<DrawOutcome> {
         let next_raffle = self.next_raffle(now);
-        let time_for_raffle = now >= next_raffle;
+        let required = self.rules.required();
+        let enough_participants = participants.len() >= required;
+        let time_for_raffle = now >= next_raffle || (enough_participants && self.jackpot_due());
         if !time_for_raffle {
             return None;
         }
 
-        if let Some(winner) = participants.pick_random(random) {
-            self.next_raffle = Some(now + self.raffle_interval());
+        if !enough_participants {
+            return self.handle_shortfall(participants.len(), required, now);
+        }
+
+        let winner = participants.pick_random(random)?;
+        let (prize, prize_reveal) = self.draw_prize(random, id);
+        self.rolled_over = Amount::raw(0);
+        self.reset_jackpot();
+        self.reset();
+        self.next_raffle(now);
 
-            Some(RaffleResult {
-                winner: winner.name,
-                participants: participants.list().drain(..).map(|p| p.name).collect(),
-                prize: self.prize(),
-                destination: winner.account,
-            })
-        } else {
-            None
+        let participants = participants.list();
+        let animation = self
+            .animation
+            .unwrap_or_else(|| AnimationMode::auto(participants.len()));
+        Some(DrawOutcome::Drawn(RaffleResult {
+            id,
+            raffle: self.name.clone(),
+            winner,
+            participants,
+            prize,
+            animation,
+            random,
+            prize_reveal,
+        }))
+    }
+
+    /// A prize set by the schedule is paid as it is, otherwise the prize may be drawn at random
+    fn draw_prize(&self, random: u32, raffle_id: u64) -> (Amount, Option<PrizeReveal>) {
+        if self.next_prize.is_some() || self.distribution.is_fixed() {
+            return (self.next_prize(), None);
+        }
+        let drawn = self.distribution.draw(self.prize, random, raffle_id);
+        let extra = self.next_prize().number() - self.prize.number();
+        let prize = Amount::raw(drawn.amount.number() + extra);
+        let reveal = PrizeReveal {
+            teasers: self
+                .distribution
+                .teasers(self.prize)
+                .iter()
+                .map(|a| a.format_balance(2))
+                .collect(),
+            prize: prize.format_balance(2),
+            big_win: drawn.big_win,
+        };
+        (prize, Some(reveal))
+    }
+
+    pub fn set_prize_distribution(&mut self, distribution: PrizeDistribution) {
+        self.distribution = distribution;
+    }
+
+    fn handle_shortfall(
+        &mut self,
+        participants: usize,
+        required: usize,
+        now: Timestamp,
+    ) -> Option<DrawOutcome> {
+        let action = self.rules.when_not_met;
+        match action {
+            ShortfallAction::Postpone => {
+                // Announce the postponement only once
+                if self.postponed {
+                    return None;
+                }
+                self.postponed = true;
+            }
+            ShortfallAction::RollOver => {
+                // The jackpot keeps its pot, so that it stays persisted until it is won
+                self.rolled_over += self.next_prize.unwrap_or(self.prize);
+                self.reset();
+                self.next_raffle(now);
+            }
+            ShortfallAction::Cancel => {
+                self.reset();
+                self.next_raffle(now);
+            }
         }
+        Some(DrawOutcome::Shortfall(Shortfall {
+            participants,
+            required,
+            action,
+            next_prize: self.next_prize(),
+        }))
     }
 }
 
@@ -0,0 +1,331 @@
+use crate::amounts::parse_xno;
+use anyhow::{anyhow, bail};
+use chrono::{NaiveDateTime, NaiveTime, TimeDelta, Timelike};
+use rsnano_core::Amount;
+use std::{fmt::Display, time::Duration};
+
+/// Key of the schedule in the settings table
+pub(crate) const SCHEDULE_SETTING: &str = "schedule";
+
+/// When the raffles are drawn. An empty schedule falls back to the fixed raffle interval
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Schedule {
+    draws: Vec<ScheduledDraw>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ScheduledDraw {
+    pub when: When,
+    /// Overrides the normal prize
+    pub prize: Option<Amount>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum When {
+    /// Repeats all day or only between two local times
+    Every {
+        interval: Duration,
+        window: Option<(NaiveTime, NaiveTime)>,
+    },
+    /// Every hour at the given minute
+    Hourly {
+        minute: u32,
+    },
+    Daily(NaiveTime),
+    /// A final draw when the operator ends the stream
+    StreamEnd,
+}
+
+/// The next scheduled draw in local time
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Slot {
+    pub at: NaiveDateTime,
+    pub prize: Option<Amount>,
+}
+
+impl Schedule {
+    /// Parses draws separated by `;` or new lines:
+    /// `every 15m [20:00-22:00]`, `hourly [:MM]`, `at HH:MM` or `final`.
+    /// Every draw may end with `prize <XNO>`
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        let draws = s
+            .split([';', '\n'])
+            .map(str::trim)
+            .filter(|d| !d.is_empty())
+            .map(parse_draw)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if draws.iter().filter(|d| d.when == When::StreamEnd).count() > 1 {
+            bail!("only one final draw is allowed");
+        }
+        Ok(Self { draws })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.draws.is_empty()
+    }
+
+    /// The first timed draw after `now`. If several draws fall on the same
+    /// time, the one with a special prize wins
+    pub(crate) fn next_after(&self, now: NaiveDateTime) -> Option<Slot> {
+        let mut next: Option<Slot> = None;
+        for draw in &self.draws {
+            let Some(at) = draw.when.next_after(now) else {
+                continue;
+            };
+            let better = match &next {
+                None => true,
+                Some(slot) => at < slot.at || (at == slot.at && slot.prize.is_none()),
+            };
+            if better {
+                next = Some(Slot {
+                    at,
+                    prize: draw.prize,
+                });
+            }
+        }
+        next
+    }
+
+    pub(crate) fn final_draw(&self) -> Option<&ScheduledDraw> {
+        self.draws.iter().find(|d| d.when == When::StreamEnd)
+    }
+}
+
+impl When {
+    fn next_after(&self, now: NaiveDateTime) -> Option<NaiveDateTime> {
+        match *self {
+            When::Every { interval, window } => next_every(now, interval, window),
+            When::Hourly { minute } => {
+                let at = now.date().and_hms_opt(now.hour(), minute, 0)?;
+                Some(if at > now {
+                    at
+                } else {
+                    at + TimeDelta::hours(1)
+                })
+            }
+            When::Daily(time) => {
+                let at = now.date().and_time(time);
+                Some(if at > now {
+                    at
+                } else {
+                    at + TimeDelta::days(1)
+                })
+            }
+            When::StreamEnd => None,
+        }
+    }
+}
+
+fn next_every(
+    now: NaiveDateTime,
+    interval: Duration,
+    window: Option<(NaiveTime, NaiveTime)>,
+) -> Option<NaiveDateTime> {
+    let interval = TimeDelta::from_std(interval).ok()?;
+    let (from, until) = window.unwrap_or((NaiveTime::MIN, NaiveTime::MIN));
+    let mut length = until.signed_duration_since(from);
+    if length <= TimeDelta::zero() {
+        // The window wraps around midnight
+        length += TimeDelta::days(1);
+    }
+
+    // Yesterday's window may still be open after midnight
+    [-1, 0, 1]
+        .into_iter()
+        .filter_map(|day| {
+            let start = (now.date() + TimeDelta::days(day)).and_time(from);
+            let end = start + length;
+            let steps = if now < start {
+                0
+            } else {
+                (now - start).num_milliseconds() / interval.num_milliseconds() + 1
+            };
+            let at = start + interval * i32::try_from(steps).ok()?;
+            // Without a window the next day starts with its own draw at midnight
+            let open = if window.is_some() {
+                at <= end
+            } else {
+                at < end
+            };
+            open.then_some(at)
+        })
+        .min()
+}
+
+fn parse_draw(s: &str) -> anyhow::Result<ScheduledDraw> {
+    let mut words: Vec<&str> = s.split_whitespace().collect();
+    let prize = match words.as_slice() {
+        [.., "prize", prize] => {
+            Some(parse_xno(prize).map_err(|_| anyhow!("invalid prize: {}", prize))?)
+        }
+        _ => None,
+    };
+    if prize.is_some() {
+        words.truncate(words.len() - 2);
+    }
+
+    let when = match words.as_slice() {
+        ["every", interval] => When::Every {
+            interval: parse_interval(interval)?,
+            window: None,
+        },
+        ["every", interval, window] => {
+            let (from, until) = window
+                .split_once('-')
+                .ok_or_else(|| anyhow!("invalid time window: {}", window))?;
+            When::Every {
+                interval: parse_interval(interval)?,
+                window: Some((parse_time(from)?, parse_time(until)?)),
+            }
+        }
+        ["hourly"] => When::Hourly { minute: 0 },
+        ["hourly", minute] => {
+            let minute = minute
+                .strip_prefix(':')
+                .and_then(|m| m.parse().ok())
+                .filter(|m| *m < 60)
+                .ok_or_else(|| anyhow!("invalid minute: {}", minute))?;
+            When::Hourly { minute }
+        }
+        ["at", time] => When::Daily(parse_time(time)?),
+        ["final"] => When::StreamEnd,
+        _ => bail!("invalid scheduled draw: {}", s),
+    };
+    Ok(ScheduledDraw { when, prize })
+}
+
+/// Parses `30s`, `15m` or `2h`
+fn parse_interval(s: &str) -> anyhow::Result<Duration> {
+    let invalid = || anyhow!("invalid interval: {}", s);
+    let value = s.strip_suffix(['s', 'm', 'h']).ok_or_else(invalid)?;
+    let unit = &s[value.len()..];
+    let value: u64 = value.parse().map_err(|_| invalid())?;
+    let secs = match unit {
+        "s" => value,
+        "m" => value.saturating_mul(60),
+        "h" => value.saturating_mul(3600),
+        _ => return Err(invalid()),
+    };
+    if secs == 0 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(secs))
+}
+
+fn parse_time(s: &str) -> anyhow::Result<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M").map_err(|_| anyhow!("invalid time: {}", s))
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, draw) in self.draws.iter().enumerate() {
+            if i > 0 {
+                write!(f, "; ")?;
+            }
+            match draw.when {
+                When::Every { interval, window } => {
+                    let secs = interval.as_secs();
+                    if secs % 3600 == 0 {
+                        write!(f, "every {}h", secs / 3600)?;
+                    } else if secs % 60 == 0 {
+                        write!(f, "every {}m", secs / 60)?;
+                    } else {
+                        write!(f, "every {}s", secs)?;
+                    }
+                    if let Some((from, until)) = window {
+                        write!(f, " {}-{}", from.format("%H:%M"), until.format("%H:%M"))?;
+                    }
+                }
+                When::Hourly { minute } => write!(f, "hourly :{:02}", minute)?,
+                When::Daily(time) => write!(f, "at {}", time.format("%H:%M"))?,
+                When::StreamEnd => write!(f, "final")?,
+            }
+            if let Some(prize) = draw.prize {
+                write!(f, " prize {}", format_exact(prize))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats the amount in XNO without rounding, so that it parses back to the same amount
+fn format_exact(amount: Amount) -> String {
+    const XNO: u128 = 10u128.pow(30);
+    let raw = amount.number();
+    let fraction = raw % XNO;
+    if fraction == 0 {
+        (raw / XNO).to_string()
+    } else {
+        let fraction = format!("{:030}", fraction);
+        format!("{}.{}", raw / XNO, fraction.trim_end_matches('0'))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(&format!("2025-03-01 {}", s), "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn parse_and_display() {
+        let schedule =
+            Schedule::parse("every 15m 20:00-22:00; hourly prize 5\nat 21:30; final prize 10.5")
+                .unwrap();
+        assert_eq!(
+            schedule.to_string(),
+            "every 15m 20:00-22:00; hourly :00 prize 5; at 21:30; final prize 10.5"
+        );
+        assert_eq!(Schedule::parse(&schedule.to_string()).unwrap(), schedule);
+        assert_eq!(
+            schedule.final_draw().unwrap().prize,
+            Some(parse_xno("10.5").unwrap())
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Schedule::parse("every 0m").is_err());
+        assert!(Schedule::parse("every 15x").is_err());
+        assert!(Schedule::parse("hourly :60").is_err());
+        assert!(Schedule::parse("at 25:00").is_err());
+        assert!(Schedule::parse("final; final").is_err());
+        assert!(Schedule::parse("sometimes").is_err());
+    }
+
+    #[test]
+    fn every_within_window() {
+        let schedule = Schedule::parse("every 15m 20:00-22:00").unwrap();
+        let next = |now| schedule.next_after(time(now)).unwrap().at;
+        assert_eq!(next("12:00:00"), time("20:00:00"));
+        assert_eq!(next("20:00:00"), time("20:15:00"));
+        assert_eq!(next("21:50:00"), time("22:00:00"));
+        assert_eq!(next("22:00:00"), time("20:00:00") + TimeDelta::days(1));
+    }
+
+    #[test]
+    fn window_over_midnight() {
+        let schedule = Schedule::parse("every 1h 23:00-01:00").unwrap();
+        assert_eq!(
+            schedule.next_after(time("00:30:00")).unwrap().at,
+            time("01:00:00")
+        );
+    }
+
+    #[test]
+    fn special_prize_wins_on_same_time() {
+        let schedule = Schedule::parse("every 15m; hourly prize 5").unwrap();
+        let slot = schedule.next_after(time("20:50:00")).unwrap();
+        assert_eq!(slot.at, time("21:00:00"));
+        assert_eq!(slot.prize, Some(Amount::nano(5)));
+        assert_eq!(schedule.next_after(time("21:00:00")).unwrap().prize, None);
+    }
+
+    #[test]
+    fn final_draw_is_not_timed() {
+        let schedule = Schedule::parse("final").unwrap();
+        assert_eq!(schedule.next_after(time("20:00:00")), None);
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::anyhow;
+use std::time::Duration;
+
+/// What happens with a drawn winner when no spinner confirms the spin.
+/// The timeout counts from the start of the spin while no spinner is connected.
+/// A connected spinner gets at least two minutes to finish the animation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SpinnerFallback {
+    /// Confirm the spin automatically after the given time
+    AutoConfirm(Duration),
+    /// Keep waiting for the spinner, but alert the operator after the given time
+    HoldAndAlert(Duration),
+    /// Wait until the operator confirms the winner in the GUI
+    ManualConfirm,
+}
+
+impl Default for SpinnerFallback {
+    fn default() -> Self {
+        Self::HoldAndAlert(DEFAULT_TIMEOUT)
+    }
+}
+
+impl SpinnerFallback {
+    /// Reads the policy from the `SPINNER_FALLBACK` env var
+    pub(crate) fn from_env() -> anyhow::Result<Self> {
+        match std::env::var("SPINNER_FALLBACK") {
+            Ok(s) => Self::parse(&s),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Parses `auto[:SECS]`, `hold[:SECS]` or `manual`
+    pub(crate) fn parse(s: &str) -> anyhow::Result<Self> {
+        let (policy, secs) = match s.split_once(':') {
+            Some((policy, secs)) => (policy, Some(secs)),
+            None => (s, None),
+        };
+        let timeout = match secs {
+            Some(secs) => Duration::from_secs(
+                secs.parse()
+                    .map_err(|_| anyhow!("invalid spinner timeout: {}", secs))?,
+            ),
+            None => DEFAULT_TIMEOUT,
+        };
+        match (policy, secs) {
+            ("auto", _) => Ok(Self::AutoConfirm(timeout)),
+            ("hold", _) => Ok(Self::HoldAndAlert(timeout)),
+            ("manual", None) => Ok(Self::ManualConfirm),
+            _ => Err(anyhow!("invalid spinner fallback: {}", s)),
+        }
+    }
+}
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(
+            SpinnerFallback::parse("auto").unwrap(),
+            SpinnerFallback::AutoConfirm(DEFAULT_TIMEOUT)
+        );
+        assert_eq!(
+            SpinnerFallback::parse("auto:10").unwrap(),
+            SpinnerFallback::AutoConfirm(Duration::from_secs(10))
+        );
+        assert_eq!(
+            SpinnerFallback::parse("hold:5").unwrap(),
+            SpinnerFallback::HoldAndAlert(Duration::from_secs(5))
+        );
+        assert_eq!(
+            SpinnerFallback::parse("manual").unwrap(),
+            SpinnerFallback::ManualConfirm
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(SpinnerFallback::parse("auto:x").is_err());
+        assert!(SpinnerFallback::parse("manual:5").is_err());
+        assert!(SpinnerFallback::parse("later").is_err());
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::Context;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Colors, fonts and logo of the overlay. Missing fields use the RsNano defaults
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Theme {
+    /// Background colors of the wheel segments, repeated in this order
+    pub segment_colors: Vec<String>,
+    /// Label colors of the wheel segments, repeated in this order
+    pub label_colors: Vec<String>,
+    pub font_family: String,
+    /// Used if the font isn't installed, e.g. `/assets/fonts/MyFont.woff2`
+    pub font_url: Option<String>,
+    /// Size of the wheel relative to the overlay, between 0 and 1
+    pub radius: f64,
+    pub rim_color: String,
+    pub rim_shadow_color: String,
+    pub hub_color: String,
+    pub hub_shadow_color: String,
+    /// Color of the lights on the rim
+    pub light_color: String,
+    /// Image that is shown in the center of the wheel
+    pub logo_url: Option<String>,
+    pub background: String,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            segment_colors: vec!["#c7160c".to_owned(), "#fff".to_owned()],
+            label_colors: vec!["#fff".to_owned(), "#000".to_owned()],
+            font_family: "Pragati Narrow".to_owned(),
+            font_url: None,
+            radius: 0.88,
+            rim_color: "#c50000".to_owned(),
+            rim_shadow_color: "#720000".to_owned(),
+            hub_color: "#c00".to_owned(),
+            hub_shadow_color: "#840000".to_owned(),
+            light_color: "#fdff57".to_owned(),
+            logo_url: None,
+            background: "transparent".to_owned(),
+        }
+    }
+}
+
+/// Where the theme comes from. The file is read on every request, so that
+/// the theme can be changed while the bot is running
+#[derive(Default)]
+pub(crate) struct ThemeSource {
+    path: Option<PathBuf>,
+}
+
+impl ThemeSource {
+    /// Uses the file from the `RAFFLE_THEME` env var or `theme.json` if it exists
+    pub(crate) fn from_env() -> Self {
+        let path = match std::env::var_os("RAFFLE_THEME") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(PathBuf::from(DEFAULT_THEME_FILE)).filter(|p| p.exists()),
+        };
+        Self { path }
+    }
+
+    pub(crate) fn load(&self) -> Theme {
+        let Some(path) = &self.path else {
+            return Theme::default();
+        };
+        match read_theme(path) {
+            Ok(theme) => theme,
+            Err(e) => {
+                warn!("Using default theme: {:?}", e);
+                Theme::default()
+            }
+        }
+    }
+}
+
+const DEFAULT_THEME_FILE: &str = "theme.json";
+
+fn read_theme(path: &Path) -> anyhow::Result<Theme> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read theme {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("invalid theme {}", path.display()))
+}
+
+/// Replaces the `{{placeholders}}` in the HTML page
+pub(crate) fn render_html(template: &str, theme: &Theme, preview: bool) -> String {
+    // "</" would end the script tag the JSON is embedded in
+    let theme_json = serde_json::to_string(theme)
+        .unwrap_or_default()
+        .replace("</", "<\\/");
+    let font_src = match &theme.font_url {
+        Some(url) => format!(
+            "local('{0}'), url('{1}')",
+            escape_css(&theme.font_family),
+            escape_css(url)
+        ),
+        None => format!("local('{}')", escape_css(&theme.font_family)),
+    };
+    render(
+        template,
+        &[
+            ("theme_json", theme_json),
+            ("preview", preview.to_string()),
+            ("font_family", escape_css(&theme.font_family)),
+            ("font_src", font_src),
+            ("background", css_value(&theme.background)),
+        ],
+    )
+}
+
+/// Replaces the `{{placeholders}}` in the overlay SVG
+pub(crate) fn render_svg(template: &str, theme: &Theme) -> String {
+    render(
+        template,
+        &[
+            ("rim_color", escape_xml(&theme.rim_color)),
+            ("rim_shadow_color", escape_xml(&theme.rim_shadow_color)),
+            ("hub_color", escape_xml(&theme.hub_color)),
+            ("hub_shadow_color", escape_xml(&theme.hub_shadow_color)),
+            ("light_color", escape_xml(&theme.light_color)),
+        ],
+    )
+}
+
+fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut result = template.to_owned();
+    for (key, value) in values {
+        result = result.replace(&format!("{{{{{}}}}}", key), value);
+    }
+    result
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Values end up in quoted CSS strings inside a style tag
+fn escape_css(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('\'', "\\'")
+        .replace('"', "\\\"")
+        .replace('<', "\\3c ")
+}
+
+/// Removes everything that could end an unquoted CSS value
+fn css_value(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !matches!(c, ';' | '{' | '}' | '<' | '>' | '\'' | '"' | '\\'))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_theme_uses_defaults() {
+        let theme: Theme = serde_json::from_str(r##"{"light_color": "#00f"}"##).unwrap();
+        assert_eq!(theme.light_color, "#00f");
+        assert_eq!(theme.segment_colors, Theme::default().segment_colors);
+    }
+
+    #[test]
+    fn render_svg_colors() {
+        let theme = Theme {
+            light_color: "#00f\"/>".to_owned(),
+            ..Theme::default()
+        };
+        assert_eq!(
+            render_svg("fill:{{light_color}};fill:{{hub_color}}", &theme),
+            "fill:#00f&quot;/&gt;;fill:#c00"
+        );
+    }
+
+    #[test]
+    fn render_html_escapes_script() {
+        let theme = Theme {
+            font_family: "</script>".to_owned(),
+            ..Theme::default()
+        };
+        let html = render_html("{{theme_json}} {{preview}}", &theme, true);
+        assert!(!html.contains("</script>"));
+        assert!(html.ends_with(" true"));
+    }
+
+    #[test]
+    fn strip_css_value() {
+        assert_eq!(css_value("#000;} body {color: red"), "#000 body color: red");
+    }
+}
@@ -1,16 +1,21 @@
-use log::{debug, info};
+use log::{debug, info, warn};
+use std::{sync::Arc, time::Instant};
 use twitch_irc::{
     login::StaticLoginCredentials, message::ServerMessage, ClientConfig, SecureTCPTransport,
     TwitchIRCClient,
 };
 
-use crate::chat_messages::ChatMessage;
+use crate::{
+    chat_messages::{ChatMessage, Platform},
+    metrics::Metrics,
+};
 
-pub(crate) async fn listen_to_twitch_chat<F>(on_message: F)
+pub(crate) async fn listen_to_twitch_chat<F>(on_message: F, metrics: Arc<Metrics>)
 where
     F: Fn(ChatMessage) + Send + Sync,
 {
     info!("Connecting to Twitch chat...");
+    metrics.chat_source_started(Platform::Twitch);
     // default configuration is to join chat as anonymous.
     let config = ClientConfig::default();
     let (mut incoming_messages, client) =
@@ -24,13 +29,18 @@ where
 
     info!("Twitch chat connected!");
     while let Some(message) = incoming_messages.recv().await {
+        // Every message counts, including the pings of the server
+        metrics.chat_source_seen(Platform::Twitch, Instant::now());
         if let ServerMessage::Privmsg(msg) = message {
             debug!("Received message from twitch");
             on_message(ChatMessage {
                 author_channel_id: format!("twitch-{}", msg.sender.name),
                 author_name: Some(msg.sender.name),
                 message: msg.message_text,
+                platform: Platform::Twitch,
             })
         }
     }
+    warn!("Twitch chat disconnected");
+    metrics.chat_source_lost(Platform::Twitch);
 }
@@ -0,0 +1,185 @@
+use crate::chat_messages::Platform;
+use rsnano_core::{Account, Amount};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Everything we know about a single win
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub(crate) struct WinnerRecord {
+    pub raffle_id: u64,
+    pub stream_id: i64,
+    pub name: String,
+    pub channel_id: String,
+    pub platform: Platform,
+    pub account: Account,
+    pub prize: Amount,
+    /// Unix timestamp in seconds
+    pub timestamp: u64,
+    pub participant_count: usize,
+    /// Hash of the send block. None if the payout failed
+    pub block_hash: Option<String>,
+}
+
+impl WinnerRecord {
+    pub fn paid(&self) -> bool {
+        self.block_hash.is_some()
+    }
+
+    #[cfg(test)]
+    pub fn new_test_instance() -> Self {
+        Self {
+            raffle_id: 1,
+            stream_id: 1,
+            name: "John Doe".to_owned(),
+            channel_id: "abc".to_owned(),
+            platform: Platform::YouTube,
+            account: Account::from(42),
+            prize: Amount::nano(1),
+            timestamp: 1000,
+            participant_count: 10,
+            block_hash: Some("ABC".to_owned()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Default)]
+pub(crate) struct WinnerStats {
+    pub draws: usize,
+    pub unique_winners: usize,
+    pub total_paid: Amount,
+    pub average_participants: f64,
+}
+
+impl WinnerStats {
+    pub fn from_records<'a>(records: impl IntoIterator<Item = &'a WinnerRecord>) -> Self {
+        let mut draws = 0;
+        let mut total_participants = 0;
+        let mut total_paid = 0u128;
+        let mut winners = HashSet::new();
+        for record in records {
+            draws += 1;
+            total_participants += record.participant_count;
+            winners.insert(record.channel_id.as_str());
+            if record.paid() {
+                total_paid += record.prize.number();
+            }
+        }
+
+        Self {
+            draws,
+            unique_winners: winners.len(),
+            total_paid: Amount::raw(total_paid),
+            average_participants: if draws == 0 {
+                0.0
+            } else {
+                total_participants as f64 / draws as f64
+            },
+        }
+    }
+}
+
+/// All winners, grouped into stream sessions
+#[derive(Default)]
+pub(crate) struct WinnerHistory {
+    records: Vec<WinnerRecord>,
+    current_stream: i64,
+}
+
+impl WinnerHistory {
+    pub fn set(&mut self, records: Vec<WinnerRecord>) {
+        self.records = records;
+    }
+
+    pub fn set_current_stream(&mut self, stream_id: i64) {
+        self.current_stream = stream_id;
+    }
+
+    pub fn add(&mut self, record: WinnerRecord) {
+        self.records.push(record);
+    }
+
+    pub fn get(&self, raffle_id: u64) -> Option<&WinnerRecord> {
+        self.records.iter().find(|r| r.raffle_id == raffle_id)
+    }
+
+    pub fn set_block_hash(&mut self, raffle_id: u64, block_hash: String) {
+        if let Some(record) = self.records.iter_mut().find(|r| r.raffle_id == raffle_id) {
+            record.block_hash = Some(block_hash);
+        }
+    }
+
+    pub fn all(&self) -> &[WinnerRecord] {
+        &self.records
+    }
+
+    pub fn in_current_stream(&self) -> impl Iterator<Item = &WinnerRecord> {
+        self.records
+            .iter()
+            .filter(|r| r.stream_id == self.current_stream)
+    }
+
+    pub fn stream_stats(&self) -> WinnerStats {
+        WinnerStats::from_records(self.in_current_stream())
+    }
+
+    pub fn all_time_stats(&self) -> WinnerStats {
+        WinnerStats::from_records(&self.records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_stats() {
+        let history = WinnerHistory::default();
+        assert_eq!(history.all_time_stats(), WinnerStats::default());
+    }
+
+    #[test]
+    fn stats() {
+        let mut history = WinnerHistory::default();
+        history.add(WinnerRecord::new_test_instance());
+        history.add(WinnerRecord {
+            raffle_id: 2,
+            participant_count: 20,
+            ..WinnerRecord::new_test_instance()
+        });
+        history.add(WinnerRecord {
+            raffle_id: 3,
+            channel_id: "other".to_owned(),
+            participant_count: 30,
+            block_hash: None,
+            ..WinnerRecord::new_test_instance()
+        });
+
+        assert_eq!(
+            history.all_time_stats(),
+            WinnerStats {
+                draws: 3,
+                unique_winners: 2,
+                total_paid: Amount::nano(2),
+                average_participants: 20.0,
+            }
+        );
+    }
+
+    #[test]
+    fn stats_per_stream() {
+        let mut history = WinnerHistory::default();
+        history.set(vec![
+            WinnerRecord::new_test_instance(),
+            WinnerRecord {
+                raffle_id: 2,
+                stream_id: 2,
+                ..WinnerRecord::new_test_instance()
+            },
+        ]);
+        history.set_current_stream(2);
+
+        assert_eq!(history.stream_stats().draws, 1);
+        assert_eq!(history.all_time_stats().draws, 2);
+        assert_eq!(history.in_current_stream().next().unwrap().raffle_id, 2);
+    }
+}
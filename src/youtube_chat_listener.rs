@@ -1,9 +1,15 @@
-use crate::chat_messages::ChatMessage;
+use crate::{
+    chat_messages::{ChatMessage, Platform},
+    metrics::Metrics,
+};
 use gauth::app::Auth;
 use log::{debug, error, info, warn};
 use reqwest::header::AUTHORIZATION;
 use serde::Deserialize;
-use std::time::Duration;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tokio::time::sleep;
 
 #[derive(Deserialize, Debug)]
@@ -58,11 +64,12 @@ struct MessageSnippet {
 struct AuthorDetails {
     display_name: String,
 }
-pub(crate) async fn listen_to_youtube_chat<F>(on_message: F)
+pub(crate) async fn listen_to_youtube_chat<F>(on_message: F, metrics: Arc<Metrics>)
 where
     F: Fn(ChatMessage) + Send + Sync,
 {
     info!("Connecting to YouTube chat...");
+    metrics.chat_source_started(Platform::YouTube);
     let token = get_auth_token().await.unwrap();
     let youtube_client = YouTubeClient::new(token.clone());
     let broadcasts = youtube_client.get_my_live_broadcasts().await.unwrap();
@@ -85,6 +92,7 @@ where
 
         match response {
             Ok(response) => {
+                metrics.chat_source_seen(Platform::YouTube, Instant::now());
                 page_token = response.next_page_token;
 
                 let item_count = response.items.len();
@@ -115,6 +123,7 @@ impl From<MessageItem> for ChatMessage {
             author_channel_id: value.snippet.author_channel_id,
             author_name: Some(value.author_details.display_name),
             message: value.snippet.display_message,
+            platform: Platform::YouTube,
         }
     }
 }